@@ -0,0 +1,102 @@
+use anchor_client::{Client, Cluster};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signer::Signer};
+use std::ops::Deref;
+
+pub const LOCALNET_RPC_URL: &str = "http://127.0.0.1:8899";
+
+// Set by `anchor test` to the validator it started
+pub const PROVIDER_URL_VAR: &str = "ANCHOR_PROVIDER_URL";
+
+// The cluster to connect to and the commitment to read and confirm at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterConfig {
+    pub rpc_url: String,
+    pub ws_url: String,
+    pub commitment: CommitmentConfig,
+}
+
+impl Default for ClusterConfig {
+    fn default() -> Self {
+        Self::localnet()
+    }
+}
+
+impl ClusterConfig {
+    // `rpc_url` at confirmed commitment, with the websocket on the next port
+    pub fn new(rpc_url: &str) -> Self {
+        Self {
+            rpc_url: rpc_url.to_string(),
+            ws_url: websocket_url(rpc_url),
+            commitment: CommitmentConfig::confirmed(),
+        }
+    }
+
+    pub fn localnet() -> Self {
+        Self::new(LOCALNET_RPC_URL)
+    }
+
+    // The validator `anchor test` started, or a local one
+    pub fn from_env() -> Self {
+        match std::env::var(PROVIDER_URL_VAR) {
+            Ok(url) if !url.is_empty() => Self::new(&url),
+            _ => Self::localnet(),
+        }
+    }
+
+    pub fn with_commitment(self, commitment: CommitmentConfig) -> Self {
+        Self { commitment, ..self }
+    }
+
+    pub fn cluster(&self) -> Cluster {
+        Cluster::Custom(self.rpc_url.clone(), self.ws_url.clone())
+    }
+
+    pub fn client<C: Clone + Deref<Target = impl Signer>>(&self, payer: C) -> Client<C> {
+        Client::new_with_options(self.cluster(), payer, self.commitment)
+    }
+
+    pub fn rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment)
+    }
+}
+
+// The websocket URL the Solana CLI derives from an RPC URL: same host with
+// the ws(s) scheme, and the port after the RPC port if one is given
+pub fn websocket_url(rpc_url: &str) -> String {
+    let (scheme, rest) = match rpc_url.split_once("://") {
+        Some(("https", rest)) => ("wss", rest),
+        Some((_, rest)) => ("ws", rest),
+        None => ("ws", rpc_url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(at) => rest.split_at(at),
+        None => (rest, ""),
+    };
+    let authority = match authority.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => format!("{host}:{}", port.wrapping_add(1)),
+            Err(_) => authority.to_string(),
+        },
+        None => authority.to_string(),
+    };
+    format!("{scheme}://{authority}{path}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_websocket_urls() {
+        assert_eq!(websocket_url(LOCALNET_RPC_URL), "ws://127.0.0.1:8900");
+        assert_eq!(
+            websocket_url("https://api.devnet.solana.com"),
+            "wss://api.devnet.solana.com"
+        );
+        assert_eq!(
+            websocket_url("http://localhost:9000/rpc"),
+            "ws://localhost:9001/rpc"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::{
+    commitment_config::CommitmentLevel, pubkey::Pubkey, signature::Signature, signer::SignerError,
+    transaction::TransactionError,
+};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    // Boxed, as the RPC client's error is much larger than the others
+    #[error("RPC request failed: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("Error signing transaction: {0}")]
+    Signer(#[from] SignerError),
+    // Raised by callers building their own transactions to send
+    #[error("Error building transaction: {0}")]
+    Build(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Transaction {signature} failed: {error}")]
+    TransactionFailed {
+        signature: Signature,
+        error: TransactionError,
+    },
+    #[error("Transaction {signature} was not {commitment:?} after {elapsed:?}")]
+    ConfirmationTimeout {
+        signature: Signature,
+        commitment: CommitmentLevel,
+        elapsed: Duration,
+    },
+    #[error("Transaction expired {attempts} time(s) before it landed")]
+    BlockhashExpired { attempts: u32 },
+    #[error("Airdrop of {lamports} lamports to {pubkey} did not arrive; balance is {balance}")]
+    AirdropTimeout {
+        pubkey: Pubkey,
+        lamports: u64,
+        balance: u64,
+    },
+}
+
+impl From<ClientError> for Error {
+    fn from(error: ClientError) -> Self {
+        Self::Rpc(Box::new(error))
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
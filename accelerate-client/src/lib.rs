@@ -0,0 +1,12 @@
+// Helpers for talking to a cluster from the projects' validator tests and the
+// voting client: where to connect, funding wallets with airdrops, and sending
+// and confirming transactions without fixed sleeps. They work with
+// anchor-client's `async` feature, which the root workspace enables.
+pub mod cluster;
+pub mod error;
+pub mod nonblocking;
+pub mod retry;
+
+pub use cluster::ClusterConfig;
+pub use error::{Error, Result};
+pub use retry::Backoff;
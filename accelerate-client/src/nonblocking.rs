@@ -0,0 +1,289 @@
+// For anchor-client's `Program::rpc()` with its `async` feature
+use crate::{Backoff, Error, Result};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, pubkey::Pubkey,
+    signature::Signature, signer::Signer, transaction::Transaction,
+};
+use std::{future::Future, time::Instant};
+use tokio::time::sleep;
+
+// Signing with a new blockhash after the previous one expired
+pub const MAX_SEND_ATTEMPTS: u32 = 3;
+
+// Poll until `signature` reaches `commitment`, failing if the transaction
+// failed or the backoff runs out first
+pub async fn confirm(
+    rpc: &RpcClient,
+    signature: &Signature,
+    commitment: CommitmentConfig,
+    backoff: &Backoff,
+) -> Result<()> {
+    let started = Instant::now();
+    let mut delays = backoff.delays();
+    loop {
+        match rpc
+            .get_signature_status_with_commitment(signature, commitment)
+            .await?
+        {
+            Some(Ok(())) => return Ok(()),
+            Some(Err(error)) => {
+                return Err(Error::TransactionFailed {
+                    signature: *signature,
+                    error,
+                })
+            }
+            None => match delays.next() {
+                Some(delay) => sleep(delay).await,
+                None => {
+                    return Err(Error::ConfirmationTimeout {
+                        signature: *signature,
+                        commitment: commitment.commitment,
+                        elapsed: started.elapsed(),
+                    })
+                }
+            },
+        }
+    }
+}
+
+// Airdrop `lamports` to `pubkey` and wait until the balance shows them
+pub async fn airdrop(
+    rpc: &RpcClient,
+    pubkey: &Pubkey,
+    lamports: u64,
+    commitment: CommitmentConfig,
+    backoff: &Backoff,
+) -> Result<Signature> {
+    let before = rpc
+        .get_balance_with_commitment(pubkey, commitment)
+        .await?
+        .value;
+    let signature = rpc.request_airdrop(pubkey, lamports).await?;
+    confirm(rpc, &signature, commitment, backoff).await?;
+
+    // The balance can trail the signature status on some RPC nodes
+    let mut delays = backoff.delays();
+    loop {
+        let balance = rpc
+            .get_balance_with_commitment(pubkey, commitment)
+            .await?
+            .value;
+        if balance >= before.saturating_add(lamports) {
+            return Ok(signature);
+        }
+        match delays.next() {
+            Some(delay) => sleep(delay).await,
+            None => {
+                return Err(Error::AirdropTimeout {
+                    pubkey: *pubkey,
+                    lamports,
+                    balance,
+                })
+            }
+        }
+    }
+}
+
+// Sign `instructions` with the latest blockhash, send them and wait for
+// `commitment`. If the blockhash expires before the transaction lands, it is
+// signed again with a new one, up to `MAX_SEND_ATTEMPTS` times
+pub async fn send_and_confirm(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&dyn Signer],
+    commitment: CommitmentConfig,
+    backoff: &Backoff,
+) -> Result<Signature> {
+    let sign = || async move {
+        let blockhash = rpc
+            .get_latest_blockhash_with_commitment(commitment)
+            .await?
+            .0;
+        let mut tx = Transaction::new_with_payer(instructions, Some(payer));
+        tx.try_sign(signers, blockhash)?;
+        Ok(tx)
+    };
+    send_signed_and_confirm(rpc, commitment, backoff, MAX_SEND_ATTEMPTS, sign)
+        .await
+        .result
+}
+
+// How many transactions `send_signed_and_confirm` sent, and how it ended
+#[derive(Debug)]
+pub struct Sent {
+    pub attempts: u32,
+    pub result: Result<Signature>,
+}
+
+// Send the transaction `sign` returns and wait for `commitment`. Only once its
+// blockhash has expired without it landing is `sign` called for another, up
+// to `max_attempts` in all, so the same instructions can never land twice.
+// `sign` is free to build the transaction anew each time, e.g. to price it for
+// the current fees
+pub async fn send_signed_and_confirm<F, Fut>(
+    rpc: &RpcClient,
+    commitment: CommitmentConfig,
+    backoff: &Backoff,
+    max_attempts: u32,
+    mut sign: F,
+) -> Sent
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Transaction>>,
+{
+    let config = RpcSendTransactionConfig {
+        preflight_commitment: Some(commitment.commitment),
+        ..RpcSendTransactionConfig::default()
+    };
+    let mut attempts = 0;
+    while attempts < max_attempts.max(1) {
+        attempts += 1;
+        let result = match sign().await {
+            Ok(tx) => send_until_expired(rpc, &tx, config, commitment, backoff).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Err(Error::BlockhashExpired { .. }) => {}
+            result => return Sent { attempts, result },
+        }
+    }
+    Sent {
+        attempts,
+        result: Err(Error::BlockhashExpired { attempts }),
+    }
+}
+
+// Send `tx` and wait for `commitment`, failing with `BlockhashExpired` once
+// its blockhash has expired without it landing
+async fn send_until_expired(
+    rpc: &RpcClient,
+    tx: &Transaction,
+    config: RpcSendTransactionConfig,
+    commitment: CommitmentConfig,
+    backoff: &Backoff,
+) -> Result<Signature> {
+    let signature = rpc.send_transaction_with_config(tx, config).await?;
+    let started = Instant::now();
+    let mut delays = backoff.delays();
+    loop {
+        // Check the blockhash first: a transaction not found once its
+        // blockhash has expired can no longer land
+        let valid = rpc
+            .is_blockhash_valid(&tx.message.recent_blockhash, commitment)
+            .await?;
+        match rpc
+            .get_signature_status_with_commitment(&signature, commitment)
+            .await?
+        {
+            Some(Ok(())) => return Ok(signature),
+            Some(Err(error)) => return Err(Error::TransactionFailed { signature, error }),
+            None if !valid => return Err(Error::BlockhashExpired { attempts: 1 }),
+            None => {}
+        }
+        let delay = delays.next().ok_or(Error::ConfirmationTimeout {
+            signature,
+            commitment: commitment.commitment,
+            elapsed: started.elapsed(),
+        })?;
+        sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use solana_client::rpc_request::RpcRequest;
+    use solana_sdk::{hash::Hash, signature::Keypair, system_instruction};
+    use std::collections::HashMap;
+
+    // A mock RPC node answering `isBlockhashValid` once with `valid`, next to
+    // the mock's canned answers (`url` picks those)
+    fn rpc(url: &str, valid: bool, mut mocks: HashMap<RpcRequest, serde_json::Value>) -> RpcClient {
+        let response = json!({ "context": { "slot": 1 }, "value": valid });
+        mocks.insert(RpcRequest::IsBlockhashValid, response);
+        RpcClient::new_mock_with_mocks(url.to_string(), mocks)
+    }
+
+    fn transfer(payer: &Keypair) -> Transaction {
+        let ix = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+        Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], Hash::default())
+    }
+
+    #[tokio::test]
+    async fn reports_the_attempt_that_landed() {
+        let rpc = rpc("succeeds", true, HashMap::new());
+        let payer = Keypair::new();
+        let sign = || async { Ok(transfer(&payer)) };
+        let sent = send_signed_and_confirm(
+            &rpc,
+            CommitmentConfig::confirmed(),
+            &Backoff::default(),
+            3,
+            sign,
+        )
+        .await;
+        assert_eq!(sent.attempts, 1);
+        assert!(sent.result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn does_not_resend_failed_transactions() {
+        let rpc = rpc("instruction_error", true, HashMap::new());
+        let payer = Keypair::new();
+        let sign = || async { Ok(transfer(&payer)) };
+        let sent = send_signed_and_confirm(
+            &rpc,
+            CommitmentConfig::confirmed(),
+            &Backoff::default(),
+            3,
+            sign,
+        )
+        .await;
+        assert_eq!(sent.attempts, 1);
+        assert!(matches!(sent.result, Err(Error::TransactionFailed { .. })));
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_the_blockhash_expired_on_every_attempt() {
+        let not_found = json!({ "context": { "slot": 1 }, "value": [null] });
+        let rpc = rpc(
+            "succeeds",
+            false,
+            HashMap::from([(RpcRequest::GetSignatureStatuses, not_found)]),
+        );
+        let payer = Keypair::new();
+        let sign = || async { Ok(transfer(&payer)) };
+        let sent = send_signed_and_confirm(
+            &rpc,
+            CommitmentConfig::confirmed(),
+            &Backoff::default(),
+            1,
+            sign,
+        )
+        .await;
+        assert_eq!(sent.attempts, 1);
+        assert!(matches!(
+            sent.result,
+            Err(Error::BlockhashExpired { attempts: 1 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stops_when_signing_fails() {
+        let rpc = rpc("succeeds", true, HashMap::new());
+        let sign = || async { Err(Error::Build("no signer".into())) };
+        let sent = send_signed_and_confirm(
+            &rpc,
+            CommitmentConfig::confirmed(),
+            &Backoff::default(),
+            3,
+            sign,
+        )
+        .await;
+        assert_eq!(sent.attempts, 1);
+        assert!(matches!(sent.result, Err(Error::Build(_))));
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+// How long to keep polling the cluster, and how often: the delay doubles
+// after every poll up to `max_delay`, until `timeout` has passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Backoff {
+    // Long enough for a transaction's blockhash to expire (150 slots,
+    // about a minute), so callers learn it expired rather than time out
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+impl Backoff {
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    // The delays to wait between polls; exhausted once they add up to `timeout`
+    pub fn delays(&self) -> Delays {
+        Delays {
+            next: self.initial_delay,
+            max_delay: self.max_delay,
+            remaining: self.timeout,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Delays {
+    next: Duration,
+    max_delay: Duration,
+    remaining: Duration,
+}
+
+impl Iterator for Delays {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.remaining.is_zero() {
+            return None;
+        }
+        let delay = self.next.min(self.remaining);
+        self.remaining -= delay;
+        self.next = (self.next * 2).min(self.max_delay);
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_double_up_to_the_cap_until_the_timeout() {
+        let backoff = Backoff {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            timeout: Duration::from_millis(1500),
+        };
+        let delays: Vec<u64> = backoff.delays().map(|d| d.as_millis() as u64).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 300]);
+        assert_eq!(backoff.with_timeout(Duration::ZERO).delays().next(), None);
+    }
+}
@@ -0,0 +1,71 @@
+// Fixtures shared by the bank tests of the two journal programs (project 1's
+// `counter` and `project-3-pdas`). Both key an entry by its title and owner
+// and take the same accounts in every instruction, so only the program id
+// and instruction data differ between them
+use anchor_lang::{AccountDeserialize, InstructionData};
+use solana_program_test::{BanksClient, BanksClientError};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::Transaction,
+};
+
+// Send `instruction`, paid for and signed by `owner`
+pub async fn send(
+    banks: &mut BanksClient,
+    instruction: Instruction,
+    owner: &Keypair,
+) -> Result<(), BanksClientError> {
+    let blockhash = banks.get_latest_blockhash().await?;
+    let tx = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&owner.pubkey()),
+        &[owner],
+        blockhash,
+    );
+    banks.process_transaction(tx).await
+}
+
+// A new wallet holding 1 SOL from `payer`
+pub async fn funded(banks: &mut BanksClient, payer: &Keypair) -> Keypair {
+    let wallet = Keypair::new();
+    let fund = system_instruction::transfer(&payer.pubkey(), &wallet.pubkey(), LAMPORTS_PER_SOL);
+    send(banks, fund, payer).await.unwrap();
+    wallet
+}
+
+pub fn entry_address(program_id: &Pubkey, title: &str, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[title.as_bytes(), owner.as_ref()], program_id).0
+}
+
+// Every instruction takes the same accounts: the entry, its owner and the
+// system program
+pub fn instruction(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    title: &str,
+    data: impl InstructionData,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![
+            AccountMeta::new(entry_address(program_id, title, owner), false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data: data.data(),
+    }
+}
+
+// The entry at `address`, or `None` once it was deleted
+pub async fn fetch_entry<T: AccountDeserialize>(
+    banks: &mut BanksClient,
+    address: Pubkey,
+) -> Option<T> {
+    let account = banks.get_account(address).await.unwrap()?;
+    Some(T::try_deserialize(&mut account.data.as_slice()).unwrap())
+}
@@ -0,0 +1,115 @@
+// The journal program in an in-process bank, so `cargo test` needs no
+// validator. The fixtures are shared with the other journal program's suite
+use counter::JournalEntryState;
+use journal_test_support::{entry_address, fetch_entry, funded, instruction, send};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, signature::Keypair,
+    signer::Signer,
+};
+
+// Anchor's entrypoint ties the account slice and account infos to a single
+// lifetime, which the native processor signature doesn't provide
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    counter::entry(program_id, accounts, data)
+}
+
+async fn start() -> (BanksClient, Keypair) {
+    let program_test = ProgramTest::new("counter", counter::ID, processor!(process_instruction));
+    let (banks, payer, _) = program_test.start().await;
+    (banks, payer)
+}
+
+#[tokio::test]
+async fn test_create_update_and_delete_entry() {
+    let (mut banks, owner) = start().await;
+    let address = entry_address(&counter::ID, "Day one", &owner.pubkey());
+
+    let create = counter::instruction::CreateJournalEntry {
+        title: "Day one".to_string(),
+        message: "Hello".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&counter::ID, &owner.pubkey(), "Day one", create),
+        &owner,
+    )
+    .await
+    .unwrap();
+    let entry = fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .unwrap();
+    assert_eq!(entry.owner, owner.pubkey());
+    assert_eq!(entry.title, "Day one");
+    assert_eq!(entry.message, "Hello");
+
+    let update = counter::instruction::UpdateJournalEntry {
+        title: "Day one".to_string(),
+        message: "A much longer message than the first one".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&counter::ID, &owner.pubkey(), "Day one", update),
+        &owner,
+    )
+    .await
+    .unwrap();
+    let entry = fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .unwrap();
+    assert_eq!(entry.message, "A much longer message than the first one");
+
+    let delete = counter::instruction::DeleteJournalEntry {
+        title: "Day one".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&counter::ID, &owner.pubkey(), "Day one", delete),
+        &owner,
+    )
+    .await
+    .unwrap();
+    assert!(fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_entries_are_per_owner() {
+    let (mut banks, owner) = start().await;
+    let other = funded(&mut banks, &owner).await;
+    let create = counter::instruction::CreateJournalEntry {
+        title: "Shared title".to_string(),
+        message: "Mine".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&counter::ID, &owner.pubkey(), "Shared title", create),
+        &owner,
+    )
+    .await
+    .unwrap();
+
+    // Another wallet can't update an entry it doesn't own, whose address it
+    // can't derive from its own key
+    let update = counter::instruction::UpdateJournalEntry {
+        title: "Shared title".to_string(),
+        message: "Not yours".to_string(),
+    };
+    let mut ix = instruction(&counter::ID, &other.pubkey(), "Shared title", update);
+    ix.accounts[0].pubkey = entry_address(&counter::ID, "Shared title", &owner.pubkey());
+    assert!(send(&mut banks, ix, &other).await.is_err());
+
+    let entry = fetch_entry::<JournalEntryState>(
+        &mut banks,
+        entry_address(&counter::ID, "Shared title", &owner.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(entry.message, "Mine");
+}
@@ -0,0 +1,48 @@
+// Reference external signer for `exec:` signer sources, used by the
+// external-signer tests. It serves the line-delimited JSON protocol described
+// on `voting_client::signer::ExternalSigner` from a keypair file, so that path
+// can be exercised without real signing hardware:
+//
+//   cargo build --example mock-signer
+//   voting-client vote --keypair "exec:target/debug/examples/mock-signer voter.json"
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use solana_sdk::{signature::read_keypair_file, signer::Signer};
+use std::io::{self, BufRead, Write};
+use voting_client::signer::{SignerRequest, SignerResponse};
+
+fn main() -> Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .context("usage: mock-signer <KEYPAIR_FILE>")?;
+    let keypair =
+        read_keypair_file(&path).map_err(|e| anyhow!("Error reading keypair file {path}: {e}"))?;
+
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let response = match serde_json::from_str(&line?) {
+            Ok(SignerRequest::GetPubkey) => SignerResponse {
+                pubkey: Some(keypair.pubkey().to_string()),
+                ..SignerResponse::default()
+            },
+            Ok(SignerRequest::SignMessage { message }) => match BASE64.decode(message) {
+                Ok(message) => SignerResponse {
+                    signature: Some(keypair.sign_message(&message).to_string()),
+                    ..SignerResponse::default()
+                },
+                Err(e) => SignerResponse {
+                    error: Some(format!("invalid message encoding: {e}")),
+                    ..SignerResponse::default()
+                },
+            },
+            Err(e) => SignerResponse {
+                error: Some(format!("invalid request: {e}")),
+                ..SignerResponse::default()
+            },
+        };
+        serde_json::to_writer(&mut stdout, &response)?;
+        writeln!(stdout)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1550 @@
+#![allow(unexpected_cfgs)]
+use anchor_lang::{
+    prelude::*,
+    solana_program::hash::hashv,
+    system_program::{self, CreateAccount},
+};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use std::cmp::Reverse;
+
+declare_id!("5Couhd2qWo7v3L8LR3Q4daDPdFyJpV8MNqi3wkzNWGvu");
+
+pub const MAX_DESCRIPTION_LEN: usize = 280;
+// Candidate names are also PDA seeds, which are limited to 32 bytes
+pub const MAX_CANDIDATE_NAME_LEN: usize = 32;
+// Approval and ranked-choice ballots refer to candidates by index, and the
+// tally tracks eliminations in a 16-bit mask
+pub const MAX_BALLOT_CANDIDATES: usize = 16;
+
+#[program]
+pub mod voting {
+    use super::*;
+
+    pub fn initialize_poll(
+        ctx: Context<InitializePoll>,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+    ) -> Result<()> {
+        ctx.accounts.poll.init(
+            ctx.accounts.signer.key(),
+            poll_id,
+            description,
+            poll_start,
+            poll_end,
+            PollKind::SingleChoice,
+        )?;
+        emit!(ctx.accounts.poll.created());
+        Ok(())
+    }
+
+    // A poll where each voter casts one ballot listing the candidates they
+    // approve of, or ranking them for an instant-runoff count. Ballots are
+    // counted by cranking `tally_ballots` once the poll has ended
+    pub fn initialize_ballot_poll(
+        ctx: Context<InitializeBallotPoll>,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+        kind: PollKind,
+    ) -> Result<()> {
+        require!(kind != PollKind::SingleChoice, VotingError::WrongPollKind);
+        ctx.accounts.poll.init(
+            ctx.accounts.signer.key(),
+            poll_id,
+            description,
+            poll_start,
+            poll_end,
+            kind,
+        )?;
+        emit!(ctx.accounts.poll.created());
+
+        let tally = &mut ctx.accounts.tally;
+        tally.poll_id = poll_id;
+        // Ballots start out counted in round 0, i.e. not at all
+        tally.round = 1;
+        Ok(())
+    }
+
+    // A poll with sealed ballots: voters commit to `hash(candidate || salt)`
+    // until `poll_end` and reveal until `reveal_end`; unrevealed ballots never count
+    pub fn initialize_sealed_poll(
+        ctx: Context<InitializePoll>,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+        reveal_end: u64,
+    ) -> Result<()> {
+        require!(poll_end < reveal_end, VotingError::InvalidPollWindow);
+        let poll = &mut ctx.accounts.poll;
+        poll.init(
+            ctx.accounts.signer.key(),
+            poll_id,
+            description,
+            poll_start,
+            poll_end,
+            PollKind::SingleChoice,
+        )?;
+        poll.reveal_end = Some(reveal_end);
+        emit!(poll.created());
+        Ok(())
+    }
+
+    // A poll where each vote weighs as many governance tokens as the voter
+    // locks in the poll's escrow until the poll ends
+    pub fn initialize_token_poll(
+        ctx: Context<InitializeTokenPoll>,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        poll.init(
+            ctx.accounts.signer.key(),
+            poll_id,
+            description,
+            poll_start,
+            poll_end,
+            PollKind::SingleChoice,
+        )?;
+        poll.governance_mint = Some(ctx.accounts.governance_mint.key());
+        emit!(poll.created());
+        Ok(())
+    }
+
+    pub fn initialize_candidate(
+        ctx: Context<InitializeCandidate>,
+        candidate_name: String,
+        poll_id: u64,
+    ) -> Result<()> {
+        require!(
+            candidate_name.len() <= MAX_CANDIDATE_NAME_LEN,
+            VotingError::CandidateNameTooLong
+        );
+        let poll = &mut ctx.accounts.poll;
+        require!(!poll.finalized, VotingError::PollFinalized);
+        if poll.kind != PollKind::SingleChoice {
+            // Ballots already cast rank the candidates as they were
+            require!(poll.ballot_count == 0, VotingError::CandidatesLocked);
+            require!(
+                poll.candidate_amount < MAX_BALLOT_CANDIDATES as u64,
+                VotingError::TooManyCandidates
+            );
+        }
+
+        let candidate = &mut ctx.accounts.candidate;
+        candidate.poll_id = poll_id;
+        candidate.candidate_name = candidate_name;
+        candidate.candidate_votes = 0;
+        candidate.index = poll.candidate_amount;
+        emit!(CandidateAdded {
+            poll_id,
+            candidate_name: candidate.candidate_name.clone(),
+            index: candidate.index,
+        });
+
+        poll.candidate_amount = poll
+            .candidate_amount
+            .checked_add(1)
+            .ok_or(VotingError::Overflow)?;
+        Ok(())
+    }
+
+    // Cast a vote that also counts for every delegator passed in the remaining
+    // accounts, as `(poll delegation, global delegation, receipt)` triples.
+    // Each delegator's delegate must be the voter or a delegator before it,
+    // and a receipt is created for each so they can't be counted again or
+    // vote themselves; delegators who already voted keep their own vote
+    pub fn vote<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Vote<'info>>,
+        candidate_name: String,
+        poll_id: u64,
+    ) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        require!(
+            poll.kind == PollKind::SingleChoice,
+            VotingError::WrongPollKind
+        );
+        require!(
+            poll.governance_mint.is_none(),
+            VotingError::TokenWeightedPoll
+        );
+        require!(poll.reveal_end.is_none(), VotingError::SealedPoll);
+        poll.require_open()?;
+
+        let voter = ctx.accounts.signer.key();
+        let mut counted = vec![voter];
+        for accounts in ctx.remaining_accounts.chunks(3) {
+            let [poll_delegation, global_delegation, receipt] = accounts else {
+                return err!(VotingError::InvalidDelegationAccounts);
+            };
+            let delegation = match read_delegation(poll_delegation)? {
+                Some(delegation) => delegation,
+                None => read_delegation(global_delegation)?.ok_or(VotingError::NotDelegated)?,
+            };
+            let delegator = delegation.delegator;
+            require_keys_eq!(
+                poll_delegation.key(),
+                delegation_address(Some(poll_id), &delegator),
+                VotingError::InvalidDelegationAccounts
+            );
+            require_keys_eq!(
+                global_delegation.key(),
+                delegation_address(None, &delegator),
+                VotingError::InvalidDelegationAccounts
+            );
+            require!(
+                counted.contains(&delegation.delegate),
+                VotingError::DelegateNotCounted
+            );
+
+            let delegator_receipt = VoterReceipt {
+                poll_id,
+                voter: delegator,
+                candidate_name: candidate_name.clone(),
+                weight: 0,
+                escrowed: 0,
+                commitment: None,
+                delegate: Some(voter),
+            };
+            create_receipt(
+                &ctx.accounts.signer,
+                receipt,
+                &ctx.accounts.system_program,
+                &delegator_receipt,
+            )?;
+            emit!(VoteCast {
+                poll_id,
+                voter: delegator,
+                candidate_name: candidate_name.clone(),
+                weight: 0,
+                delegate: Some(voter),
+            });
+            counted.push(delegator);
+        }
+        if counted.len() > 1 {
+            msg!("Vote counts for {} delegator(s)", counted.len() - 1);
+        }
+
+        let receipt = &mut ctx.accounts.voter_receipt;
+        receipt.poll_id = poll_id;
+        receipt.voter = voter;
+        let weight = counted.len() as u64;
+        record_vote(&mut ctx.accounts.candidate, receipt, candidate_name, weight)
+    }
+
+    // Delegate the signer's vote in poll `poll_id`, or in every poll if
+    // `None`; a per-poll delegation takes precedence over a global one.
+    // The remaining accounts follow the delegate's own delegation chain to its
+    // end, proving that it doesn't lead back to the signer: per member, the
+    // member's delegation for the poll and their global delegation, or, when
+    // setting a global delegation, their global delegation and their
+    // `PollDelegations`. A global delegation may only pass through members
+    // without per-poll delegations, as one of those could lead back to the
+    // signer in its poll
+    pub fn set_delegation<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SetDelegation<'info>>,
+        poll_id: Option<u64>,
+        delegate: Pubkey,
+    ) -> Result<()> {
+        let delegator = ctx.accounts.signer.key();
+        require_keys_neq!(delegator, delegate, VotingError::DelegationCycle);
+
+        let mut accounts = ctx.remaining_accounts.iter();
+        let mut member = delegate;
+        loop {
+            let mut next = None;
+            if let Some(poll_id) = poll_id {
+                let info = accounts
+                    .next()
+                    .ok_or(VotingError::IncompleteDelegationChain)?;
+                require_keys_eq!(
+                    info.key(),
+                    delegation_address(Some(poll_id), &member),
+                    VotingError::InvalidDelegationAccounts
+                );
+                next = read_delegation(info)?.map(|d| d.delegate);
+            }
+            let info = accounts
+                .next()
+                .ok_or(VotingError::IncompleteDelegationChain)?;
+            require_keys_eq!(
+                info.key(),
+                delegation_address(None, &member),
+                VotingError::InvalidDelegationAccounts
+            );
+            if next.is_none() {
+                next = read_delegation(info)?.map(|d| d.delegate);
+            }
+            if poll_id.is_none() {
+                let info = accounts
+                    .next()
+                    .ok_or(VotingError::IncompleteDelegationChain)?;
+                require_keys_eq!(
+                    info.key(),
+                    poll_delegations_address(&member),
+                    VotingError::InvalidDelegationAccounts
+                );
+                if !info.data_is_empty() {
+                    let count = Account::<PollDelegations>::try_from(info)?.count;
+                    require!(count == 0, VotingError::OverlappingPollDelegation);
+                }
+            }
+
+            match next {
+                None => break,
+                Some(next) => {
+                    require_keys_neq!(next, delegator, VotingError::DelegationCycle);
+                    member = next;
+                }
+            }
+        }
+
+        let delegation = &mut ctx.accounts.delegation;
+        delegation.delegator = delegator;
+        delegation.delegate = delegate;
+        delegation.poll_id = poll_id;
+        if poll_id.is_some() {
+            let poll_delegations = &mut ctx.accounts.poll_delegations;
+            poll_delegations.count = poll_delegations
+                .count
+                .checked_add(1)
+                .ok_or(VotingError::Overflow)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_delegation(ctx: Context<ClearDelegation>, poll_id: Option<u64>) -> Result<()> {
+        if poll_id.is_some() {
+            let poll_delegations = &mut ctx.accounts.poll_delegations;
+            poll_delegations.count = poll_delegations.count.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    // Vote with the weight of `amount` governance tokens, moved into the
+    // poll's escrow so they cannot be counted again from another wallet
+    pub fn vote_weighted(
+        ctx: Context<VoteWeighted>,
+        candidate_name: String,
+        poll_id: u64,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, VotingError::ZeroWeight);
+        ctx.accounts.poll.require_open()?;
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.voter_token_account.to_account_info(),
+            mint: ctx.accounts.governance_mint.to_account_info(),
+            to: ctx.accounts.escrow.to_account_info(),
+            authority: ctx.accounts.signer.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        let decimals = ctx.accounts.governance_mint.decimals;
+        token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+        let receipt = &mut ctx.accounts.voter_receipt;
+        receipt.poll_id = poll_id;
+        receipt.voter = ctx.accounts.signer.key();
+        receipt.escrowed = amount;
+        record_vote(&mut ctx.accounts.candidate, receipt, candidate_name, amount)
+    }
+
+    // Seal a ballot in a commit-reveal poll; nothing is counted until it is revealed
+    pub fn commit_vote(ctx: Context<CommitVote>, poll_id: u64, commitment: [u8; 32]) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        require!(poll.reveal_end.is_some(), VotingError::NotSealedPoll);
+        let now = now()?;
+        require!(
+            poll.poll_start <= now && now <= poll.poll_end,
+            VotingError::CommitPhaseClosed
+        );
+
+        let receipt = &mut ctx.accounts.voter_receipt;
+        receipt.poll_id = poll_id;
+        receipt.voter = ctx.accounts.signer.key();
+        receipt.commitment = Some(commitment);
+        Ok(())
+    }
+
+    // Open a sealed ballot by presenting the candidate and salt it committed to
+    pub fn reveal_vote(
+        ctx: Context<RevealVote>,
+        candidate_name: String,
+        poll_id: u64,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let reveal_end = ctx
+            .accounts
+            .poll
+            .reveal_end
+            .ok_or(VotingError::NotSealedPoll)?;
+        let now = now()?;
+        require!(
+            ctx.accounts.poll.poll_end < now && now <= reveal_end,
+            VotingError::RevealPhaseClosed
+        );
+
+        let receipt = &mut ctx.accounts.voter_receipt;
+        let commitment = receipt
+            .commitment
+            .take()
+            .ok_or(VotingError::AlreadyRevealed)?;
+        require!(
+            ballot_commitment(poll_id, &ctx.accounts.signer.key(), &candidate_name, &salt)
+                == commitment,
+            VotingError::CommitmentMismatch
+        );
+        record_vote(&mut ctx.accounts.candidate, receipt, candidate_name, 1)
+    }
+
+    // Cast an approval or ranked-choice ballot: candidate indexes the voter
+    // approves of, or ranks from most to least preferred
+    pub fn cast_ballot(ctx: Context<CastBallot>, poll_id: u64, rankings: Vec<u8>) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        require!(
+            poll.kind != PollKind::SingleChoice,
+            VotingError::WrongPollKind
+        );
+        poll.require_open()?;
+        require!(!rankings.is_empty(), VotingError::EmptyBallot);
+        let mut listed = 0u16;
+        for &candidate in &rankings {
+            require!(
+                u64::from(candidate) < poll.candidate_amount,
+                VotingError::UnknownCandidate
+            );
+            require!(
+                listed & (1 << candidate) == 0,
+                VotingError::DuplicateCandidate
+            );
+            listed |= 1 << candidate;
+        }
+
+        poll.ballot_count = poll
+            .ballot_count
+            .checked_add(1)
+            .ok_or(VotingError::Overflow)?;
+        let ballot = &mut ctx.accounts.ballot;
+        ballot.poll_id = poll_id;
+        ballot.voter = ctx.accounts.signer.key();
+        ballot.rankings = rankings;
+        ballot.counted_round = 0;
+        emit!(BallotCast {
+            poll_id,
+            voter: ballot.voter,
+            rankings: ballot.rankings.clone(),
+        });
+        Ok(())
+    }
+
+    // Permissionless crank counting the ballots passed as remaining accounts
+    // into the current round. Once every ballot is in, the round is settled:
+    // a candidate with a majority (or, for approval, the most approvals)
+    // wins, otherwise the trailing candidate is eliminated and the next
+    // round starts over with every ballot
+    pub fn tally_ballots<'info>(
+        ctx: Context<'_, '_, 'info, 'info, TallyBallots<'info>>,
+        poll_id: u64,
+    ) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        require!(now()? > poll.poll_end, VotingError::PollNotEnded);
+        let tally = &mut ctx.accounts.tally;
+        require!(!tally.complete, VotingError::TallyComplete);
+
+        for info in ctx.remaining_accounts {
+            let mut ballot = Account::<Ballot>::try_from(info)?;
+            require!(ballot.poll_id == poll_id, VotingError::WrongPoll);
+            // Already counted this round, e.g. by a concurrent crank
+            if ballot.counted_round == tally.round {
+                continue;
+            }
+            tally.count(poll.kind, &ballot.rankings);
+            ballot.counted_round = tally.round;
+            ballot.exit(&crate::ID)?;
+        }
+
+        if tally.ballots_counted == poll.ballot_count {
+            tally.settle_round(poll.kind, poll.candidate_amount);
+            match tally.winner {
+                Some(winner) => msg!("Candidate {} wins in round {}", winner, tally.round),
+                None if tally.complete => msg!("No ballots were cast"),
+                None => msg!("Round {} starts", tally.round),
+            }
+        }
+        Ok(())
+    }
+
+    // Return a voter's escrowed governance tokens once the poll has ended
+    pub fn withdraw_escrow(ctx: Context<WithdrawEscrow>, poll_id: u64) -> Result<()> {
+        let poll = &ctx.accounts.poll;
+        require!(now()? > poll.poll_end, VotingError::PollNotEnded);
+
+        let amount = ctx.accounts.voter_receipt.escrowed;
+        require!(amount > 0, VotingError::NothingEscrowed);
+
+        let poll_id_bytes = poll_id.to_le_bytes();
+        let signer_seeds: &[&[&[u8]]] = &[&[b"poll", &poll_id_bytes, &[ctx.bumps.poll]]];
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.escrow.to_account_info(),
+            mint: ctx.accounts.governance_mint.to_account_info(),
+            to: ctx.accounts.voter_token_account.to_account_info(),
+            authority: ctx.accounts.poll.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        let decimals = ctx.accounts.governance_mint.decimals;
+        token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+        ctx.accounts.voter_receipt.escrowed = 0;
+        Ok(())
+    }
+
+    pub fn change_vote(
+        ctx: Context<ChangeVote>,
+        candidate_name: String,
+        poll_id: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.poll.reveal_end.is_none(),
+            VotingError::SealedPoll
+        );
+        ctx.accounts.poll.require_open()?;
+        let receipt = &mut ctx.accounts.voter_receipt;
+        require!(receipt.delegate.is_none(), VotingError::VotedByDelegate);
+        require!(
+            receipt.candidate_name != candidate_name,
+            VotingError::SameCandidate
+        );
+
+        let previous = &mut ctx.accounts.previous_candidate;
+        previous.candidate_votes = previous
+            .candidate_votes
+            .checked_sub(receipt.weight)
+            .ok_or(VotingError::Overflow)?;
+        let candidate = &mut ctx.accounts.candidate;
+        candidate.candidate_votes = candidate
+            .candidate_votes
+            .checked_add(receipt.weight)
+            .ok_or(VotingError::Overflow)?;
+        receipt.candidate_name = candidate_name;
+
+        msg!(
+            "Vote moved from {} to {}",
+            previous.candidate_name,
+            candidate.candidate_name
+        );
+        emit!(VoteChanged {
+            poll_id,
+            voter: receipt.voter,
+            from: previous.candidate_name.clone(),
+            to: candidate.candidate_name.clone(),
+            weight: receipt.weight,
+        });
+        Ok(())
+    }
+
+    // Permissionless crank writing the winner into the poll once it has
+    // closed. Every candidate of the poll is passed as a remaining account;
+    // single-choice polls are won by the most votes (the earliest-added
+    // candidate on a tie), approval and ranked-choice polls by their
+    // completed tally
+    pub fn finalize_poll<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizePoll<'info>>,
+        poll_id: u64,
+    ) -> Result<()> {
+        let poll = &mut ctx.accounts.poll;
+        require!(!poll.finalized, VotingError::PollFinalized);
+        require!(now()? > poll.closes_at(), VotingError::PollNotEnded);
+        require!(
+            ctx.remaining_accounts.len() as u64 == poll.candidate_amount,
+            VotingError::MissingCandidates
+        );
+
+        // Candidate names and votes by index
+        let mut candidates: Vec<Option<(String, u64)>> = vec![None; ctx.remaining_accounts.len()];
+        for info in ctx.remaining_accounts {
+            let candidate = Account::<Candidate>::try_from(info)?;
+            require!(candidate.poll_id == poll_id, VotingError::WrongPoll);
+            let slot = candidates
+                .get_mut(candidate.index as usize)
+                .ok_or(VotingError::MissingCandidates)?;
+            require!(slot.is_none(), VotingError::DuplicateCandidate);
+            *slot = Some((candidate.candidate_name.clone(), candidate.candidate_votes));
+        }
+        let candidates: Vec<(String, u64)> = candidates.into_iter().flatten().collect();
+
+        let winner = match poll.kind {
+            PollKind::SingleChoice => candidates
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, votes))| *votes > 0)
+                .max_by_key(|(index, (_, votes))| (*votes, Reverse(*index)))
+                .map(|(index, _)| index),
+            PollKind::Approval | PollKind::RankedChoice => {
+                let tally = ctx
+                    .accounts
+                    .tally
+                    .as_ref()
+                    .ok_or(VotingError::TallyIncomplete)?;
+                require!(tally.complete, VotingError::TallyIncomplete);
+                tally.winner.map(usize::from)
+            }
+        };
+
+        poll.finalized = true;
+        poll.winner = winner.map(|index| candidates[index].0.clone());
+        match &poll.winner {
+            Some(winner) => msg!("Poll {} won by {}", poll_id, winner),
+            None => msg!("Poll {} closed without votes", poll_id),
+        }
+        emit!(PollFinalized {
+            poll_id,
+            winner: poll.winner.clone(),
+        });
+        Ok(())
+    }
+
+    // Return a finalized poll's candidate rent to the poll's authority
+    pub fn close_candidate(
+        _ctx: Context<CloseCandidate>,
+        _candidate_name: String,
+        _poll_id: u64,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    // Return a voter's receipt rent once the poll is finalized and any
+    // escrowed tokens have been withdrawn
+    pub fn close_receipt(ctx: Context<CloseReceipt>, _poll_id: u64) -> Result<()> {
+        require!(
+            ctx.accounts.voter_receipt.escrowed == 0,
+            VotingError::EscrowNotWithdrawn
+        );
+        Ok(())
+    }
+
+    // Return a voter's approval or ranked-choice ballot rent once the poll is finalized
+    pub fn close_ballot(_ctx: Context<CloseBallot>, _poll_id: u64) -> Result<()> {
+        Ok(())
+    }
+}
+
+// The value a sealed ballot commits to: SHA-256 of the poll id, the voter,
+// the candidate name and the salt. Binding the poll and voter keeps a
+// commitment from being copied by another voter or replayed in another poll
+pub fn ballot_commitment(
+    poll_id: u64,
+    voter: &Pubkey,
+    candidate_name: &str,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    hashv(&[
+        &poll_id.to_le_bytes(),
+        voter.as_ref(),
+        candidate_name.as_bytes(),
+        salt,
+    ])
+    .to_bytes()
+}
+
+// Seeds of the delegation PDA: [b"delegation", poll_id, delegator] for a
+// single poll and [b"delegation", delegator] for every poll
+pub fn delegation_scope(poll_id: Option<u64>) -> Vec<u8> {
+    poll_id.map_or_else(Vec::new, |id| id.to_le_bytes().to_vec())
+}
+
+pub fn delegation_address(poll_id: Option<u64>, delegator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"delegation".as_ref(),
+            &delegation_scope(poll_id),
+            delegator.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+// PDA counting `member`'s per-poll delegations
+pub fn poll_delegations_address(member: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"poll-delegations".as_ref(), member.as_ref()], &crate::ID).0
+}
+
+// The delegation stored in `info`, or `None` if the account was never created
+fn read_delegation<'info>(info: &'info AccountInfo<'info>) -> Result<Option<Delegation>> {
+    if info.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Account::<Delegation>::try_from(info)?.into_inner()))
+}
+
+// Create the receipt PDA of a delegator counted through their delegate's
+// vote, paid for by the voter; fails if the delegator already has a receipt
+fn create_receipt<'info>(
+    payer: &Signer<'info>,
+    receipt: &'info AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    contents: &VoterReceipt,
+) -> Result<()> {
+    let poll_id_bytes = contents.poll_id.to_le_bytes();
+    let (address, bump) =
+        Pubkey::find_program_address(&[&poll_id_bytes, contents.voter.as_ref()], &crate::ID);
+    require_keys_eq!(
+        receipt.key(),
+        address,
+        VotingError::InvalidDelegationAccounts
+    );
+    require!(receipt.data_is_empty(), VotingError::DelegatorVoted);
+
+    let space = 8 + VoterReceipt::INIT_SPACE;
+    let signer_seeds: &[&[&[u8]]] = &[&[&poll_id_bytes, contents.voter.as_ref(), &[bump]]];
+    let cpi_accounts = CreateAccount {
+        from: payer.to_account_info(),
+        to: receipt.clone(),
+    };
+    let cpi_ctx =
+        CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer_seeds);
+    system_program::create_account(
+        cpi_ctx,
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        &crate::ID,
+    )?;
+
+    let mut data = receipt.try_borrow_mut_data()?;
+    contents.try_serialize(&mut &mut data[..])
+}
+
+fn now() -> Result<u64> {
+    Ok(u64::try_from(Clock::get()?.unix_timestamp).unwrap_or(0))
+}
+
+// Count `weight` votes for `candidate` and record them on the voter's receipt.
+// The receipt's `init` has already failed if this wallet voted in the poll.
+fn record_vote(
+    candidate: &mut Candidate,
+    receipt: &mut VoterReceipt,
+    candidate_name: String,
+    weight: u64,
+) -> Result<()> {
+    candidate.candidate_votes = candidate
+        .candidate_votes
+        .checked_add(weight)
+        .ok_or(VotingError::Overflow)?;
+    receipt.candidate_name = candidate_name;
+    receipt.weight = weight;
+
+    msg!("Voted for candidate: {}", receipt.candidate_name);
+    msg!("Votes: {}", candidate.candidate_votes);
+    emit!(VoteCast {
+        poll_id: receipt.poll_id,
+        voter: receipt.voter,
+        candidate_name: receipt.candidate_name.clone(),
+        weight,
+        delegate: None,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct InitializePoll<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Poll::INIT_SPACE,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct InitializeBallotPoll<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Poll::INIT_SPACE,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Tally::INIT_SPACE,
+        seeds = [b"tally", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub tally: Account<'info, Tally>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct InitializeTokenPoll<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Poll::INIT_SPACE,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(mint::token_program = token_program)]
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    // Holds the tokens voters lock in, owned by the poll PDA
+    #[account(
+        init,
+        payer = signer,
+        seeds = [b"escrow", poll_id.to_le_bytes().as_ref()],
+        bump,
+        token::mint = governance_mint,
+        token::authority = poll,
+        token::token_program = token_program,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct InitializeCandidate<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    // Only the poll's creator adds candidates, so nobody else can pad the poll
+    // past what `finalize_poll` can load in one transaction
+    #[account(
+        mut,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Candidate::INIT_SPACE,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct Vote<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + VoterReceipt::INIT_SPACE,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: Option<u64>)]
+pub struct SetDelegation<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Delegation::INIT_SPACE,
+        seeds = [b"delegation".as_ref(), delegation_scope(poll_id).as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8 + PollDelegations::INIT_SPACE,
+        seeds = [b"poll-delegations".as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub poll_delegations: Account<'info, PollDelegations>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: Option<u64>)]
+pub struct ClearDelegation<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"delegation".as_ref(), delegation_scope(poll_id).as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub delegation: Account<'info, Delegation>,
+    #[account(
+        mut,
+        seeds = [b"poll-delegations".as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub poll_delegations: Account<'info, PollDelegations>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct VoteWeighted<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.governance_mint == Some(governance_mint.key()) @ VotingError::WrongGovernanceMint,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + VoterReceipt::INIT_SPACE,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = governance_mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub voter_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"escrow", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CommitVote<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + VoterReceipt::INIT_SPACE,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct RevealVote<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CastBallot<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Ballot::INIT_SPACE,
+        seeds = [b"ballot", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub ballot: Account<'info, Ballot>,
+    pub system_program: Program<'info, System>,
+}
+
+// The ballots to count are passed, writable, as remaining accounts
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct TallyBallots<'info> {
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [b"tally", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub tally: Account<'info, Tally>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct WithdrawEscrow<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.governance_mint == Some(governance_mint.key()) @ VotingError::WrongGovernanceMint,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+    pub governance_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = governance_mint,
+        token::authority = signer,
+        token::token_program = token_program,
+    )]
+    pub voter_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"escrow", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct ChangeVote<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), voter_receipt.candidate_name.as_bytes()],
+        bump,
+    )]
+    pub previous_candidate: Account<'info, Candidate>,
+    #[account(
+        mut,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+}
+
+// The candidates of the poll are passed as remaining accounts
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct FinalizePoll<'info> {
+    #[account(
+        mut,
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub poll: Account<'info, Poll>,
+    // Only for approval and ranked-choice polls
+    #[account(
+        seeds = [b"tally", poll_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub tally: Option<Account<'info, Tally>>,
+}
+
+#[derive(Accounts)]
+#[instruction(candidate_name: String, poll_id: u64)]
+pub struct CloseCandidate<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        has_one = authority,
+        constraint = poll.finalized @ VotingError::PollNotFinalized,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        close = authority,
+        seeds = [poll_id.to_le_bytes().as_ref(), candidate_name.as_bytes()],
+        bump,
+    )]
+    pub candidate: Account<'info, Candidate>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CloseReceipt<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.finalized @ VotingError::PollNotFinalized,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        close = signer,
+        seeds = [poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub voter_receipt: Account<'info, VoterReceipt>,
+}
+
+#[derive(Accounts)]
+#[instruction(poll_id: u64)]
+pub struct CloseBallot<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"poll", poll_id.to_le_bytes().as_ref()],
+        bump,
+        constraint = poll.finalized @ VotingError::PollNotFinalized,
+    )]
+    pub poll: Account<'info, Poll>,
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"ballot", poll_id.to_le_bytes().as_ref(), signer.key().as_ref()],
+        bump,
+    )]
+    pub ballot: Account<'info, Ballot>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Poll {
+    // Creator of the poll, who reclaims the candidates' rent once it is finalized
+    pub authority: Pubkey,
+    pub poll_id: u64,
+    #[max_len(280)]
+    pub description: String,
+    pub poll_start: u64,
+    pub poll_end: u64,
+    pub candidate_amount: u64,
+    // Set for token-weighted polls
+    pub governance_mint: Option<Pubkey>,
+    // Set for commit-reveal polls: ballots are revealed after `poll_end` until then
+    pub reveal_end: Option<u64>,
+    pub kind: PollKind,
+    // Approval and ranked-choice ballots cast so far
+    pub ballot_count: u64,
+    pub finalized: bool,
+    // Written by `finalize_poll`; `None` once finalized means no votes were counted
+    #[max_len(32)]
+    pub winner: Option<String>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PollKind {
+    // One candidate per vote, counted straight into `Candidate::candidate_votes`
+    SingleChoice,
+    // A ballot lists every candidate the voter approves of; most approvals wins
+    Approval,
+    // A ballot ranks candidates, counted by instant runoff
+    RankedChoice,
+}
+
+impl Poll {
+    fn init(
+        &mut self,
+        authority: Pubkey,
+        poll_id: u64,
+        description: String,
+        poll_start: u64,
+        poll_end: u64,
+        kind: PollKind,
+    ) -> Result<()> {
+        require!(
+            description.len() <= MAX_DESCRIPTION_LEN,
+            VotingError::DescriptionTooLong
+        );
+        require!(poll_start < poll_end, VotingError::InvalidPollWindow);
+
+        self.authority = authority;
+        self.poll_id = poll_id;
+        self.description = description;
+        self.poll_start = poll_start;
+        self.poll_end = poll_end;
+        self.candidate_amount = 0;
+        self.governance_mint = None;
+        self.reveal_end = None;
+        self.kind = kind;
+        self.ballot_count = 0;
+        self.finalized = false;
+        self.winner = None;
+        Ok(())
+    }
+
+    // Votes, ballots and changes are only accepted between `poll_start` and `poll_end`
+    fn require_open(&self) -> Result<()> {
+        let now = now()?;
+        require!(
+            self.poll_start <= now && now <= self.poll_end,
+            VotingError::PollClosed
+        );
+        Ok(())
+    }
+
+    // When the result is settled: after the reveal window for sealed polls
+    fn closes_at(&self) -> u64 {
+        self.reveal_end.unwrap_or(self.poll_end)
+    }
+
+    fn created(&self) -> PollCreated {
+        PollCreated {
+            poll_id: self.poll_id,
+            authority: self.authority,
+            description: self.description.clone(),
+            poll_start: self.poll_start,
+            poll_end: self.poll_end,
+            kind: self.kind,
+            governance_mint: self.governance_mint,
+            reveal_end: self.reveal_end,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Candidate {
+    pub poll_id: u64,
+    #[max_len(32)]
+    pub candidate_name: String,
+    pub candidate_votes: u64,
+    // Order the candidate was added in, which ballots refer to it by
+    pub index: u64,
+}
+
+// An approval or ranked-choice ballot, one per voter and poll
+#[account]
+#[derive(InitSpace)]
+pub struct Ballot {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    // Candidate indexes, most preferred first for ranked-choice polls
+    #[max_len(16)]
+    pub rankings: Vec<u8>,
+    // Tally round the ballot was last counted in
+    pub counted_round: u8,
+}
+
+// Progress of an approval or ranked-choice count, advanced by `tally_ballots`
+#[account]
+#[derive(InitSpace)]
+pub struct Tally {
+    pub poll_id: u64,
+    pub round: u8,
+    // Bit `i` is set once candidate `i` is eliminated
+    pub eliminated: u16,
+    // This round's votes per candidate index
+    pub counts: [u64; 16],
+    pub ballots_counted: u64,
+    pub complete: bool,
+    // `None` once complete means no ballots were cast
+    pub winner: Option<u8>,
+}
+
+impl Tally {
+    pub fn is_eliminated(&self, candidate: u8) -> bool {
+        self.eliminated & (1 << candidate) != 0
+    }
+
+    fn count(&mut self, kind: PollKind, rankings: &[u8]) {
+        match kind {
+            PollKind::Approval => {
+                for &candidate in rankings {
+                    self.counts[candidate as usize] += 1;
+                }
+            }
+            PollKind::RankedChoice => {
+                // An exhausted ballot, whose candidates are all eliminated, counts for no one
+                if let Some(&candidate) = rankings.iter().find(|&&c| !self.is_eliminated(c)) {
+                    self.counts[candidate as usize] += 1;
+                }
+            }
+            PollKind::SingleChoice => {}
+        }
+        self.ballots_counted += 1;
+    }
+
+    // Ties are broken by candidate index: the earliest-added candidate leads,
+    // and the latest-added trailing candidate is eliminated
+    fn settle_round(&mut self, kind: PollKind, candidate_amount: u64) {
+        let standing: Vec<u8> = (0..candidate_amount as u8)
+            .filter(|&c| !self.is_eliminated(c))
+            .collect();
+        let counts = self.counts;
+        let votes = |c: &u8| (counts[*c as usize], Reverse(*c));
+        let active: u64 = standing.iter().map(|&c| counts[c as usize]).sum();
+        if active == 0 {
+            self.complete = true;
+            return;
+        }
+
+        let leader = standing.iter().copied().max_by_key(votes).unwrap();
+        if kind == PollKind::Approval || counts[leader as usize] * 2 > active {
+            self.winner = Some(leader);
+            self.complete = true;
+            return;
+        }
+
+        let trailing = standing.iter().copied().min_by_key(votes).unwrap();
+        self.eliminated |= 1 << trailing;
+        self.round += 1;
+        self.counts = [0; 16];
+        self.ballots_counted = 0;
+    }
+}
+
+// One per voter and poll, proving the voter already voted and for whom
+#[account]
+#[derive(InitSpace)]
+pub struct VoterReceipt {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    #[max_len(32)]
+    pub candidate_name: String,
+    // Votes the receipt counts for: 1 plus any delegators counted with it, or
+    // the tokens locked in a token-weighted poll
+    pub weight: u64,
+    // Governance tokens still held in escrow for this voter
+    pub escrowed: u64,
+    // Sealed ballot awaiting its reveal; the vote is only counted once revealed
+    pub commitment: Option<[u8; 32]>,
+    // Set for a delegator counted through this delegate's vote, with no weight of its own
+    pub delegate: Option<Pubkey>,
+}
+
+// `delegator` lets `delegate` vote on their behalf, in one poll or in every poll
+#[account]
+#[derive(InitSpace)]
+pub struct Delegation {
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    // `None` for a global delegation
+    pub poll_id: Option<u64>,
+}
+
+// How many per-poll delegations a member has, created with their first delegation
+#[account]
+#[derive(InitSpace)]
+pub struct PollDelegations {
+    pub count: u32,
+}
+
+// Events logged for off-chain indexers, which can rebuild every poll's
+// results from them without reading accounts that may since have been closed
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct PollCreated {
+    pub poll_id: u64,
+    pub authority: Pubkey,
+    pub description: String,
+    pub poll_start: u64,
+    pub poll_end: u64,
+    pub kind: PollKind,
+    pub governance_mint: Option<Pubkey>,
+    pub reveal_end: Option<u64>,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct CandidateAdded {
+    pub poll_id: u64,
+    pub candidate_name: String,
+    pub index: u64,
+}
+
+// A single-choice vote, or a sealed one once revealed. Delegators counted
+// through their delegate's vote are logged with no weight and their delegate
+#[event]
+#[derive(Clone, Debug)]
+pub struct VoteCast {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub candidate_name: String,
+    pub weight: u64,
+    pub delegate: Option<Pubkey>,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct VoteChanged {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub from: String,
+    pub to: String,
+    pub weight: u64,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct BallotCast {
+    pub poll_id: u64,
+    pub voter: Pubkey,
+    pub rankings: Vec<u8>,
+}
+
+#[event]
+#[derive(Clone, Debug)]
+pub struct PollFinalized {
+    pub poll_id: u64,
+    pub winner: Option<String>,
+}
+
+#[error_code]
+pub enum VotingError {
+    #[msg("Poll description is too long")]
+    DescriptionTooLong,
+    #[msg("Candidate name is too long")]
+    CandidateNameTooLong,
+    #[msg("Poll must start before it ends")]
+    InvalidPollWindow,
+    #[msg("Vote already counts for this candidate")]
+    SameCandidate,
+    #[msg("Token-weighted polls take weighted votes")]
+    TokenWeightedPoll,
+    #[msg("Mint is not the poll's governance mint")]
+    WrongGovernanceMint,
+    #[msg("Vote weight must be greater than zero")]
+    ZeroWeight,
+    #[msg("Poll has not ended yet")]
+    PollNotEnded,
+    #[msg("No tokens are escrowed for this voter")]
+    NothingEscrowed,
+    #[msg("Sealed-ballot polls take committed votes")]
+    SealedPoll,
+    #[msg("Poll does not use sealed ballots")]
+    NotSealedPoll,
+    #[msg("Poll is not accepting sealed ballots")]
+    CommitPhaseClosed,
+    #[msg("Poll is not accepting reveals")]
+    RevealPhaseClosed,
+    #[msg("Ballot was already revealed")]
+    AlreadyRevealed,
+    #[msg("Candidate and salt do not match the committed ballot")]
+    CommitmentMismatch,
+    #[msg("Instruction does not apply to this kind of poll")]
+    WrongPollKind,
+    #[msg("Candidates cannot be added once ballots are cast")]
+    CandidatesLocked,
+    #[msg("Poll has too many candidates for ballots")]
+    TooManyCandidates,
+    #[msg("Poll is not accepting votes")]
+    PollClosed,
+    #[msg("Ballot lists no candidates")]
+    EmptyBallot,
+    #[msg("Ballot lists an unknown candidate")]
+    UnknownCandidate,
+    #[msg("Ballot lists a candidate more than once")]
+    DuplicateCandidate,
+    #[msg("Ballot belongs to another poll")]
+    WrongPoll,
+    #[msg("Poll has already been tallied")]
+    TallyComplete,
+    #[msg("Poll has already been finalized")]
+    PollFinalized,
+    #[msg("Poll has not been finalized yet")]
+    PollNotFinalized,
+    #[msg("Every candidate of the poll must be passed")]
+    MissingCandidates,
+    #[msg("Poll's tally is not complete")]
+    TallyIncomplete,
+    #[msg("Withdraw escrowed tokens before closing the receipt")]
+    EscrowNotWithdrawn,
+    #[msg("Delegation accounts are missing or do not match the delegators")]
+    InvalidDelegationAccounts,
+    #[msg("Member has not delegated their vote")]
+    NotDelegated,
+    #[msg("Delegator's delegate has not been counted in this vote")]
+    DelegateNotCounted,
+    #[msg("Delegator already voted")]
+    DelegatorVoted,
+    #[msg("Vote was cast by a delegate")]
+    VotedByDelegate,
+    #[msg("Delegation would form a cycle")]
+    DelegationCycle,
+    #[msg("Delegation chain does not reach its end")]
+    IncompleteDelegationChain,
+    #[msg("A global delegation can't pass through a member with per-poll delegations")]
+    OverlappingPollDelegation,
+    #[msg("Arithmetic overflow")]
+    Overflow,
+}
@@ -0,0 +1,221 @@
+use crate::{
+    signer::{load_signer, SignerSource},
+    vote::{send_vote, SendOptions},
+};
+use anchor_client::{Client, Cluster};
+use anyhow::{bail, Context, Result};
+use futures::stream::{self, StreamExt};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature, signer::Signer,
+};
+use std::{fmt, rc::Rc};
+
+// One line of a batch file: `<signer>,<poll id>,<candidate name>`,
+// where the signer is any `SignerSource`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchRow {
+    pub line: usize,
+    pub signer: SignerSource,
+    pub poll_id: u64,
+    pub candidate: String,
+}
+
+// Parse a batch file, skipping blank lines and `#` comments
+pub fn parse_rows(contents: &str) -> Result<Vec<BatchRow>> {
+    let mut rows = Vec::new();
+    for (index, raw) in contents.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = trimmed.splitn(3, ',').map(str::trim).collect();
+        let [signer, poll_id, candidate] = fields[..] else {
+            bail!("line {line}: expected `<signer>,<poll id>,<candidate>`, got `{trimmed}`");
+        };
+        if candidate.is_empty() {
+            bail!("line {line}: candidate name is empty");
+        }
+
+        rows.push(BatchRow {
+            line,
+            signer: signer
+                .parse()
+                .with_context(|| format!("line {line}: invalid signer `{signer}`"))?,
+            poll_id: poll_id
+                .parse()
+                .with_context(|| format!("line {line}: invalid poll id `{poll_id}`"))?,
+            candidate: candidate.to_string(),
+        });
+    }
+    Ok(rows)
+}
+
+// Result of submitting a single batch row
+#[derive(Debug)]
+pub struct RowReport {
+    pub row: BatchRow,
+    pub voter: Option<Pubkey>,
+    pub attempts: u32,
+    pub result: Result<Signature, String>,
+}
+
+impl RowReport {
+    pub const CSV_HEADER: &'static str = "line,voter,poll_id,candidate,attempts,status,detail";
+}
+
+// Quote a CSV field when it holds a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Render the report as a CSV record matching `RowReport::CSV_HEADER`
+impl fmt::Display for RowReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let voter = self.voter.map(|v| v.to_string()).unwrap_or_default();
+        let (status, detail) = match &self.result {
+            Ok(signature) => ("ok", signature.to_string()),
+            Err(err) => ("error", csv_field(err)),
+        };
+        write!(
+            f,
+            "{},{},{},{},{},{},{}",
+            self.row.line,
+            voter,
+            self.row.poll_id,
+            csv_field(&self.row.candidate),
+            self.attempts,
+            status,
+            detail
+        )
+    }
+}
+
+// Settings shared by every row of a batch
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    pub cluster: Cluster,
+    pub program_id: Pubkey,
+    pub concurrency: usize,
+    pub send: SendOptions,
+}
+
+// Submit every row with at most `concurrency` votes in flight.
+// Reports are returned in the same order as the input rows.
+pub async fn run_batch(config: &BatchConfig, rows: Vec<BatchRow>) -> Vec<RowReport> {
+    let mut reports: Vec<RowReport> = stream::iter(rows)
+        .map(|row| submit_row(config, row))
+        .buffer_unordered(config.concurrency.max(1))
+        .collect()
+        .await;
+    reports.sort_by_key(|report| report.row.line);
+    reports
+}
+
+async fn submit_row(config: &BatchConfig, row: BatchRow) -> RowReport {
+    let signer = match load_signer(&row.signer) {
+        Ok(signer) => Rc::new(signer),
+        Err(err) => {
+            return RowReport {
+                row,
+                voter: None,
+                attempts: 0,
+                result: Err(format!("{err:#}")),
+            };
+        }
+    };
+    let voter = signer.pubkey();
+
+    let client = Client::new_with_options(
+        config.cluster.clone(),
+        signer,
+        CommitmentConfig::confirmed(),
+    );
+    let outcome = match client.program(config.program_id) {
+        Ok(program) => send_vote(&program, row.poll_id, &row.candidate, &config.send).await,
+        Err(err) => {
+            return RowReport {
+                row,
+                voter: Some(voter),
+                attempts: 0,
+                result: Err(err.to_string()),
+            };
+        }
+    };
+
+    RowReport {
+        row,
+        voter: Some(voter),
+        attempts: outcome.attempts,
+        result: outcome.result.map_err(|err| err.to_string()),
+    }
+}
+
+// Summarise a finished batch as `(succeeded, failed)`
+pub fn summary(reports: &[RowReport]) -> (usize, usize) {
+    let ok = reports.iter().filter(|r| r.result.is_ok()).count();
+    (ok, reports.len() - ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parses_rows_and_skips_comments() {
+        let rows = parse_rows(
+            "# keypair,poll,candidate\n\
+             voters/a.json,1,Smooth\n\
+             \n\
+             env://VOTER_B, 2 , Crunchy\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rows,
+            vec![
+                BatchRow {
+                    line: 2,
+                    signer: SignerSource::File(PathBuf::from("voters/a.json")),
+                    poll_id: 1,
+                    candidate: "Smooth".to_string(),
+                },
+                BatchRow {
+                    line: 4,
+                    signer: SignerSource::Env("VOTER_B".to_string()),
+                    poll_id: 2,
+                    candidate: "Crunchy".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_rows() {
+        assert!(parse_rows("voters/a.json,1").is_err());
+        assert!(parse_rows("voters/a.json,one,Smooth").is_err());
+        assert!(parse_rows("voters/a.json,1,").is_err());
+    }
+
+    #[test]
+    fn quotes_report_fields() {
+        let report = RowReport {
+            row: parse_rows("voters/a.json,1,Salt, \"Sea\" & Vinegar")
+                .unwrap()
+                .remove(0),
+            voter: None,
+            attempts: 1,
+            result: Err("custom program error: 0x1".to_string()),
+        };
+        assert_eq!(
+            report.to_string(),
+            "1,,1,\"Salt, \"\"Sea\"\" & Vinegar\",1,error,custom program error: 0x1"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Results indexer for the voting program.
+//!
+//! Replays the program's transactions from a validator's RPC, decodes the
+//! events they logged and keeps a SQLite database of polls, candidates,
+//! votes and voters, so past results stay queryable after their accounts
+//! have been closed:
+//!
+//! ```sh
+//! indexer --url localnet sync --follow
+//! indexer query results --poll-id 1
+//! ```
+use anchor_client::Cluster;
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{path::PathBuf, time::Duration};
+use voting::PollKind;
+use voting_client::{
+    indexer::{self, Database, PollRow},
+    VOTING_PROGRAM_ID,
+};
+
+#[derive(Parser)]
+#[command(about = "Index the voting program's results into a SQLite database")]
+struct Cli {
+    /// SQLite database to write and query
+    #[arg(long, global = true, default_value = "voting.sqlite")]
+    db: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index the program's transactions not in the database yet
+    Sync {
+        /// Cluster to replay: devnet, localnet, mainnet or an RPC URL
+        #[arg(long, short = 'u', default_value = "localnet")]
+        url: Cluster,
+        /// Program ID of the voting application
+        #[arg(long, default_value = VOTING_PROGRAM_ID)]
+        program_id: Pubkey,
+        /// Keep polling for new transactions
+        #[arg(long)]
+        follow: bool,
+        /// Seconds between polls with --follow
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+    /// Read the indexed results
+    Query {
+        #[command(subcommand)]
+        query: Query,
+    },
+}
+
+#[derive(Subcommand)]
+enum Query {
+    /// List every indexed poll
+    Polls,
+    /// Show a poll's results
+    Results {
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// List the wallets that voted in a poll and how
+    Voters {
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// Show every vote a wallet cast, across polls
+    History {
+        #[arg(long)]
+        voter: Pubkey,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut db = Database::open(&cli.db)?;
+    match cli.command {
+        Command::Sync {
+            url,
+            program_id,
+            follow,
+            interval,
+        } => {
+            let rpc = RpcClient::new_with_commitment(
+                url.url().to_string(),
+                CommitmentConfig::confirmed(),
+            );
+            loop {
+                let indexed = indexer::sync(&rpc, &program_id, &mut db).await?;
+                if indexed > 0 || !follow {
+                    println!("Indexed {indexed} transaction(s) into {}", cli.db.display());
+                }
+                if !follow {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+            }
+        }
+        Command::Query { query } => run_query(&db, query)?,
+    }
+    Ok(())
+}
+
+fn run_query(db: &Database, query: Query) -> Result<()> {
+    match query {
+        Query::Polls => {
+            let polls = db.polls()?;
+            if polls.is_empty() {
+                println!("No polls indexed yet; run `indexer sync` first");
+            }
+            for poll in polls {
+                println!(
+                    "Poll {} ({}, {} voter(s)): {}",
+                    poll.poll_id,
+                    indexer::kind_name(poll.kind),
+                    poll.voters,
+                    poll.description
+                );
+                println!("  {}", status(&poll));
+            }
+        }
+        Query::Results { poll_id } => {
+            let Some(poll) = db.poll(poll_id)? else {
+                bail!("Poll {poll_id} is not indexed");
+            };
+            println!("Poll {poll_id}: {}", poll.description);
+            println!("{}", status(&poll));
+            let candidates = db.candidates(poll_id)?;
+            match db.tally(poll_id)? {
+                None => {
+                    for candidate in &candidates {
+                        println!("  {}: {}", candidate.name, candidate.votes);
+                    }
+                }
+                Some(result) => {
+                    for (round, counts) in result.rounds.iter().enumerate() {
+                        if poll.kind == PollKind::RankedChoice {
+                            println!("Round {}:", round + 1);
+                        }
+                        for candidate in &candidates {
+                            println!(
+                                "  {}: {}",
+                                candidate.name, counts.counts[candidate.index as usize]
+                            );
+                        }
+                        if let Some(eliminated) = counts.eliminated {
+                            println!("  {} is eliminated", candidates[eliminated as usize].name);
+                        }
+                    }
+                }
+            }
+        }
+        Query::Voters { poll_id } => {
+            let candidates = db.candidates(poll_id)?;
+            for voter in db.voters(poll_id)? {
+                let choice = match (&voter.candidate, &voter.rankings) {
+                    (Some(candidate), _) => candidate.clone(),
+                    (None, Some(rankings)) => rankings
+                        .iter()
+                        .map(|&index| candidates[index as usize].name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" > "),
+                    (None, None) => "?".to_string(),
+                };
+                match &voter.delegate {
+                    Some(delegate) => println!("{}: {choice} (through {delegate})", voter.voter),
+                    None => println!("{}: {choice} (weight {})", voter.voter, voter.weight),
+                }
+            }
+        }
+        Query::History { voter } => {
+            for vote in db.history(&voter)? {
+                let what = match vote.action.as_str() {
+                    "change" => format!(
+                        "changed {} -> {}",
+                        vote.previous.unwrap_or_default(),
+                        vote.candidate.unwrap_or_default()
+                    ),
+                    "ballot" => format!("cast a ballot {:?}", vote.rankings.unwrap_or_default()),
+                    _ => match vote.delegate {
+                        Some(delegate) => format!(
+                            "counted for {} through {delegate}",
+                            vote.candidate.unwrap_or_default()
+                        ),
+                        None => format!(
+                            "voted {} (weight {})",
+                            vote.candidate.unwrap_or_default(),
+                            vote.weight
+                        ),
+                    },
+                };
+                println!(
+                    "slot {} poll {}: {what} in {}",
+                    vote.slot, vote.poll_id, vote.signature
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn status(poll: &PollRow) -> String {
+    match (&poll.winner, poll.finalized) {
+        (Some(winner), true) => format!("Finalized, winner: {winner}"),
+        (None, true) => "Finalized without votes".to_string(),
+        _ => format!("Open from {} to {}", poll.poll_start, poll.poll_end),
+    }
+}
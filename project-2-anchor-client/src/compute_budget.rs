@@ -0,0 +1,263 @@
+use anchor_client::ClientError;
+use anyhow::{anyhow, Error};
+use solana_client::{
+    client_error::{ClientError as SolanaClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcSimulateTransactionConfig,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction, pubkey::Pubkey, transaction::Transaction,
+};
+use std::str::FromStr;
+
+// Largest compute unit limit a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+// How the compute unit limit of a vote transaction is chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitLimit {
+    // Leave the limit to the runtime default
+    #[default]
+    Default,
+    Fixed(u32),
+    // Simulate the transaction and size the limit to the units it consumed
+    Simulate,
+}
+
+impl FromStr for UnitLimit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "simulate" => Ok(Self::Simulate),
+            _ => {
+                let units: u32 = s
+                    .parse()
+                    .map_err(|_| anyhow!("expected a unit count, `simulate` or `default`"))?;
+                if units == 0 || units > MAX_COMPUTE_UNIT_LIMIT {
+                    return Err(anyhow!(
+                        "compute unit limit must be between 1 and {MAX_COMPUTE_UNIT_LIMIT}"
+                    ));
+                }
+                Ok(Self::Fixed(units))
+            }
+        }
+    }
+}
+
+// How the compute unit price (priority fee) of a vote transaction is chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitPrice {
+    // Send without a priority fee
+    #[default]
+    Default,
+    // Price in micro-lamports per compute unit
+    Fixed(u64),
+    // Estimate from `getRecentPrioritizationFees` for the accounts the vote writes
+    Auto,
+}
+
+impl FromStr for UnitPrice {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "auto" => Ok(Self::Auto),
+            _ => s
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|_| anyhow!("expected micro-lamports, `auto` or `default`")),
+        }
+    }
+}
+
+// Compute budget settings applied to every vote transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub limit: UnitLimit,
+    pub price: UnitPrice,
+    // Headroom added on top of simulated usage, in percent
+    pub margin_percent: u32,
+    // Percentile of recent prioritization fees used by `UnitPrice::Auto`
+    pub fee_percentile: u8,
+}
+
+impl Default for ComputeBudget {
+    fn default() -> Self {
+        Self {
+            limit: UnitLimit::Default,
+            price: UnitPrice::Default,
+            margin_percent: 10,
+            fee_percentile: 75,
+        }
+    }
+}
+
+impl ComputeBudget {
+    // Build the `ComputeBudgetInstruction`s to prepend to `instructions`.
+    // Returns an empty list when both the limit and the price are left at their defaults.
+    pub async fn instructions(
+        &self,
+        rpc: &RpcClient,
+        payer: &Pubkey,
+        instructions: &[Instruction],
+    ) -> Result<Vec<Instruction>, ClientError> {
+        let mut budget = Vec::new();
+
+        let limit = match self.limit {
+            UnitLimit::Default => None,
+            UnitLimit::Fixed(units) => Some(units),
+            UnitLimit::Simulate => {
+                let consumed = simulate_units(rpc, payer, instructions).await?;
+                Some(limit_with_margin(consumed, self.margin_percent))
+            }
+        };
+        if let Some(units) = limit {
+            budget.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+
+        let price = match self.price {
+            UnitPrice::Default => None,
+            UnitPrice::Fixed(micro_lamports) => Some(micro_lamports),
+            UnitPrice::Auto => {
+                let accounts = writable_accounts(instructions);
+                Some(estimate_unit_price(rpc, &accounts, self.fee_percentile).await?)
+            }
+        };
+        if let Some(micro_lamports) = price {
+            budget.push(ComputeBudgetInstruction::set_compute_unit_price(
+                micro_lamports,
+            ));
+        }
+
+        Ok(budget)
+    }
+}
+
+// Estimate a compute unit price from the fees recently paid to write `accounts`
+pub async fn estimate_unit_price(
+    rpc: &RpcClient,
+    accounts: &[Pubkey],
+    percentile: u8,
+) -> Result<u64, ClientError> {
+    let mut fees: Vec<u64> = rpc
+        .get_recent_prioritization_fees(accounts)
+        .await?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    Ok(fee_percentile(&mut fees, percentile))
+}
+
+// Simulate `instructions` under the maximum limit and return the units consumed
+pub async fn simulate_units(
+    rpc: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<u64, ClientError> {
+    let mut simulated = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+        MAX_COMPUTE_UNIT_LIMIT,
+    )];
+    simulated.extend_from_slice(instructions);
+    let tx = Transaction::new_with_payer(&simulated, Some(payer));
+
+    let result = rpc
+        .simulate_transaction_with_config(
+            &tx,
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                commitment: Some(CommitmentConfig::processed()),
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .await?
+        .value;
+
+    if let Some(err) = result.err {
+        return Err(SolanaClientError::from(err).into());
+    }
+    result.units_consumed.ok_or_else(|| {
+        SolanaClientError::from(ClientErrorKind::Custom(
+            "simulation did not report units consumed".to_string(),
+        ))
+        .into()
+    })
+}
+
+// Add `margin_percent` headroom to `units`, capped at the maximum limit
+pub fn limit_with_margin(units: u64, margin_percent: u32) -> u32 {
+    let limit = units
+        .saturating_mul(100 + u64::from(margin_percent))
+        .div_ceil(100);
+    limit.min(u64::from(MAX_COMPUTE_UNIT_LIMIT)) as u32
+}
+
+// The `percentile`th fee (nearest rank), or zero when there is no fee history
+pub fn fee_percentile(fees: &mut [u64], percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (usize::from(percentile.min(100)) * fees.len()).div_ceil(100);
+    fees[rank.saturating_sub(1)]
+}
+
+fn writable_accounts(instructions: &[Instruction]) -> Vec<Pubkey> {
+    let mut accounts: Vec<Pubkey> = instructions
+        .iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| meta.is_writable)
+        .map(|meta| meta.pubkey)
+        .collect();
+    accounts.sort();
+    accounts.dedup();
+    accounts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_limit_and_price() {
+        assert_eq!(
+            "simulate".parse::<UnitLimit>().unwrap(),
+            UnitLimit::Simulate
+        );
+        assert_eq!(
+            "200000".parse::<UnitLimit>().unwrap(),
+            UnitLimit::Fixed(200_000)
+        );
+        assert!("0".parse::<UnitLimit>().is_err());
+        assert!("1400001".parse::<UnitLimit>().is_err());
+
+        assert_eq!("auto".parse::<UnitPrice>().unwrap(), UnitPrice::Auto);
+        assert_eq!(
+            "5000".parse::<UnitPrice>().unwrap(),
+            UnitPrice::Fixed(5_000)
+        );
+        assert!("cheap".parse::<UnitPrice>().is_err());
+    }
+
+    #[test]
+    fn adds_margin_and_caps_limit() {
+        assert_eq!(limit_with_margin(10_000, 10), 11_000);
+        assert_eq!(limit_with_margin(10_001, 0), 10_001);
+        assert_eq!(limit_with_margin(999, 10), 1_099);
+        assert_eq!(limit_with_margin(1_300_000, 20), MAX_COMPUTE_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn picks_fee_percentile() {
+        assert_eq!(fee_percentile(&mut [], 75), 0);
+        let mut fees = [400, 100, 300, 200];
+        assert_eq!(fee_percentile(&mut fees, 50), 200);
+        assert_eq!(fee_percentile(&mut fees, 75), 300);
+        assert_eq!(fee_percentile(&mut fees, 100), 400);
+        assert_eq!(fee_percentile(&mut fees, 0), 100);
+    }
+}
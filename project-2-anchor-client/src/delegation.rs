@@ -0,0 +1,360 @@
+use crate::vote::{vote_instruction, voter_receipt_address};
+use anchor_client::{ClientError, Program};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anyhow::{bail, Result};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signer::Signer,
+    system_program,
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Deref,
+};
+use voting::Delegation;
+
+// Delegators counted per vote; each takes three accounts, and the vote
+// transaction has to stay within the size limit
+pub const MAX_DELEGATORS_PER_VOTE: usize = 8;
+
+// Derive `delegator`'s delegation PDA for poll `poll_id`, or for every poll if `None`
+pub fn delegation_address(program_id: &Pubkey, poll_id: Option<u64>, delegator: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"delegation".as_ref(),
+            &voting::delegation_scope(poll_id),
+            delegator.as_ref(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+// Derive the PDA counting `member`'s per-poll delegations
+pub fn poll_delegations_address(program_id: &Pubkey, member: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"poll-delegations".as_ref(), member.as_ref()], program_id).0
+}
+
+// Build the `set_delegation` instruction; `chain` are the accounts returned
+// by `DelegationGraph::chain_accounts` for the new delegate
+pub fn set_delegation_instruction(
+    program_id: &Pubkey,
+    delegator: &Pubkey,
+    poll_id: Option<u64>,
+    delegate: &Pubkey,
+    chain: &[Pubkey],
+) -> Instruction {
+    let mut accounts = voting::accounts::SetDelegation {
+        signer: *delegator,
+        delegation: delegation_address(program_id, poll_id, delegator),
+        poll_delegations: poll_delegations_address(program_id, delegator),
+        system_program: system_program::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        chain
+            .iter()
+            .map(|account| AccountMeta::new_readonly(*account, false)),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: voting::instruction::SetDelegation {
+            poll_id,
+            delegate: *delegate,
+        }
+        .data(),
+    }
+}
+
+pub fn clear_delegation_instruction(
+    program_id: &Pubkey,
+    delegator: &Pubkey,
+    poll_id: Option<u64>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::ClearDelegation {
+            signer: *delegator,
+            delegation: delegation_address(program_id, poll_id, delegator),
+            poll_delegations: poll_delegations_address(program_id, delegator),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::ClearDelegation { poll_id }.data(),
+    }
+}
+
+// The remaining accounts of a `vote` counting `delegators`, in order
+pub fn delegated_vote_accounts(
+    program_id: &Pubkey,
+    poll_id: u64,
+    delegators: &[Pubkey],
+) -> Vec<AccountMeta> {
+    delegators
+        .iter()
+        .flat_map(|delegator| {
+            [
+                AccountMeta::new_readonly(
+                    delegation_address(program_id, Some(poll_id), delegator),
+                    false,
+                ),
+                AccountMeta::new_readonly(delegation_address(program_id, None, delegator), false),
+                AccountMeta::new(voter_receipt_address(program_id, poll_id, delegator), false),
+            ]
+        })
+        .collect()
+}
+
+// Build a `vote` instruction that also counts `delegators`, each after its delegate
+pub fn delegated_vote_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+    delegators: &[Pubkey],
+) -> Instruction {
+    let mut ix = vote_instruction(program_id, voter, poll_id, candidate);
+    ix.accounts
+        .extend(delegated_vote_accounts(program_id, poll_id, delegators));
+    ix
+}
+
+// Who each member delegates to in one poll (or, without a poll, globally):
+// a delegation for the poll takes precedence over a global one
+#[derive(Debug, Clone, Default)]
+pub struct DelegationGraph {
+    pub poll_id: Option<u64>,
+    pub edges: HashMap<Pubkey, Pubkey>,
+    // Members with a delegation for any poll, which global delegations can't pass through
+    pub poll_delegators: HashSet<Pubkey>,
+}
+
+impl DelegationGraph {
+    pub fn new(delegations: &[Delegation], poll_id: Option<u64>) -> Self {
+        let poll_delegators = delegations
+            .iter()
+            .filter(|d| d.poll_id.is_some())
+            .map(|d| d.delegator)
+            .collect();
+        let mut edges = HashMap::new();
+        for delegation in delegations.iter().filter(|d| d.poll_id.is_none()) {
+            edges.insert(delegation.delegator, delegation.delegate);
+        }
+        if poll_id.is_some() {
+            for delegation in delegations.iter().filter(|d| d.poll_id == poll_id) {
+                edges.insert(delegation.delegator, delegation.delegate);
+            }
+        }
+        Self {
+            poll_id,
+            edges,
+            poll_delegators,
+        }
+    }
+
+    // The accounts `set_delegation` needs to follow `delegate`'s chain to its
+    // end, failing if `delegator` delegating to it would close a cycle, or if
+    // a global delegation's chain passes through a member with per-poll
+    // delegations
+    pub fn chain_accounts(
+        &self,
+        program_id: &Pubkey,
+        delegator: &Pubkey,
+        delegate: &Pubkey,
+    ) -> Result<Vec<Pubkey>> {
+        let mut accounts = Vec::new();
+        let mut member = *delegate;
+        loop {
+            if member == *delegator {
+                bail!("Delegating to {delegate} would form a cycle back to {delegator}");
+            }
+            if let Some(poll_id) = self.poll_id {
+                accounts.push(delegation_address(program_id, Some(poll_id), &member));
+            }
+            accounts.push(delegation_address(program_id, None, &member));
+            if self.poll_id.is_none() {
+                if self.poll_delegators.contains(&member) {
+                    bail!("{member} has per-poll delegations, which a global delegation can't pass through");
+                }
+                accounts.push(poll_delegations_address(program_id, &member));
+            }
+            match self.edges.get(&member) {
+                Some(next) => member = *next,
+                None => return Ok(accounts),
+            }
+        }
+    }
+
+    // Members whose votes `voter`'s vote carries, nearest first so that every
+    // delegator comes after its delegate. A delegator who voted keeps their
+    // own vote, and so does everyone delegating through them
+    pub fn delegators_of(&self, voter: &Pubkey, voted: &HashSet<Pubkey>) -> Vec<Pubkey> {
+        let mut delegators = Vec::new();
+        let mut seen = HashSet::from([*voter]);
+        let mut queue = VecDeque::from([*voter]);
+        while let Some(delegate) = queue.pop_front() {
+            let mut direct: Vec<Pubkey> = self
+                .edges
+                .iter()
+                .filter(|(delegator, to)| *to == &delegate && !voted.contains(*delegator))
+                .map(|(delegator, _)| *delegator)
+                .collect();
+            direct.sort();
+            for delegator in direct {
+                if seen.insert(delegator) {
+                    delegators.push(delegator);
+                    queue.push_back(delegator);
+                }
+            }
+        }
+        delegators
+    }
+
+    // Delegation cycles, each listed once starting from its smallest member.
+    // `set_delegation` rejects them, including ones mixing per-poll and
+    // global delegations, so any found here predate that check; votes stay
+    // correct as every member is counted at most once
+    pub fn cycles(&self) -> Vec<Vec<Pubkey>> {
+        let mut cycles = Vec::new();
+        let mut members: Vec<&Pubkey> = self.edges.keys().collect();
+        members.sort();
+        for start in members {
+            let mut path = vec![*start];
+            while let Some(next) = self.edges.get(path.last().unwrap()) {
+                if let Some(at) = path.iter().position(|member| member == next) {
+                    let cycle = &path[at..];
+                    if cycle.iter().min() == Some(start) {
+                        cycles.push(cycle.to_vec());
+                    }
+                    break;
+                }
+                path.push(*next);
+            }
+        }
+        cycles
+    }
+}
+
+// Every delegation, global and per poll
+pub async fn fetch_delegations<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+) -> Result<Vec<Delegation>, ClientError> {
+    Ok(program
+        .accounts::<Delegation>(vec![])
+        .await?
+        .into_iter()
+        .map(|(_, delegation)| delegation)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delegation(delegator: Pubkey, delegate: Pubkey, poll_id: Option<u64>) -> Delegation {
+        Delegation {
+            delegator,
+            delegate,
+            poll_id,
+        }
+    }
+
+    #[test]
+    fn poll_delegations_override_global_ones() {
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let delegations = [delegation(a, b, None), delegation(a, c, Some(1))];
+
+        assert_eq!(DelegationGraph::new(&delegations, Some(1)).edges[&a], c);
+        assert_eq!(DelegationGraph::new(&delegations, Some(2)).edges[&a], b);
+        assert_eq!(DelegationGraph::new(&delegations, None).edges[&a], b);
+    }
+
+    #[test]
+    fn delegators_are_ordered_after_their_delegates() {
+        let [voter, a, b, c, d] = [(); 5].map(|_| Pubkey::new_unique());
+        // a -> b -> voter, c -> voter (in poll 1), d -> c
+        let delegations = [
+            delegation(a, b, None),
+            delegation(b, voter, None),
+            delegation(c, voter, Some(1)),
+            delegation(d, c, None),
+        ];
+        let graph = DelegationGraph::new(&delegations, Some(1));
+
+        let mut direct = vec![b, c];
+        direct.sort();
+        let indirect: Vec<Pubkey> = direct.iter().map(|&m| if m == b { a } else { d }).collect();
+        assert_eq!(
+            graph.delegators_of(&voter, &HashSet::new()),
+            [direct, indirect].concat()
+        );
+        // Once c votes, c's vote carries d
+        let voted = HashSet::from([c]);
+        assert_eq!(graph.delegators_of(&voter, &voted), vec![b, a]);
+    }
+
+    #[test]
+    fn finds_cycles_across_scopes() {
+        let [a, b, c, d] = [(); 4].map(|_| Pubkey::new_unique());
+        // Globally a -> b -> c, but in poll 1 c delegates back to a; d joins the cycle
+        let delegations = [
+            delegation(a, b, None),
+            delegation(b, c, None),
+            delegation(c, a, Some(1)),
+            delegation(d, a, None),
+        ];
+        assert!(DelegationGraph::new(&delegations, None).cycles().is_empty());
+
+        let cycles = DelegationGraph::new(&delegations, Some(1)).cycles();
+        assert_eq!(cycles.len(), 1);
+        let mut members = cycles[0].clone();
+        members.sort();
+        let mut expected = vec![a, b, c];
+        expected.sort();
+        assert_eq!(members, expected);
+        assert_eq!(cycles[0][0], expected[0]);
+    }
+
+    #[test]
+    fn chain_accounts_reject_cycles() {
+        let program_id = Pubkey::new_unique();
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let graph = DelegationGraph::new(&[delegation(b, c, None), delegation(c, a, None)], None);
+
+        assert!(graph.chain_accounts(&program_id, &a, &b).is_err());
+        assert!(graph.chain_accounts(&program_id, &a, &a).is_err());
+        // b -> c -> (end): one global delegation account per member
+        let graph = DelegationGraph::new(&[delegation(b, c, None)], Some(1));
+        assert_eq!(
+            graph.chain_accounts(&program_id, &a, &b).unwrap(),
+            vec![
+                delegation_address(&program_id, Some(1), &b),
+                delegation_address(&program_id, None, &b),
+                delegation_address(&program_id, Some(1), &c),
+                delegation_address(&program_id, None, &c),
+            ]
+        );
+    }
+
+    #[test]
+    fn global_chain_accounts_avoid_poll_delegators() {
+        let program_id = Pubkey::new_unique();
+        let [a, b, c] = [(); 3].map(|_| Pubkey::new_unique());
+        let graph = DelegationGraph::new(&[delegation(b, c, None)], None);
+        assert_eq!(
+            graph.chain_accounts(&program_id, &a, &b).unwrap(),
+            vec![
+                delegation_address(&program_id, None, &b),
+                poll_delegations_address(&program_id, &b),
+                delegation_address(&program_id, None, &c),
+                poll_delegations_address(&program_id, &c),
+            ]
+        );
+
+        // In poll 1, c -> a would close a -> b -> c -> a
+        let graph =
+            DelegationGraph::new(&[delegation(b, c, None), delegation(c, a, Some(1))], None);
+        assert!(graph.chain_accounts(&program_id, &a, &b).is_err());
+    }
+}
@@ -0,0 +1,616 @@
+use crate::tally::{self, TallyResult};
+use anchor_lang::Event;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rusqlite::{params, Connection, OptionalExtension};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status_client_types::{
+    option_serializer::OptionSerializer, UiTransactionEncoding,
+};
+use std::{path::Path, str::FromStr};
+use voting::{
+    BallotCast, CandidateAdded, PollCreated, PollFinalized, PollKind, VoteCast, VoteChanged,
+};
+
+// The most signatures `getSignaturesForAddress` returns per request
+const SIGNATURES_PER_PAGE: usize = 1000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    signature TEXT NOT NULL UNIQUE,
+    slot INTEGER NOT NULL,
+    block_time INTEGER
+);
+CREATE TABLE IF NOT EXISTS polls (
+    poll_id INTEGER PRIMARY KEY,
+    authority TEXT NOT NULL,
+    description TEXT NOT NULL,
+    poll_start INTEGER NOT NULL,
+    poll_end INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    governance_mint TEXT,
+    reveal_end INTEGER,
+    finalized INTEGER NOT NULL DEFAULT 0,
+    winner TEXT
+);
+CREATE TABLE IF NOT EXISTS candidates (
+    poll_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    idx INTEGER NOT NULL,
+    votes INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (poll_id, name)
+);
+CREATE TABLE IF NOT EXISTS voters (
+    poll_id INTEGER NOT NULL,
+    voter TEXT NOT NULL,
+    candidate TEXT,
+    rankings BLOB,
+    weight INTEGER NOT NULL,
+    delegate TEXT,
+    PRIMARY KEY (poll_id, voter)
+);
+CREATE TABLE IF NOT EXISTS votes (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    poll_id INTEGER NOT NULL,
+    voter TEXT NOT NULL,
+    action TEXT NOT NULL,
+    candidate TEXT,
+    previous TEXT,
+    rankings BLOB,
+    weight INTEGER NOT NULL,
+    delegate TEXT,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS votes_by_voter ON votes (voter);
+";
+
+// An event logged by the voting program
+#[derive(Debug, Clone)]
+pub enum VotingEvent {
+    PollCreated(PollCreated),
+    CandidateAdded(CandidateAdded),
+    VoteCast(VoteCast),
+    VoteChanged(VoteChanged),
+    BallotCast(BallotCast),
+    PollFinalized(PollFinalized),
+}
+
+fn decode<T: Event>(data: &[u8]) -> Option<T> {
+    let mut fields = data.strip_prefix(T::DISCRIMINATOR)?;
+    T::deserialize(&mut fields).ok()
+}
+
+// Decode the payload of an `emit!`, or `None` if it isn't a voting event
+pub fn decode_event(data: &[u8]) -> Option<VotingEvent> {
+    decode(data)
+        .map(VotingEvent::PollCreated)
+        .or_else(|| decode(data).map(VotingEvent::CandidateAdded))
+        .or_else(|| decode(data).map(VotingEvent::VoteCast))
+        .or_else(|| decode(data).map(VotingEvent::VoteChanged))
+        .or_else(|| decode(data).map(VotingEvent::BallotCast))
+        .or_else(|| decode(data).map(VotingEvent::PollFinalized))
+}
+
+// The events `program_id` logged in a transaction's log messages, in order.
+// Only `Program data:` lines logged while the program itself is executing
+// count, so another program can't forge events by logging the same bytes
+pub fn parse_events(program_id: &Pubkey, logs: &[String]) -> Vec<VotingEvent> {
+    let program_id = program_id.to_string();
+    let mut invoked: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for line in logs {
+        if let Some(data) = line.strip_prefix("Program data: ") {
+            if invoked.last() == Some(&program_id.as_str()) {
+                let event = data
+                    .split(' ')
+                    .next()
+                    .and_then(|data| BASE64.decode(data).ok())
+                    .and_then(|data| decode_event(&data));
+                events.extend(event);
+            }
+            continue;
+        }
+        if line.starts_with("Program log: ") || line.starts_with("Program return: ") {
+            continue;
+        }
+        let mut words = line.split(' ');
+        if words.next() != Some("Program") {
+            continue;
+        }
+        match (words.next(), words.next()) {
+            (Some(id), Some("invoke")) => invoked.push(id),
+            (Some(_), Some("success" | "failed:")) => {
+                invoked.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+// SQLite columns are signed; timestamps like an open-ended `u64::MAX` poll end are clamped
+fn int(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+pub fn kind_name(kind: PollKind) -> &'static str {
+    match kind {
+        PollKind::SingleChoice => "single-choice",
+        PollKind::Approval => "approval",
+        PollKind::RankedChoice => "ranked-choice",
+    }
+}
+
+fn parse_kind(name: &str) -> PollKind {
+    match name {
+        "approval" => PollKind::Approval,
+        "ranked-choice" => PollKind::RankedChoice,
+        _ => PollKind::SingleChoice,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollRow {
+    pub poll_id: u64,
+    pub authority: String,
+    pub description: String,
+    pub poll_start: u64,
+    pub poll_end: u64,
+    pub kind: PollKind,
+    pub governance_mint: Option<String>,
+    pub reveal_end: Option<u64>,
+    pub finalized: bool,
+    pub winner: Option<String>,
+    // Wallets with a vote or ballot in the poll, including counted delegators
+    pub voters: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateRow {
+    pub name: String,
+    pub index: u64,
+    // Single-choice votes; approval and ranked-choice ballots are counted by `Database::tally`
+    pub votes: u64,
+}
+
+// A wallet's current vote in a poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoterRow {
+    pub voter: String,
+    pub candidate: Option<String>,
+    pub rankings: Option<Vec<u8>>,
+    pub weight: u64,
+    pub delegate: Option<String>,
+}
+
+// One vote, change or ballot as it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteRow {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub poll_id: u64,
+    // `vote`, `change` or `ballot`
+    pub action: String,
+    pub candidate: Option<String>,
+    pub previous: Option<String>,
+    pub rankings: Option<Vec<u8>>,
+    pub weight: u64,
+    pub delegate: Option<String>,
+}
+
+// Polls, candidates, votes and voters rebuilt from the program's events
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Error opening database {}", path.display()))?;
+        Self::with_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn is_indexed(&self, signature: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT 1 FROM transactions WHERE signature = ?1",
+                [signature],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some())
+    }
+
+    // The most recent transaction indexed, where the next sync resumes
+    pub fn last_signature(&self) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT signature FROM transactions ORDER BY seq DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    // Apply one transaction's events, atomically with recording the
+    // transaction itself so it is never applied twice
+    pub fn index_transaction(
+        &mut self,
+        signature: &str,
+        slot: u64,
+        block_time: Option<i64>,
+        events: &[VotingEvent],
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+            params![signature, int(slot), block_time],
+        )?;
+        for (index, event) in events.iter().enumerate() {
+            apply(&tx, signature, index, event)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn polls(&self) -> Result<Vec<PollRow>> {
+        self.query_polls("", [])
+    }
+
+    pub fn poll(&self, poll_id: u64) -> Result<Option<PollRow>> {
+        Ok(self
+            .query_polls("WHERE p.poll_id = ?1", [int(poll_id)])?
+            .pop())
+    }
+
+    fn query_polls<P: rusqlite::Params>(&self, filter: &str, params: P) -> Result<Vec<PollRow>> {
+        let sql = format!(
+            "SELECT p.poll_id, p.authority, p.description, p.poll_start, p.poll_end, p.kind,
+                    p.governance_mint, p.reveal_end, p.finalized, p.winner,
+                    (SELECT COUNT(*) FROM voters v WHERE v.poll_id = p.poll_id)
+             FROM polls p {filter} ORDER BY p.poll_id"
+        );
+        let mut statement = self.conn.prepare(&sql)?;
+        let rows = statement.query_map(params, |row| {
+            Ok(PollRow {
+                poll_id: row.get::<_, i64>(0)? as u64,
+                authority: row.get(1)?,
+                description: row.get(2)?,
+                poll_start: row.get::<_, i64>(3)? as u64,
+                poll_end: row.get::<_, i64>(4)? as u64,
+                kind: parse_kind(&row.get::<_, String>(5)?),
+                governance_mint: row.get(6)?,
+                reveal_end: row.get::<_, Option<i64>>(7)?.map(|t| t as u64),
+                finalized: row.get(8)?,
+                winner: row.get(9)?,
+                voters: row.get::<_, i64>(10)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // The candidates of a poll, in the order they were added
+    pub fn candidates(&self, poll_id: u64) -> Result<Vec<CandidateRow>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT name, idx, votes FROM candidates WHERE poll_id = ?1 ORDER BY idx")?;
+        let rows = statement.query_map([int(poll_id)], |row| {
+            Ok(CandidateRow {
+                name: row.get(0)?,
+                index: row.get::<_, i64>(1)? as u64,
+                votes: row.get::<_, i64>(2)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn voters(&self, poll_id: u64) -> Result<Vec<VoterRow>> {
+        let mut statement = self.conn.prepare(
+            "SELECT voter, candidate, rankings, weight, delegate FROM voters
+             WHERE poll_id = ?1 ORDER BY voter",
+        )?;
+        let rows = statement.query_map([int(poll_id)], |row| {
+            Ok(VoterRow {
+                voter: row.get(0)?,
+                candidate: row.get(1)?,
+                rankings: row.get(2)?,
+                weight: row.get::<_, i64>(3)? as u64,
+                delegate: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // Every vote, change and ballot of `voter`, oldest first
+    pub fn history(&self, voter: &Pubkey) -> Result<Vec<VoteRow>> {
+        let mut statement = self.conn.prepare(
+            "SELECT v.signature, t.slot, t.block_time, v.poll_id, v.action, v.candidate,
+                    v.previous, v.rankings, v.weight, v.delegate
+             FROM votes v JOIN transactions t ON t.signature = v.signature
+             WHERE v.voter = ?1 ORDER BY t.seq, v.event_index",
+        )?;
+        let rows = statement.query_map([voter.to_string()], |row| {
+            Ok(VoteRow {
+                signature: row.get(0)?,
+                slot: row.get::<_, i64>(1)? as u64,
+                block_time: row.get(2)?,
+                poll_id: row.get::<_, i64>(3)? as u64,
+                action: row.get(4)?,
+                candidate: row.get(5)?,
+                previous: row.get(6)?,
+                rankings: row.get(7)?,
+                weight: row.get::<_, i64>(8)? as u64,
+                delegate: row.get(9)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // Count an approval or ranked-choice poll's ballots the way the program's
+    // crank does; `None` for other polls
+    pub fn tally(&self, poll_id: u64) -> Result<Option<TallyResult>> {
+        let Some(poll) = self.poll(poll_id)? else {
+            return Ok(None);
+        };
+        if poll.kind == PollKind::SingleChoice {
+            return Ok(None);
+        }
+        let candidates = self.candidates(poll_id)?.len();
+        let ballots: Vec<Vec<u8>> = self
+            .voters(poll_id)?
+            .into_iter()
+            .filter_map(|voter| voter.rankings)
+            .collect();
+        Ok(Some(tally::tally(poll.kind, candidates, &ballots)))
+    }
+}
+
+fn apply(
+    tx: &rusqlite::Transaction,
+    signature: &str,
+    index: usize,
+    event: &VotingEvent,
+) -> Result<()> {
+    match event {
+        VotingEvent::PollCreated(e) => {
+            tx.execute(
+                "INSERT OR REPLACE INTO polls (poll_id, authority, description, poll_start,
+                     poll_end, kind, governance_mint, reveal_end)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    int(e.poll_id),
+                    e.authority.to_string(),
+                    e.description,
+                    int(e.poll_start),
+                    int(e.poll_end),
+                    kind_name(e.kind),
+                    e.governance_mint.map(|mint| mint.to_string()),
+                    e.reveal_end.map(int),
+                ],
+            )?;
+        }
+        VotingEvent::CandidateAdded(e) => {
+            tx.execute(
+                "INSERT OR REPLACE INTO candidates (poll_id, name, idx) VALUES (?1, ?2, ?3)",
+                params![int(e.poll_id), e.candidate_name, int(e.index)],
+            )?;
+        }
+        VotingEvent::VoteCast(e) => {
+            let delegate = e.delegate.map(|delegate| delegate.to_string());
+            tx.execute(
+                "INSERT OR REPLACE INTO voters (poll_id, voter, candidate, weight, delegate)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    int(e.poll_id),
+                    e.voter.to_string(),
+                    e.candidate_name,
+                    int(e.weight),
+                    delegate,
+                ],
+            )?;
+            tx.execute(
+                "UPDATE candidates SET votes = votes + ?3 WHERE poll_id = ?1 AND name = ?2",
+                params![int(e.poll_id), e.candidate_name, int(e.weight)],
+            )?;
+            tx.execute(
+                "INSERT INTO votes (signature, event_index, poll_id, voter, action, candidate,
+                     weight, delegate)
+                 VALUES (?1, ?2, ?3, ?4, 'vote', ?5, ?6, ?7)",
+                params![
+                    signature,
+                    index as i64,
+                    int(e.poll_id),
+                    e.voter.to_string(),
+                    e.candidate_name,
+                    int(e.weight),
+                    delegate,
+                ],
+            )?;
+        }
+        VotingEvent::VoteChanged(e) => {
+            // Delegators counted with the vote follow it to the new candidate
+            tx.execute(
+                "UPDATE voters SET candidate = ?3
+                 WHERE poll_id = ?1 AND (voter = ?2 OR delegate = ?2)",
+                params![int(e.poll_id), e.voter.to_string(), e.to],
+            )?;
+            tx.execute(
+                "UPDATE candidates SET votes = votes - ?3 WHERE poll_id = ?1 AND name = ?2",
+                params![int(e.poll_id), e.from, int(e.weight)],
+            )?;
+            tx.execute(
+                "UPDATE candidates SET votes = votes + ?3 WHERE poll_id = ?1 AND name = ?2",
+                params![int(e.poll_id), e.to, int(e.weight)],
+            )?;
+            tx.execute(
+                "INSERT INTO votes (signature, event_index, poll_id, voter, action, candidate,
+                     previous, weight)
+                 VALUES (?1, ?2, ?3, ?4, 'change', ?5, ?6, ?7)",
+                params![
+                    signature,
+                    index as i64,
+                    int(e.poll_id),
+                    e.voter.to_string(),
+                    e.to,
+                    e.from,
+                    int(e.weight),
+                ],
+            )?;
+        }
+        VotingEvent::BallotCast(e) => {
+            tx.execute(
+                "INSERT OR REPLACE INTO voters (poll_id, voter, rankings, weight)
+                 VALUES (?1, ?2, ?3, 1)",
+                params![int(e.poll_id), e.voter.to_string(), e.rankings],
+            )?;
+            tx.execute(
+                "INSERT INTO votes (signature, event_index, poll_id, voter, action, rankings,
+                     weight)
+                 VALUES (?1, ?2, ?3, ?4, 'ballot', ?5, 1)",
+                params![
+                    signature,
+                    index as i64,
+                    int(e.poll_id),
+                    e.voter.to_string(),
+                    e.rankings,
+                ],
+            )?;
+        }
+        VotingEvent::PollFinalized(e) => {
+            tx.execute(
+                "UPDATE polls SET finalized = 1, winner = ?2 WHERE poll_id = ?1",
+                params![int(e.poll_id), e.winner],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Index every transaction of `program_id` the database hasn't seen yet,
+// oldest first, returning how many were indexed. Failed transactions are
+// recorded without events so later syncs don't fetch them again
+pub async fn sync(rpc: &RpcClient, program_id: &Pubkey, db: &mut Database) -> Result<usize> {
+    let commitment = CommitmentConfig::confirmed();
+    let until = db
+        .last_signature()?
+        .map(|signature| Signature::from_str(&signature))
+        .transpose()?;
+
+    // Signatures come newest first, a page at a time
+    let mut pending = Vec::new();
+    let mut before = None;
+    loop {
+        let config = GetConfirmedSignaturesForAddress2Config {
+            before,
+            until,
+            limit: Some(SIGNATURES_PER_PAGE),
+            commitment: Some(commitment),
+        };
+        let page = rpc
+            .get_signatures_for_address_with_config(program_id, config)
+            .await?;
+        let full = page.len() == SIGNATURES_PER_PAGE;
+        before = match page.last() {
+            Some(last) => Some(Signature::from_str(&last.signature)?),
+            None => None,
+        };
+        pending.extend(page);
+        if !full {
+            break;
+        }
+    }
+
+    let mut indexed = 0;
+    for status in pending.into_iter().rev() {
+        if db.is_indexed(&status.signature)? {
+            continue;
+        }
+        let events = if status.err.is_some() {
+            Vec::new()
+        } else {
+            let signature = Signature::from_str(&status.signature)?;
+            let config = RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Json),
+                commitment: Some(commitment),
+                max_supported_transaction_version: Some(0),
+            };
+            let tx = rpc
+                .get_transaction_with_config(&signature, config)
+                .await
+                .with_context(|| format!("Error fetching transaction {signature}"))?;
+            match tx.transaction.meta.map(|meta| meta.log_messages) {
+                Some(OptionSerializer::Some(logs)) => parse_events(program_id, &logs),
+                _ => Vec::new(),
+            }
+        };
+        db.index_transaction(&status.signature, status.slot, status.block_time, &events)?;
+        indexed += 1;
+    }
+    Ok(indexed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_line(event: &impl Event) -> String {
+        format!("Program data: {}", BASE64.encode(event.data()))
+    }
+
+    #[test]
+    fn only_events_logged_by_the_program_count() {
+        let other = Pubkey::new_unique();
+        let finalized = PollFinalized {
+            poll_id: 7,
+            winner: Some("Smooth".to_string()),
+        };
+        let added = CandidateAdded {
+            poll_id: 7,
+            candidate_name: "Crunchy".to_string(),
+            index: 1,
+        };
+        let logs = vec![
+            format!("Program {other} invoke [1]"),
+            data_line(&finalized),
+            format!("Program {other} success"),
+            format!("Program {} invoke [1]", voting::ID),
+            "Program log: Instruction: InitializeCandidate".to_string(),
+            format!("Program {} invoke [2]", solana_sdk::system_program::ID),
+            data_line(&finalized),
+            format!("Program {} success", solana_sdk::system_program::ID),
+            data_line(&added),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            format!(
+                "Program {} consumed 5000 of 200000 compute units",
+                voting::ID
+            ),
+            format!("Program {} success", voting::ID),
+        ];
+
+        let events = parse_events(&voting::ID, &logs);
+        assert_eq!(events.len(), 1);
+        let VotingEvent::CandidateAdded(event) = &events[0] else {
+            panic!("unexpected event {:?}", events[0]);
+        };
+        assert_eq!(event.candidate_name, "Crunchy");
+        assert_eq!(event.index, 1);
+    }
+}
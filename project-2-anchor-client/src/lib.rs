@@ -0,0 +1,13 @@
+pub mod batch;
+pub mod compute_budget;
+pub mod delegation;
+pub mod indexer;
+pub mod multisig;
+pub mod offline;
+pub mod sealed;
+pub mod signer;
+pub mod tally;
+pub mod vote;
+
+// Program ID of the voting application
+pub const VOTING_PROGRAM_ID: &str = "5Couhd2qWo7v3L8LR3Q4daDPdFyJpV8MNqi3wkzNWGvu";
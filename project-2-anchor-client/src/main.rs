@@ -1,71 +1,1295 @@
-use anchor_client::{Client, Cluster};
-use anyhow::Result;
-use sha2::{Digest, Sha256};
+use anchor_client::{Client, Cluster, Program};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    instruction::AccountMeta, instruction::Instruction, pubkey::Pubkey,
-    signature::read_keypair_file, signer::Signer,
+    clock::Clock, commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey, signer::Signer,
+    sysvar, transaction::Transaction,
+};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
+};
+use voting::PollKind;
+use voting_client::{
+    batch::{self, BatchConfig, RowReport},
+    compute_budget::{ComputeBudget, UnitLimit, UnitPrice},
+    delegation::{self, DelegationGraph, MAX_DELEGATORS_PER_VOTE},
+    multisig::{
+        transaction_address, Multisig, MultisigState, VaultTransactionMessage,
+        VaultTransactionState, SQUADS_PROGRAM_ID,
+    },
+    offline::{self, OfflineVote, TxEncoding},
+    sealed::{BallotStore, SealedBallot},
+    signer::{load_signer, SignerSource, VoterSigner},
+    tally,
+    vote::{self, send_vote, GovernanceTokens, SendOptions},
+    VOTING_PROGRAM_ID,
 };
-use std::{rc::Rc, str::FromStr};
 
-// Program ID of the voting application
-const VOTING_PROGRAM_ID: &str = "5Couhd2qWo7v3L8LR3Q4daDPdFyJpV8MNqi3wkzNWGvu";
+// Close instructions per transaction, within the transaction size limit
+const CLOSES_PER_TRANSACTION: usize = 8;
 
-// The poll ID you want to vote in
-const POLL_ID: u64 = 1;
+#[derive(Parser)]
+#[command(about = "Cast votes with the Solana voting program")]
+struct Cli {
+    /// Cluster to connect to: devnet, localnet, mainnet or an RPC URL
+    #[arg(long, short = 'u', global = true, default_value = "devnet")]
+    url: Cluster,
+    /// Program ID of the voting application
+    #[arg(long, global = true, default_value = VOTING_PROGRAM_ID)]
+    program_id: Pubkey,
+    #[command(subcommand)]
+    command: Command,
+}
 
-// The name of the candidate you want to vote for
-const CANDIDATE_NAME: &str = "Smooth"; // Or "Crunchy"
+#[derive(Subcommand)]
+enum Command {
+    /// Create a poll
+    CreatePoll {
+        /// Payer's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// ID of the new poll
+        #[arg(long)]
+        poll_id: u64,
+        /// What the poll is about
+        #[arg(long)]
+        description: String,
+        /// Unix timestamp the poll opens at
+        #[arg(long, default_value_t = 0)]
+        poll_start: u64,
+        /// Unix timestamp the poll closes at
+        #[arg(long, default_value_t = u64::MAX)]
+        poll_end: u64,
+        /// Weigh votes by the tokens of this SPL mint that voters lock until the poll ends
+        #[arg(long, conflicts_with = "reveal_end")]
+        governance_mint: Option<Pubkey>,
+        /// Use sealed ballots, committed until the poll closes and revealed until this
+        /// Unix timestamp
+        #[arg(long)]
+        reveal_end: Option<u64>,
+        /// How ballots are cast and counted
+        #[arg(long, value_enum, default_value = "single-choice")]
+        kind: Kind,
+    },
+    /// Add a candidate to a poll
+    AddCandidate {
+        /// Payer's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The poll ID to add the candidate to
+        #[arg(long)]
+        poll_id: u64,
+        /// Name of the new candidate
+        #[arg(long)]
+        candidate: String,
+    },
+    /// Cast a single vote
+    Vote {
+        /// Voter's signer: a keypair path, `usb://ledger`, `env://NAME` or `exec:COMMAND`
+        /// (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The poll ID you want to vote in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+        /// The name of the candidate you want to vote for
+        #[arg(long, default_value = "Smooth")]
+        candidate: String,
+        /// Comma-separated candidates for an approval poll, or a ranked-choice poll's
+        /// ranking from most to least preferred
+        #[arg(long, value_delimiter = ',', conflicts_with = "candidate")]
+        ballot: Option<Vec<String>>,
+        /// Governance tokens (in base units) to lock as vote weight in a token-weighted
+        /// poll; defaults to your whole balance
+        #[arg(long)]
+        tokens: Option<u64>,
+        /// Where salts of sealed ballots are kept until they are revealed
+        /// (defaults to ~/.config/voting-client/ballots.json)
+        #[arg(long)]
+        ballots: Option<PathBuf>,
+        #[command(flatten)]
+        send: SendArgs,
+    },
+    /// Reveal your sealed ballots once their polls close
+    Reveal {
+        /// Voter's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// Only reveal the ballot in this poll
+        #[arg(long)]
+        poll_id: Option<u64>,
+        /// Where salts of sealed ballots are kept
+        /// (defaults to ~/.config/voting-client/ballots.json)
+        #[arg(long)]
+        ballots: Option<PathBuf>,
+        /// Wait for polls that are still accepting ballots to close, then reveal
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Count an approval or ranked-choice poll round by round
+    Tally {
+        /// The poll ID to count
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+        /// Also crank the on-chain count to completion, paid for by this signer
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// Crank the on-chain count after the poll has ended
+        #[arg(long)]
+        crank: bool,
+    },
+    /// Write the winner of a closed poll into the poll account
+    Finalize {
+        /// Payer's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The poll ID to finalize
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// Reclaim rent from your receipt or ballot in a finalized poll, and from its
+    /// candidates if you created the poll
+    Close {
+        /// Signer of the voter or poll creator (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The finalized poll's ID
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// Reclaim governance tokens locked in a token-weighted poll after it ends
+    Withdraw {
+        /// Voter's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The poll ID you voted in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// Move your vote in a poll to another candidate
+    ChangeVote {
+        /// Voter's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// The poll ID you voted in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+        /// The candidate your vote should count for
+        #[arg(long)]
+        candidate: String,
+    },
+    /// Delegate your vote to another member, or inspect delegations
+    Delegate {
+        /// Delegator's signer (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        #[command(subcommand)]
+        command: DelegateCommand,
+    },
+    /// Show who a wallet voted for in a poll
+    Receipt {
+        /// Signer whose vote to show (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        /// Show this wallet's vote instead of the signer's
+        #[arg(long)]
+        voter: Option<Pubkey>,
+        /// The poll ID to look up
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+    /// Cast votes from a file of `<signer>,<poll id>,<candidate>` rows
+    Batch {
+        /// Batch file to read rows from
+        file: PathBuf,
+        /// Maximum number of votes in flight at once
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+        #[command(flatten)]
+        send: SendArgs,
+        /// Write the per-row CSV report here instead of stdout
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Build an unsigned vote transaction against a durable nonce, for offline signing
+    Build {
+        /// Voter's public key
+        #[arg(long)]
+        voter: Pubkey,
+        /// Fee payer's public key (defaults to the voter)
+        #[arg(long)]
+        fee_payer: Option<Pubkey>,
+        /// Durable nonce account
+        #[arg(long)]
+        nonce_account: Pubkey,
+        /// Authority of the nonce account (defaults to the fee payer)
+        #[arg(long)]
+        nonce_authority: Option<Pubkey>,
+        /// Current nonce value; read from the nonce account when omitted
+        #[arg(long)]
+        nonce: Option<Hash>,
+        /// The poll ID you want to vote in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+        /// The name of the candidate you want to vote for
+        #[arg(long, default_value = "Smooth")]
+        candidate: String,
+        #[command(flatten)]
+        budget: BudgetArgs,
+        /// Text encoding of the transaction file
+        #[arg(long, default_value = "base64")]
+        encoding: TxEncoding,
+        /// Write the transaction here instead of stdout
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Add a signature to a transaction file; needs no network access
+    Sign {
+        /// Transaction file produced by `build` or a previous `sign`
+        input: PathBuf,
+        /// Signer to sign with (defaults to ~/.config/solana/id.json)
+        #[arg(long, short = 'k')]
+        keypair: Option<SignerSource>,
+        #[command(flatten)]
+        file: TxFileArgs,
+        /// Write the signed transaction here instead of back to the input file
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Vote from a multisig vault through multisig proposals
+    Multisig {
+        #[command(flatten)]
+        multisig: MultisigArgs,
+        #[command(subcommand)]
+        command: MultisigCommand,
+    },
+    /// Broadcast a fully signed transaction file
+    Submit {
+        /// Transaction file with every required signature
+        input: PathBuf,
+        #[command(flatten)]
+        file: TxFileArgs,
+    },
+}
 
-// #[tokio::main]
-fn main() -> Result<()> {
-    let signer = read_keypair_file("/Users/joseph/.config/solana/id.json")
-        .map_err(|e| anyhow::anyhow!("Error reading keypair file: {}", e))?;
-    let signer_pub_key = signer.pubkey();
-    let client = Client::new(Cluster::Devnet, Rc::new(signer));
-    let program = client.program(Pubkey::from_str(VOTING_PROGRAM_ID)?)?;
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    /// One candidate per vote
+    SingleChoice,
+    /// Each ballot approves any number of candidates
+    Approval,
+    /// Each ballot ranks candidates, counted by instant runoff
+    RankedChoice,
+}
 
-    let poll_id = POLL_ID;
-    let candidate = CANDIDATE_NAME;
+impl From<Kind> for PollKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::SingleChoice => PollKind::SingleChoice,
+            Kind::Approval => PollKind::Approval,
+            Kind::RankedChoice => PollKind::RankedChoice,
+        }
+    }
+}
 
-    let (poll_account_pubkey, _poll_bump) = Pubkey::find_program_address(
-        &[b"poll", &poll_id.to_le_bytes()],
-        &Pubkey::from_str(VOTING_PROGRAM_ID)?,
-    );
-    println!("Poll Account: {}", poll_account_pubkey);
+#[derive(Subcommand)]
+enum DelegateCommand {
+    /// Let another member's votes count for you while you don't vote yourself
+    Set {
+        /// The member to delegate to
+        #[arg(long)]
+        to: Pubkey,
+        /// Only delegate in this poll; delegates in every poll when omitted
+        #[arg(long)]
+        poll_id: Option<u64>,
+    },
+    /// Remove your delegation
+    Clear {
+        /// Remove the delegation for this poll instead of the global one
+        #[arg(long)]
+        poll_id: Option<u64>,
+    },
+    /// Show the delegation graph of a poll and the weight each vote would carry
+    Show {
+        /// The poll ID to show delegations in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+    },
+}
 
-    let (candidate_account_pubkey, _candidate_bump) = Pubkey::find_program_address(
-        &[&poll_id.to_le_bytes(), candidate.as_bytes()],
-        &Pubkey::from_str(VOTING_PROGRAM_ID)?,
-    );
-    println!("Candidate Account: {candidate_account_pubkey}");
-
-    let mut hasher = Sha256::new();
-    hasher.update(b"global:vote");
-    let discriminator = &hasher.finalize()[..8];
-
-    let tx = program
-        .request()
-        .accounts(vec![
-            AccountMeta::new(signer_pub_key, true),
-            AccountMeta::new(poll_account_pubkey, false),
-            AccountMeta::new(candidate_account_pubkey, false),
-        ])
-        .instruction(Instruction {
-            program_id: program.id(),
-            accounts: vec![
-                AccountMeta::new(signer_pub_key, true),
-                AccountMeta::new(poll_account_pubkey, false),
-                AccountMeta::new(candidate_account_pubkey, false),
-            ],
-            data: vec![
-                discriminator.to_vec(),
-                candidate.as_bytes().to_vec(),
-                poll_id.to_le_bytes().to_vec(),
-            ]
-            .concat(),
-        })
-        .send()?;
-
-    println!("Vote cast successfully! Transaction signature: {}", tx);
+#[derive(Subcommand)]
+enum MultisigCommand {
+    /// Wrap a vote from the vault into a new vault transaction and proposal
+    Propose {
+        /// Index of the vault that votes
+        #[arg(long, default_value_t = 0)]
+        vault_index: u8,
+        /// The poll ID you want to vote in
+        #[arg(long, default_value_t = 1)]
+        poll_id: u64,
+        /// The name of the candidate you want to vote for
+        #[arg(long, default_value = "Smooth")]
+        candidate: String,
+    },
+    /// Approve a pending vote proposal
+    Approve {
+        /// Transaction index printed by `propose`
+        #[arg(long)]
+        transaction_index: u64,
+    },
+    /// Execute an approved vote proposal
+    Execute {
+        /// Transaction index printed by `propose`
+        #[arg(long)]
+        transaction_index: u64,
+    },
+}
+
+#[derive(Args)]
+struct MultisigArgs {
+    /// Multisig account address
+    #[arg(long)]
+    multisig: Pubkey,
+    /// Program ID of the Squads-compatible multisig program
+    #[arg(long, default_value = SQUADS_PROGRAM_ID)]
+    multisig_program_id: Pubkey,
+    /// Member's signer (defaults to ~/.config/solana/id.json)
+    #[arg(long, short = 'k')]
+    keypair: Option<SignerSource>,
+}
+
+#[derive(Args)]
+struct SendArgs {
+    /// Attempts per vote before giving up, each with a fresh blockhash
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: u32,
+    #[command(flatten)]
+    budget: BudgetArgs,
+}
+
+#[derive(Args)]
+struct BudgetArgs {
+    /// Compute unit limit: a unit count, `simulate` to size it from a simulation, or `default`
+    #[arg(long, default_value = "default")]
+    compute_unit_limit: UnitLimit,
+    /// Compute unit price in micro-lamports, `auto` to estimate from recent fees, or `default`
+    #[arg(long, default_value = "default")]
+    compute_unit_price: UnitPrice,
+    /// Headroom added to the simulated compute units, in percent
+    #[arg(long, default_value_t = 10)]
+    compute_unit_margin: u32,
+    /// Percentile of recent prioritization fees used by `--compute-unit-price auto`
+    #[arg(long, default_value_t = 75, value_parser = clap::value_parser!(u8).range(0..=100))]
+    fee_percentile: u8,
+}
+
+impl From<BudgetArgs> for ComputeBudget {
+    fn from(args: BudgetArgs) -> Self {
+        Self {
+            limit: args.compute_unit_limit,
+            price: args.compute_unit_price,
+            margin_percent: args.compute_unit_margin,
+            fee_percentile: args.fee_percentile,
+        }
+    }
+}
+
+impl From<SendArgs> for SendOptions {
+    fn from(args: SendArgs) -> Self {
+        Self {
+            max_attempts: args.max_attempts,
+            compute_budget: args.budget.into(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct TxFileArgs {
+    /// Text encoding of the transaction file
+    #[arg(long, default_value = "base64")]
+    encoding: TxEncoding,
+    /// Message hash printed by `build`; aborts unless the transaction still has it
+    #[arg(long)]
+    message_hash: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::CreatePoll {
+            keypair,
+            poll_id,
+            description,
+            poll_start,
+            poll_end,
+            governance_mint,
+            reveal_end,
+            kind,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let kind = PollKind::from(kind);
+            if kind != PollKind::SingleChoice && (governance_mint.is_some() || reveal_end.is_some())
+            {
+                bail!("Approval and ranked-choice polls can't be token-weighted or sealed");
+            }
+            let ix = match (governance_mint, reveal_end) {
+                _ if kind != PollKind::SingleChoice => vote::initialize_ballot_poll_instruction(
+                    &cli.program_id,
+                    &program.payer(),
+                    poll_id,
+                    &description,
+                    poll_start,
+                    poll_end,
+                    kind,
+                ),
+                (_, Some(reveal_end)) => vote::initialize_sealed_poll_instruction(
+                    &cli.program_id,
+                    &program.payer(),
+                    poll_id,
+                    &description,
+                    poll_start,
+                    poll_end,
+                    reveal_end,
+                ),
+                (Some(mint), None) => {
+                    let tokens = GovernanceTokens {
+                        mint,
+                        token_program: program.rpc().get_account(&mint).await?.owner,
+                    };
+                    vote::initialize_token_poll_instruction(
+                        &cli.program_id,
+                        &program.payer(),
+                        poll_id,
+                        &description,
+                        poll_start,
+                        poll_end,
+                        &tokens,
+                    )
+                }
+                (None, None) => vote::initialize_poll_instruction(
+                    &cli.program_id,
+                    &program.payer(),
+                    poll_id,
+                    &description,
+                    poll_start,
+                    poll_end,
+                ),
+            };
+            let tx = program.request().instruction(ix).send().await?;
+            println!(
+                "Poll Account: {}",
+                vote::poll_address(&cli.program_id, poll_id)
+            );
+            println!("Poll created! Transaction signature: {tx}");
+        }
+        Command::AddCandidate {
+            keypair,
+            poll_id,
+            candidate,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let ix = vote::initialize_candidate_instruction(
+                &cli.program_id,
+                &program.payer(),
+                poll_id,
+                &candidate,
+            );
+            let tx = program.request().instruction(ix).send().await?;
+            println!(
+                "Candidate Account: {}",
+                vote::candidate_address(&cli.program_id, poll_id, &candidate)
+            );
+            println!("Candidate added! Transaction signature: {tx}");
+        }
+        Command::Vote {
+            keypair,
+            poll_id,
+            candidate,
+            ballot,
+            tokens,
+            ballots,
+            send,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let voter = program.payer();
+            if let Some(receipt) = vote::fetch_receipt(&program, poll_id, &voter).await? {
+                if receipt.commitment.is_some() {
+                    bail!("You already committed a sealed ballot in poll {poll_id}");
+                }
+                bail!(
+                    "You already voted for {} in poll {poll_id}; use `change-vote` to move your vote",
+                    receipt.candidate_name
+                );
+            }
+
+            println!(
+                "Poll Account: {}",
+                vote::poll_address(&cli.program_id, poll_id)
+            );
+            let poll = vote::fetch_poll(&program, poll_id).await?;
+            if poll.kind != PollKind::SingleChoice {
+                let names = ballot.unwrap_or_else(|| vec![candidate]);
+                let candidates = tally::fetch_candidates(&program, poll_id).await?;
+                let rankings = names
+                    .iter()
+                    .map(|name| {
+                        candidates
+                            .iter()
+                            .find(|c| &c.candidate_name == name)
+                            .map(|c| c.index as u8)
+                            .with_context(|| format!("Poll {poll_id} has no candidate {name}"))
+                    })
+                    .collect::<Result<Vec<u8>>>()?;
+                println!(
+                    "Ballot Account: {}",
+                    vote::ballot_address(&cli.program_id, poll_id, &voter)
+                );
+                let tx = vote::send_ballot(&program, poll_id, &rankings, &send.into())
+                    .await
+                    .result?;
+                println!("Ballot cast successfully! Transaction signature: {tx}");
+                match poll.kind {
+                    PollKind::Approval => println!("You approved {}", names.join(", ")),
+                    _ => println!("You ranked {}", names.join(" > ")),
+                }
+                return Ok(());
+            }
+            if ballot.is_some() {
+                bail!("Poll {poll_id} takes a single --candidate");
+            }
+            println!(
+                "Candidate Account: {}",
+                vote::candidate_address(&cli.program_id, poll_id, &candidate)
+            );
+
+            let governance = vote::governance_tokens(&program, &poll).await?;
+            let outcome = match governance {
+                _ if poll.reveal_end.is_some() => {
+                    // Save the salt before committing: without it the ballot can't be revealed
+                    let ballot = SealedBallot::new(&cli.program_id, poll_id, &voter, &candidate);
+                    let commitment = ballot.commitment()?;
+                    let path = ballots.unwrap_or_else(BallotStore::default_path);
+                    BallotStore::open(&path)?.insert(ballot)?;
+                    println!(
+                        "Sealed ballot saved to {}; run `reveal` after the poll closes",
+                        path.display()
+                    );
+                    vote::send_sealed_vote(&program, poll_id, commitment, &send.into()).await
+                }
+                Some(governance) => {
+                    let amount = match tokens {
+                        Some(amount) => amount,
+                        None => program
+                            .rpc()
+                            .get_token_account_balance(&governance.voter_account(&voter))
+                            .await?
+                            .amount
+                            .parse()?,
+                    };
+                    println!("Locking {amount} governance tokens until the poll ends");
+                    vote::send_weighted_vote(
+                        &program,
+                        poll_id,
+                        &candidate,
+                        &governance,
+                        amount,
+                        &send.into(),
+                    )
+                    .await
+                }
+                None if tokens.is_some() => bail!("Poll {poll_id} is not token-weighted"),
+                None => {
+                    let mut delegators = vote_delegators(&program, poll_id).await?;
+                    if delegators.len() > MAX_DELEGATORS_PER_VOTE {
+                        eprintln!(
+                            "Only {MAX_DELEGATORS_PER_VOTE} of {} delegators fit in one vote; \
+                             the others remain uncounted and can still vote themselves",
+                            delegators.len()
+                        );
+                        delegators.truncate(MAX_DELEGATORS_PER_VOTE);
+                    }
+                    if delegators.is_empty() {
+                        send_vote(&program, poll_id, &candidate, &send.into()).await
+                    } else {
+                        println!(
+                            "Your vote also counts for {} delegator(s)",
+                            delegators.len()
+                        );
+                        vote::send_delegated_vote(
+                            &program,
+                            poll_id,
+                            &candidate,
+                            &delegators,
+                            &send.into(),
+                        )
+                        .await
+                    }
+                }
+            };
+            let tx = outcome.result?;
+            println!("Vote cast successfully! Transaction signature: {}", tx);
+            println!("You voted for {candidate}");
+        }
+        Command::Reveal {
+            keypair,
+            poll_id,
+            ballots,
+            wait,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let path = ballots.unwrap_or_else(BallotStore::default_path);
+            run_reveal(&program, &path, poll_id, wait).await?;
+        }
+        Command::Tally {
+            poll_id,
+            keypair,
+            crank,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            run_tally(&program, poll_id, crank).await?;
+        }
+        Command::Finalize { keypair, poll_id } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let poll = vote::fetch_poll(&program, poll_id).await?;
+            if poll.finalized {
+                bail!("Poll {poll_id} is already finalized");
+            }
+            if poll.kind != PollKind::SingleChoice
+                && !tally::fetch_tally(&program, poll_id).await?.complete
+            {
+                bail!("Poll {poll_id} has not been tallied; run `tally --crank` first");
+            }
+            let candidates = tally::fetch_candidates(&program, poll_id).await?;
+            let names: Vec<&str> = candidates
+                .iter()
+                .map(|candidate| candidate.candidate_name.as_str())
+                .collect();
+
+            let ix = vote::finalize_poll_instruction(&cli.program_id, poll_id, poll.kind, &names);
+            let tx = program.request().instruction(ix).send().await?;
+            println!("Poll finalized! Transaction signature: {tx}");
+            match vote::fetch_poll(&program, poll_id).await?.winner {
+                Some(winner) => println!("Winner: {winner}"),
+                None => println!("No votes were counted"),
+            }
+        }
+        Command::Close { keypair, poll_id } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            run_close(&program, poll_id).await?;
+        }
+        Command::Withdraw { keypair, poll_id } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let governance = vote::fetch_governance_tokens(&program, poll_id)
+                .await?
+                .with_context(|| format!("Poll {poll_id} is not token-weighted"))?;
+            let ix = vote::withdraw_escrow_instruction(
+                &cli.program_id,
+                &program.payer(),
+                poll_id,
+                &governance,
+            );
+            let tx = program.request().instruction(ix).send().await?;
+            println!("Tokens returned! Transaction signature: {tx}");
+        }
+        Command::ChangeVote {
+            keypair,
+            poll_id,
+            candidate,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let voter = program.payer();
+            let receipt = vote::fetch_receipt(&program, poll_id, &voter)
+                .await?
+                .with_context(|| format!("You have not voted in poll {poll_id} yet"))?;
+
+            let ix = vote::change_vote_instruction(
+                &cli.program_id,
+                &voter,
+                poll_id,
+                &receipt.candidate_name,
+                &candidate,
+            );
+            let tx = program.request().instruction(ix).send().await?;
+            println!("Vote changed! Transaction signature: {tx}");
+            println!(
+                "You voted for {candidate} (previously {})",
+                receipt.candidate_name
+            );
+        }
+        Command::Delegate { keypair, command } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            run_delegate(&program, command).await?;
+        }
+        Command::Receipt {
+            keypair,
+            voter,
+            poll_id,
+        } => {
+            let program = program_for(cli.url, cli.program_id, keypair)?;
+            let voter = voter.unwrap_or_else(|| program.payer());
+            match vote::fetch_receipt(&program, poll_id, &voter).await? {
+                Some(receipt) if receipt.delegate.is_some() => {
+                    let delegate = receipt.delegate.unwrap();
+                    let candidate = vote::fetch_receipt(&program, poll_id, &delegate)
+                        .await?
+                        .map_or(receipt.candidate_name, |receipt| receipt.candidate_name);
+                    println!(
+                        "{voter}'s vote in poll {poll_id} was cast by delegate {delegate} for {candidate}"
+                    );
+                }
+                Some(receipt) if receipt.commitment.is_some() => println!(
+                    "{voter} committed a sealed ballot in poll {poll_id} that has not been revealed"
+                ),
+                Some(receipt) if receipt.weight == 1 => println!(
+                    "{voter} voted for {} in poll {poll_id}",
+                    receipt.candidate_name
+                ),
+                Some(receipt) => println!(
+                    "{voter} voted for {} in poll {poll_id} with weight {} ({} tokens still escrowed)",
+                    receipt.candidate_name, receipt.weight, receipt.escrowed
+                ),
+                None => println!("{voter} has not voted in poll {poll_id}"),
+            }
+        }
+        Command::Batch {
+            file,
+            concurrency,
+            send,
+            report,
+        } => {
+            let contents = fs::read_to_string(&file)
+                .with_context(|| format!("Error reading batch file {}", file.display()))?;
+            let rows = batch::parse_rows(&contents)?;
+            let config = BatchConfig {
+                cluster: cli.url,
+                program_id: cli.program_id,
+                concurrency,
+                send: send.into(),
+            };
+
+            let reports = batch::run_batch(&config, rows).await;
+
+            let mut csv = format!("{}\n", RowReport::CSV_HEADER);
+            for report in &reports {
+                csv.push_str(&format!("{report}\n"));
+            }
+            match report {
+                Some(path) => fs::write(&path, csv)
+                    .with_context(|| format!("Error writing report {}", path.display()))?,
+                None => print!("{csv}"),
+            }
+
+            let (ok, failed) = batch::summary(&reports);
+            eprintln!("{ok} vote(s) succeeded, {failed} failed");
+        }
+        Command::Build {
+            voter,
+            fee_payer,
+            nonce_account,
+            nonce_authority,
+            nonce,
+            poll_id,
+            candidate,
+            budget,
+            encoding,
+            output,
+        } => {
+            let fee_payer = fee_payer.unwrap_or(voter);
+            let vote = OfflineVote {
+                program_id: cli.program_id,
+                voter,
+                fee_payer,
+                nonce_account,
+                nonce_authority: nonce_authority.unwrap_or(fee_payer),
+                poll_id,
+                candidate,
+            };
+            let budget = ComputeBudget::from(budget);
+
+            let rpc = RpcClient::new_with_commitment(
+                cli.url.url().to_string(),
+                CommitmentConfig::confirmed(),
+            );
+            let nonce = match nonce {
+                Some(nonce) => nonce,
+                None => offline::fetch_nonce(&rpc, &nonce_account, &vote.nonce_authority).await?,
+            };
+            let budget_ixs = budget
+                .instructions(&rpc, &fee_payer, &[vote.vote_instruction()])
+                .await?;
+
+            let tx = vote.build(nonce, &budget_ixs);
+            write_transaction(&tx, encoding, output.as_ref())?;
+            print_status(&tx);
+        }
+        Command::Sign {
+            input,
+            keypair,
+            file,
+            output,
+        } => {
+            let mut tx = read_transaction(&input, &file)?;
+            let source = keypair.unwrap_or_else(|| SignerSource::File(default_keypair_path()));
+            let signer = load_signer(&source)?;
+
+            offline::sign_offline(&mut tx, &signer)?;
+            write_transaction(&tx, file.encoding, Some(output.as_ref().unwrap_or(&input)))?;
+            eprintln!("Signed by {}", signer.pubkey());
+            print_status(&tx);
+        }
+        Command::Multisig { multisig, command } => {
+            run_multisig(cli.url, cli.program_id, multisig, command).await?;
+        }
+        Command::Submit { input, file } => {
+            let tx = read_transaction(&input, &file)?;
+            offline::check_fully_signed(&tx)?;
+
+            let rpc = RpcClient::new_with_commitment(
+                cli.url.url().to_string(),
+                CommitmentConfig::confirmed(),
+            );
+            let signature = rpc.send_and_confirm_transaction(&tx).await?;
+            println!("Vote cast successfully! Transaction signature: {signature}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_multisig(
+    cluster: Cluster,
+    program_id: Pubkey,
+    args: MultisigArgs,
+    command: MultisigCommand,
+) -> Result<()> {
+    let multisig = Multisig::new(args.multisig_program_id, args.multisig);
+    let program = program_for(cluster, program_id, args.keypair)?;
+    let member = program.payer();
+    let rpc = program.rpc();
+
+    match command {
+        MultisigCommand::Propose {
+            vault_index,
+            poll_id,
+            candidate,
+        } => {
+            let state =
+                MultisigState::from_account_data(&rpc.get_account_data(&multisig.address).await?)?;
+            let transaction_index = state.transaction_index + 1;
+            let vault = multisig.vault(vault_index);
+            let vote = vote::vote_instruction(&program_id, &vault, poll_id, &candidate);
+            let message = VaultTransactionMessage::compile(&vault, &[vote])?;
+
+            let tx = program
+                .request()
+                .instruction(multisig.create_transaction(
+                    &member,
+                    transaction_index,
+                    vault_index,
+                    &message,
+                )?)
+                .instruction(multisig.create_proposal(&member, transaction_index))
+                .send()
+                .await?;
+            println!("Vault: {vault}");
+            println!("Proposal created for transaction index {transaction_index}: {tx}");
+        }
+        MultisigCommand::Approve { transaction_index } => {
+            let tx = program
+                .request()
+                .instruction(multisig.approve(&member, transaction_index))
+                .send()
+                .await?;
+            println!("Proposal {transaction_index} approved: {tx}");
+        }
+        MultisigCommand::Execute { transaction_index } => {
+            let address =
+                transaction_address(&multisig.program_id, &multisig.address, transaction_index);
+            let state =
+                VaultTransactionState::from_account_data(&rpc.get_account_data(&address).await?)?;
+            let tx = program
+                .request()
+                .instruction(multisig.execute(&member, transaction_index, &state.message)?)
+                .send()
+                .await?;
+            println!("Vote cast successfully from the multisig vault! Transaction signature: {tx}");
+        }
+    }
+    Ok(())
+}
+
+fn read_transaction(path: &Path, file: &TxFileArgs) -> Result<Transaction> {
+    let encoded = fs::read_to_string(path)
+        .with_context(|| format!("Error reading transaction file {}", path.display()))?;
+    let tx = offline::decode_transaction(&encoded, file.encoding)?;
+    offline::check_message_hash(&tx, &file.message_hash)?;
+    Ok(tx)
+}
+
+fn write_transaction(tx: &Transaction, encoding: TxEncoding, path: Option<&PathBuf>) -> Result<()> {
+    let encoded = offline::encode_transaction(tx, encoding)?;
+    match path {
+        Some(path) => fs::write(path, format!("{encoded}\n"))
+            .with_context(|| format!("Error writing transaction file {}", path.display())),
+        None => {
+            println!("{encoded}");
+            Ok(())
+        }
+    }
+}
+
+// Status goes to stderr so that stdout can carry the transaction itself
+fn print_status(tx: &Transaction) {
+    eprintln!("Message hash: {}", offline::message_hash(tx));
+    let missing = offline::missing_signers(tx);
+    if missing.is_empty() {
+        eprintln!("All signatures present; ready to submit");
+    } else {
+        for signer in missing {
+            eprintln!("Awaiting signature from {signer}");
+        }
+    }
+}
+
+// Reveal the signer's stored sealed ballots whose polls have closed
+async fn run_reveal(
+    program: &Program<Rc<VoterSigner>>,
+    path: &Path,
+    poll_id: Option<u64>,
+    wait: bool,
+) -> Result<()> {
+    let voter = program.payer();
+    let mut store = BallotStore::open(path)?;
+    let ballots: Vec<SealedBallot> = store
+        .pending(&program.id(), &voter)
+        .into_iter()
+        .filter(|ballot| poll_id.is_none_or(|id| ballot.poll_id == id))
+        .collect();
+    if ballots.is_empty() {
+        println!("No sealed ballots to reveal");
+        return Ok(());
+    }
+
+    for ballot in ballots {
+        let poll = vote::fetch_poll(program, ballot.poll_id).await?;
+        let reveal_end = poll
+            .reveal_end
+            .with_context(|| format!("Poll {} does not use sealed ballots", ballot.poll_id))?;
+
+        let mut now = cluster_time(program).await?;
+        while now <= poll.poll_end && wait {
+            let remaining = poll.poll_end - now + 1;
+            println!("Poll {} closes in {remaining}s", ballot.poll_id);
+            tokio::time::sleep(Duration::from_secs(remaining.min(30))).await;
+            now = cluster_time(program).await?;
+        }
+        if now <= poll.poll_end {
+            println!(
+                "Poll {} is still accepting ballots; reveal after it closes",
+                ballot.poll_id
+            );
+            continue;
+        }
+        if now > reveal_end {
+            eprintln!(
+                "The reveal window of poll {} has closed; your ballot was not counted",
+                ballot.poll_id
+            );
+            store.remove(&ballot)?;
+            continue;
+        }
+
+        let ix = vote::reveal_vote_instruction(
+            &program.id(),
+            &voter,
+            ballot.poll_id,
+            &ballot.candidate,
+            ballot.salt()?,
+        );
+        let tx = program.request().instruction(ix).send().await?;
+        store.remove(&ballot)?;
+        println!(
+            "You voted for {} in poll {}! Transaction signature: {tx}",
+            ballot.candidate, ballot.poll_id
+        );
+    }
+    Ok(())
+}
+
+// Print the off-chain count of an approval or ranked-choice poll, optionally
+// cranking the on-chain count and checking that both agree
+async fn run_tally(program: &Program<Rc<VoterSigner>>, poll_id: u64, crank: bool) -> Result<()> {
+    let poll = vote::fetch_poll(program, poll_id).await?;
+    if poll.kind == PollKind::SingleChoice {
+        bail!("Poll {poll_id} is single-choice; its votes are counted as they are cast");
+    }
+    let candidates = tally::fetch_candidates(program, poll_id).await?;
+    let ballots: Vec<Vec<u8>> = tally::fetch_ballots(program, poll_id)
+        .await?
+        .into_iter()
+        .map(|(_, ballot)| ballot.rankings)
+        .collect();
+    let name = |index: u8| candidates[index as usize].candidate_name.as_str();
+
+    let result = tally::tally(poll.kind, candidates.len(), &ballots);
+    println!("{} ballot(s) in poll {poll_id}", ballots.len());
+    for (round, counts) in result.rounds.iter().enumerate() {
+        println!("Round {}:", round + 1);
+        for candidate in &candidates {
+            println!(
+                "  {}: {}",
+                candidate.candidate_name, counts.counts[candidate.index as usize]
+            );
+        }
+        if let Some(eliminated) = counts.eliminated {
+            println!("  {} is eliminated", name(eliminated));
+        }
+    }
+    match result.winner {
+        Some(winner) => println!("Winner: {}", name(winner)),
+        None => println!("No winner: no ballots were cast"),
+    }
+
+    if crank {
+        let tally = tally::crank_tally(program, poll_id).await?;
+        if tally.winner != result.winner {
+            bail!(
+                "On-chain tally disagrees: winner {:?} after round {}",
+                tally.winner.map(name),
+                tally.round
+            );
+        }
+        println!("On-chain tally complete after round {}", tally.round);
+    }
+    Ok(())
+}
+
+// Close the signer's accounts in a finalized poll: their receipt (withdrawing
+// any escrowed tokens first) or ballot, and the candidates if they created the poll
+async fn run_close(program: &Program<Rc<VoterSigner>>, poll_id: u64) -> Result<()> {
+    let signer = program.payer();
+    let poll = vote::fetch_poll(program, poll_id).await?;
+    if !poll.finalized {
+        bail!("Poll {poll_id} is not finalized yet; run `finalize` after it closes");
+    }
+
+    let mut instructions = Vec::new();
+    let mut withdraws = false;
+    if let Some(receipt) = vote::fetch_receipt(program, poll_id, &signer).await? {
+        if receipt.escrowed > 0 {
+            withdraws = true;
+            let governance = vote::governance_tokens(program, &poll)
+                .await?
+                .context("Receipt holds escrowed tokens but the poll has no governance mint")?;
+            instructions.push(vote::withdraw_escrow_instruction(
+                &program.id(),
+                &signer,
+                poll_id,
+                &governance,
+            ));
+        }
+        instructions.push(vote::close_receipt_instruction(
+            &program.id(),
+            &signer,
+            poll_id,
+        ));
+    }
+    if vote::fetch_ballot(program, poll_id, &signer)
+        .await?
+        .is_some()
+    {
+        instructions.push(vote::close_ballot_instruction(
+            &program.id(),
+            &signer,
+            poll_id,
+        ));
+    }
+    if poll.authority == signer {
+        for candidate in tally::fetch_candidates(program, poll_id).await? {
+            instructions.push(vote::close_candidate_instruction(
+                &program.id(),
+                &signer,
+                poll_id,
+                &candidate.candidate_name,
+            ));
+        }
+    }
+    if instructions.is_empty() {
+        println!("Nothing to close in poll {poll_id}");
+        return Ok(());
+    }
+
+    let closed = instructions.len() - usize::from(withdraws);
+    for chunk in instructions.chunks(CLOSES_PER_TRANSACTION) {
+        let mut request = program.request();
+        for ix in chunk {
+            request = request.instruction(ix.clone());
+        }
+        let tx = request.send().await?;
+        println!("Accounts closed! Transaction signature: {tx}");
+    }
+    println!("Closed {closed} account(s) in poll {poll_id}");
+    Ok(())
+}
+
+// Non-voting members whose votes the signer's vote in `poll_id` carries,
+// each after its delegate
+async fn vote_delegators(program: &Program<Rc<VoterSigner>>, poll_id: u64) -> Result<Vec<Pubkey>> {
+    let delegations = delegation::fetch_delegations(program).await?;
+    let graph = DelegationGraph::new(&delegations, Some(poll_id));
+    let voter = program.payer();
+    let voted = voted_members(program, poll_id, &graph, &voter).await?;
+    Ok(graph.delegators_of(&voter, &voted))
+}
+
+// Members delegating (directly or not) to `voter` who have a receipt in the poll
+async fn voted_members(
+    program: &Program<Rc<VoterSigner>>,
+    poll_id: u64,
+    graph: &DelegationGraph,
+    voter: &Pubkey,
+) -> Result<HashSet<Pubkey>> {
+    let mut voted = HashSet::new();
+    for member in graph.delegators_of(voter, &HashSet::new()) {
+        if vote::fetch_receipt(program, poll_id, &member)
+            .await?
+            .is_some()
+        {
+            voted.insert(member);
+        }
+    }
+    Ok(voted)
+}
+
+async fn run_delegate(program: &Program<Rc<VoterSigner>>, command: DelegateCommand) -> Result<()> {
+    let delegator = program.payer();
+    match command {
+        DelegateCommand::Set { to, poll_id } => {
+            let delegations = delegation::fetch_delegations(program).await?;
+            let graph = DelegationGraph::new(&delegations, poll_id);
+            let chain = graph.chain_accounts(&program.id(), &delegator, &to)?;
+
+            let mut request = program.request();
+            // Replace an existing delegation in the same scope
+            if delegations
+                .iter()
+                .any(|d| d.delegator == delegator && d.poll_id == poll_id)
+            {
+                request = request.instruction(delegation::clear_delegation_instruction(
+                    &program.id(),
+                    &delegator,
+                    poll_id,
+                ));
+            }
+            let tx = request
+                .instruction(delegation::set_delegation_instruction(
+                    &program.id(),
+                    &delegator,
+                    poll_id,
+                    &to,
+                    &chain,
+                ))
+                .send()
+                .await?;
+            match poll_id {
+                Some(poll_id) => println!("Delegated your vote in poll {poll_id} to {to}"),
+                None => println!("Delegated your vote in every poll to {to}"),
+            }
+            println!("Transaction signature: {tx}");
+        }
+        DelegateCommand::Clear { poll_id } => {
+            let ix = delegation::clear_delegation_instruction(&program.id(), &delegator, poll_id);
+            let tx = program.request().instruction(ix).send().await?;
+            println!("Delegation removed! Transaction signature: {tx}");
+        }
+        DelegateCommand::Show { poll_id } => {
+            let delegations = delegation::fetch_delegations(program).await?;
+            let graph = DelegationGraph::new(&delegations, Some(poll_id));
+            if graph.edges.is_empty() {
+                println!("No delegations in poll {poll_id}");
+                return Ok(());
+            }
+
+            let mut voted = HashSet::new();
+            for member in graph.edges.keys().chain(graph.edges.values()) {
+                if vote::fetch_receipt(program, poll_id, member)
+                    .await?
+                    .is_some()
+                {
+                    voted.insert(*member);
+                }
+            }
+            let mut edges: Vec<(&Pubkey, &Pubkey)> = graph.edges.iter().collect();
+            edges.sort();
+            println!("Delegations in poll {poll_id}:");
+            for (delegator, delegate) in edges {
+                let scope = match delegations
+                    .iter()
+                    .any(|d| &d.delegator == delegator && d.poll_id == Some(poll_id))
+                {
+                    true => "this poll",
+                    false => "global",
+                };
+                let status = if voted.contains(delegator) {
+                    ", voted"
+                } else {
+                    ""
+                };
+                println!("  {delegator} -> {delegate} ({scope}{status})");
+            }
+            for cycle in graph.cycles() {
+                let members: Vec<String> = cycle.iter().map(Pubkey::to_string).collect();
+                eprintln!("Cycle: {} -> {}", members.join(" -> "), members[0]);
+            }
+
+            // Members who vote themselves, and the delegators their vote carries
+            let mut delegates: Vec<&Pubkey> = graph
+                .edges
+                .values()
+                .filter(|m| !graph.edges.contains_key(*m) || voted.contains(*m))
+                .collect();
+            delegates.sort();
+            delegates.dedup();
+            println!("Votes carried:");
+            for delegate in delegates {
+                let carried = graph.delegators_of(delegate, &voted).len();
+                println!("  {delegate}: 1 + {carried} delegated");
+            }
+        }
+    }
     Ok(())
 }
+
+// The cluster's current Unix timestamp, as programs see it
+async fn cluster_time(program: &Program<Rc<VoterSigner>>) -> Result<u64> {
+    let account = program.rpc().get_account(&sysvar::clock::ID).await?;
+    let clock: Clock = bincode::deserialize(&account.data)?;
+    Ok(u64::try_from(clock.unix_timestamp).unwrap_or(0))
+}
+
+// Voting program client signing with `keypair` (or the default keypair file)
+fn program_for(
+    cluster: Cluster,
+    program_id: Pubkey,
+    keypair: Option<SignerSource>,
+) -> Result<Program<Rc<VoterSigner>>> {
+    let source = keypair.unwrap_or_else(|| SignerSource::File(default_keypair_path()));
+    let client = Client::new_with_options(
+        cluster,
+        Rc::new(load_signer(&source)?),
+        CommitmentConfig::confirmed(),
+    );
+    Ok(client.program(program_id)?)
+}
+
+fn default_keypair_path() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    PathBuf::from(home).join(".config/solana/id.json")
+}
@@ -0,0 +1,556 @@
+// Vote from a Squads v4 style multisig vault.
+//
+// The vote instruction is wrapped into a vault transaction whose voter is the
+// multisig vault PDA. Members then create a proposal for it, approve it, and
+// once the threshold is met anyone in the multisig can execute it; the
+// multisig program signs for the vault when it invokes the voting program.
+use anyhow::{bail, Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    pubkey::Pubkey,
+    system_program,
+};
+
+// Program ID of the Squads v4 multisig program
+pub const SQUADS_PROGRAM_ID: &str = "SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf";
+
+const SEED_PREFIX: &[u8] = b"multisig";
+
+// Derive the multisig account created with `create_key`
+pub fn multisig_address(program_id: &Pubkey, create_key: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_PREFIX, b"multisig", create_key.as_ref()], program_id).0
+}
+
+// Derive the program-wide config account, which names the creation fee treasury
+pub fn program_config_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_PREFIX, b"program_config"], program_id).0
+}
+
+// Derive the vault that holds the multisig's funds and signs its transactions
+pub fn vault_address(program_id: &Pubkey, multisig: &Pubkey, vault_index: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[SEED_PREFIX, multisig.as_ref(), b"vault", &[vault_index]],
+        program_id,
+    )
+    .0
+}
+
+// Derive the vault transaction account for `transaction_index`
+pub fn transaction_address(
+    program_id: &Pubkey,
+    multisig: &Pubkey,
+    transaction_index: u64,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            b"transaction",
+            &transaction_index.to_le_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+// Derive the proposal account for `transaction_index`
+pub fn proposal_address(program_id: &Pubkey, multisig: &Pubkey, transaction_index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            b"transaction",
+            &transaction_index.to_le_bytes(),
+            b"proposal",
+        ],
+        program_id,
+    )
+    .0
+}
+
+fn discriminator(namespace: &str, name: &str) -> [u8; 8] {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{namespace}:{name}").as_bytes());
+    hasher.finalize()[..8].try_into().unwrap()
+}
+
+fn instruction_data(name: &str, args: &impl BorshSerialize) -> Vec<u8> {
+    let mut data = discriminator("global", name).to_vec();
+    args.serialize(&mut data)
+        .expect("writing to a Vec cannot fail");
+    data
+}
+
+// Member permissions, as a bit mask
+pub mod permissions {
+    pub const INITIATE: u8 = 1 << 0;
+    pub const VOTE: u8 = 1 << 1;
+    pub const EXECUTE: u8 = 1 << 2;
+    pub const ALL: u8 = INITIATE | VOTE | EXECUTE;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct Member {
+    pub key: Pubkey,
+    pub permissions: u8,
+}
+
+#[derive(BorshSerialize)]
+struct MultisigCreateArgsV2 {
+    config_authority: Option<Pubkey>,
+    threshold: u16,
+    members: Vec<Member>,
+    time_lock: u32,
+    rent_collector: Option<Pubkey>,
+    memo: Option<String>,
+}
+
+#[derive(BorshSerialize)]
+struct VaultTransactionCreateArgs {
+    vault_index: u8,
+    ephemeral_signers: u8,
+    transaction_message: Vec<u8>,
+    memo: Option<String>,
+}
+
+#[derive(BorshSerialize)]
+struct ProposalCreateArgs {
+    transaction_index: u64,
+    draft: bool,
+}
+
+#[derive(BorshSerialize)]
+struct ProposalVoteArgs {
+    memo: Option<String>,
+}
+
+// Leading fields of the multisig account; members follow and are not needed here
+#[derive(Debug, BorshDeserialize)]
+pub struct MultisigState {
+    pub create_key: Pubkey,
+    pub config_authority: Pubkey,
+    pub threshold: u16,
+    pub time_lock: u32,
+    pub transaction_index: u64,
+    pub stale_transaction_index: u64,
+}
+
+impl MultisigState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, "Multisig")?;
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+}
+
+// Leading fields of the program config account
+#[derive(Debug, BorshDeserialize)]
+pub struct ProgramConfigState {
+    pub authority: Pubkey,
+    pub multisig_creation_fee: u64,
+    pub treasury: Pubkey,
+}
+
+impl ProgramConfigState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, "ProgramConfig")?;
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct CompiledInstruction {
+    pub program_id_index: u8,
+    pub account_indexes: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct AddressTableLookup {
+    pub account_key: Pubkey,
+    pub writable_indexes: Vec<u8>,
+    pub readonly_indexes: Vec<u8>,
+}
+
+// The message of a vault transaction as stored on chain
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct VaultTransactionMessage {
+    pub num_signers: u8,
+    pub num_writable_signers: u8,
+    pub num_writable_non_signers: u8,
+    pub account_keys: Vec<Pubkey>,
+    pub instructions: Vec<CompiledInstruction>,
+    pub address_table_lookups: Vec<AddressTableLookup>,
+}
+
+impl VaultTransactionMessage {
+    // Compile `instructions` with the vault as the paying signer
+    pub fn compile(vault: &Pubkey, instructions: &[Instruction]) -> Result<Self> {
+        let message = Message::new(instructions, Some(vault));
+        let header = message.header;
+        let num_keys = message.account_keys.len();
+        let num_signers = header.num_required_signatures;
+
+        Ok(Self {
+            num_signers,
+            num_writable_signers: num_signers - header.num_readonly_signed_accounts,
+            num_writable_non_signers: u8::try_from(
+                num_keys
+                    - usize::from(num_signers)
+                    - usize::from(header.num_readonly_unsigned_accounts),
+            )
+            .context("too many accounts for a vault transaction")?,
+            account_keys: message.account_keys,
+            instructions: message
+                .instructions
+                .into_iter()
+                .map(|ix| CompiledInstruction {
+                    program_id_index: ix.program_id_index,
+                    account_indexes: ix.accounts,
+                    data: ix.data,
+                })
+                .collect(),
+            address_table_lookups: Vec::new(),
+        })
+    }
+
+    // Serialize in the compact form `vault_transaction_create` takes, where
+    // vector lengths are a single byte (a `u16` for instruction data)
+    pub fn to_compact_bytes(&self) -> Result<Vec<u8>> {
+        fn len_u8(len: usize) -> Result<u8> {
+            u8::try_from(len).context("vault transaction message is too large")
+        }
+
+        let mut out = vec![
+            self.num_signers,
+            self.num_writable_signers,
+            self.num_writable_non_signers,
+            len_u8(self.account_keys.len())?,
+        ];
+        for key in &self.account_keys {
+            out.extend_from_slice(key.as_ref());
+        }
+        out.push(len_u8(self.instructions.len())?);
+        for ix in &self.instructions {
+            out.push(ix.program_id_index);
+            out.push(len_u8(ix.account_indexes.len())?);
+            out.extend_from_slice(&ix.account_indexes);
+            let data_len = u16::try_from(ix.data.len()).context("instruction data is too large")?;
+            out.extend_from_slice(&data_len.to_le_bytes());
+            out.extend_from_slice(&ix.data);
+        }
+        out.push(len_u8(self.address_table_lookups.len())?);
+        for lookup in &self.address_table_lookups {
+            out.extend_from_slice(lookup.account_key.as_ref());
+            out.push(len_u8(lookup.writable_indexes.len())?);
+            out.extend_from_slice(&lookup.writable_indexes);
+            out.push(len_u8(lookup.readonly_indexes.len())?);
+            out.extend_from_slice(&lookup.readonly_indexes);
+        }
+        Ok(out)
+    }
+
+    pub fn is_writable_index(&self, index: usize) -> bool {
+        let num_signers = usize::from(self.num_signers);
+        if index < num_signers {
+            index < usize::from(self.num_writable_signers)
+        } else {
+            index - num_signers < usize::from(self.num_writable_non_signers)
+        }
+    }
+}
+
+// Leading fields of a vault transaction account
+#[derive(Debug, BorshDeserialize)]
+pub struct VaultTransactionState {
+    pub multisig: Pubkey,
+    pub creator: Pubkey,
+    pub index: u64,
+    pub bump: u8,
+    pub vault_index: u8,
+    pub vault_bump: u8,
+    pub ephemeral_signer_bumps: Vec<u8>,
+    pub message: VaultTransactionMessage,
+}
+
+impl VaultTransactionState {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        check_discriminator(data, "VaultTransaction")?;
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+}
+
+fn check_discriminator(data: &[u8], account: &str) -> Result<()> {
+    if data.len() < 8 || data[..8] != discriminator("account", account) {
+        bail!("account is not a multisig {account}");
+    }
+    Ok(())
+}
+
+// Instruction builders for one multisig
+#[derive(Debug, Clone, Copy)]
+pub struct Multisig {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+}
+
+impl Multisig {
+    pub fn new(program_id: Pubkey, address: Pubkey) -> Self {
+        Self {
+            program_id,
+            address,
+        }
+    }
+
+    pub fn vault(&self, vault_index: u8) -> Pubkey {
+        vault_address(&self.program_id, &self.address, vault_index)
+    }
+
+    // Create a new multisig; `create_key` must sign and seeds its address
+    pub fn create(
+        program_id: Pubkey,
+        create_key: &Pubkey,
+        creator: &Pubkey,
+        treasury: &Pubkey,
+        members: Vec<Member>,
+        threshold: u16,
+    ) -> (Self, Instruction) {
+        let multisig = Self::new(program_id, multisig_address(&program_id, create_key));
+        let ix = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(program_config_address(&program_id), false),
+                AccountMeta::new(*treasury, false),
+                AccountMeta::new(multisig.address, false),
+                AccountMeta::new_readonly(*create_key, true),
+                AccountMeta::new(*creator, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction_data(
+                "multisig_create_v2",
+                &MultisigCreateArgsV2 {
+                    config_authority: None,
+                    threshold,
+                    members,
+                    time_lock: 0,
+                    rent_collector: None,
+                    memo: None,
+                },
+            ),
+        };
+        (multisig, ix)
+    }
+
+    // Store `message` as vault transaction `transaction_index`
+    pub fn create_transaction(
+        &self,
+        creator: &Pubkey,
+        transaction_index: u64,
+        vault_index: u8,
+        message: &VaultTransactionMessage,
+    ) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.address, false),
+                AccountMeta::new(
+                    transaction_address(&self.program_id, &self.address, transaction_index),
+                    false,
+                ),
+                AccountMeta::new_readonly(*creator, true),
+                AccountMeta::new(*creator, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction_data(
+                "vault_transaction_create",
+                &VaultTransactionCreateArgs {
+                    vault_index,
+                    ephemeral_signers: 0,
+                    transaction_message: message.to_compact_bytes()?,
+                    memo: None,
+                },
+            ),
+        })
+    }
+
+    // Open a proposal for vault transaction `transaction_index`
+    pub fn create_proposal(&self, creator: &Pubkey, transaction_index: u64) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new(
+                    proposal_address(&self.program_id, &self.address, transaction_index),
+                    false,
+                ),
+                AccountMeta::new_readonly(*creator, true),
+                AccountMeta::new(*creator, true),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction_data(
+                "proposal_create",
+                &ProposalCreateArgs {
+                    transaction_index,
+                    draft: false,
+                },
+            ),
+        }
+    }
+
+    pub fn approve(&self, member: &Pubkey, transaction_index: u64) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(self.address, false),
+                AccountMeta::new(*member, true),
+                AccountMeta::new(
+                    proposal_address(&self.program_id, &self.address, transaction_index),
+                    false,
+                ),
+            ],
+            data: instruction_data("proposal_approve", &ProposalVoteArgs { memo: None }),
+        }
+    }
+
+    // Execute an approved vault transaction. Every account its message
+    // references is passed as a remaining account; the vault signs inside the
+    // multisig program, so none of them are marked as signers here.
+    pub fn execute(
+        &self,
+        member: &Pubkey,
+        transaction_index: u64,
+        message: &VaultTransactionMessage,
+    ) -> Result<Instruction> {
+        if !message.address_table_lookups.is_empty() {
+            bail!("vault transactions using address lookup tables are not supported");
+        }
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.address, false),
+            AccountMeta::new(
+                proposal_address(&self.program_id, &self.address, transaction_index),
+                false,
+            ),
+            AccountMeta::new_readonly(
+                transaction_address(&self.program_id, &self.address, transaction_index),
+                false,
+            ),
+            AccountMeta::new_readonly(*member, true),
+        ];
+        accounts.extend(message.account_keys.iter().enumerate().map(|(i, key)| {
+            if message.is_writable_index(i) {
+                AccountMeta::new(*key, false)
+            } else {
+                AccountMeta::new_readonly(*key, false)
+            }
+        }));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: discriminator("global", "vault_transaction_execute").to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vote::vote_instruction;
+
+    #[test]
+    fn compiles_vote_with_vault_as_signer() {
+        let squads = Pubkey::new_unique();
+        let voting = Pubkey::new_unique();
+        let multisig = Multisig::new(squads, Pubkey::new_unique());
+        let vault = multisig.vault(0);
+
+        let vote = vote_instruction(&voting, &vault, 1, "Smooth");
+        let message =
+            VaultTransactionMessage::compile(&vault, std::slice::from_ref(&vote)).unwrap();
+
+        assert_eq!(message.account_keys[0], vault);
+        assert_eq!(message.num_signers, 1);
+        assert_eq!(message.num_writable_signers, 1);
+        // Poll, candidate and receipt are writable; the system and voting programs are not
+        assert_eq!(message.num_writable_non_signers, 3);
+        assert_eq!(message.instructions.len(), 1);
+        assert_eq!(message.instructions[0].data, vote.data);
+
+        assert!(message.is_writable_index(0));
+        assert!(message.is_writable_index(3));
+        assert!(!message.is_writable_index(4));
+        assert!(!message.is_writable_index(5));
+    }
+
+    #[test]
+    fn encodes_compact_message() {
+        let vault = Pubkey::new_unique();
+        let vote = vote_instruction(&Pubkey::new_unique(), &vault, 7, "Crunchy");
+        let message =
+            VaultTransactionMessage::compile(&vault, std::slice::from_ref(&vote)).unwrap();
+        let bytes = message.to_compact_bytes().unwrap();
+
+        let keys_len = 6 * 32;
+        assert_eq!(&bytes[..4], &[1, 1, 3, 6]);
+        assert_eq!(&bytes[4..36], vault.as_ref());
+        // Instruction count, then program index, 5 account indexes, u16 data length, data
+        let ix = &bytes[4 + keys_len..];
+        assert_eq!(ix[0], 1);
+        assert_eq!(ix[1], message.instructions[0].program_id_index);
+        assert_eq!(ix[2], 5);
+        let data_len = u16::from_le_bytes([ix[8], ix[9]]) as usize;
+        assert_eq!(data_len, vote.data.len());
+        assert_eq!(&ix[10..10 + data_len], &vote.data[..]);
+        // No address table lookups
+        assert_eq!(bytes.last(), Some(&0));
+        assert_eq!(bytes.len(), 4 + keys_len + 1 + 1 + 1 + 5 + 2 + data_len + 1);
+    }
+
+    #[test]
+    fn reads_vault_transaction_accounts() {
+        let vault = Pubkey::new_unique();
+        let vote = vote_instruction(&Pubkey::new_unique(), &vault, 1, "Smooth");
+        let message = VaultTransactionMessage::compile(&vault, &[vote]).unwrap();
+
+        let mut data = discriminator("account", "VaultTransaction").to_vec();
+        (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            3u64,
+            255u8,
+            0u8,
+            254u8,
+        )
+            .serialize(&mut data)
+            .unwrap();
+        Vec::<u8>::new().serialize(&mut data).unwrap();
+        message.serialize(&mut data).unwrap();
+
+        let state = VaultTransactionState::from_account_data(&data).unwrap();
+        assert_eq!(state.index, 3);
+        assert_eq!(state.message, message);
+
+        assert!(MultisigState::from_account_data(&data).is_err());
+    }
+
+    #[test]
+    fn execute_passes_message_accounts() {
+        let multisig = Multisig::new(Pubkey::new_unique(), Pubkey::new_unique());
+        let vault = multisig.vault(0);
+        let vote = vote_instruction(&Pubkey::new_unique(), &vault, 1, "Smooth");
+        let message = VaultTransactionMessage::compile(&vault, &[vote]).unwrap();
+        let member = Pubkey::new_unique();
+
+        let ix = multisig.execute(&member, 1, &message).unwrap();
+        let remaining = &ix.accounts[4..];
+        assert_eq!(remaining.len(), message.account_keys.len());
+        assert!(remaining.iter().all(|meta| !meta.is_signer));
+        assert_eq!(remaining[0].pubkey, vault);
+        assert!(remaining[0].is_writable);
+        assert!(remaining[3].is_writable);
+        assert!(!remaining[4].is_writable);
+    }
+}
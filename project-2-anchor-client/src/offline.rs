@@ -0,0 +1,257 @@
+use crate::vote::vote_instruction;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    nonce_utils::nonblocking::{data_from_account, get_account_with_commitment},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, hash::Hash, instruction::Instruction, message::Message,
+    pubkey::Pubkey, signature::Signature, signer::Signer, system_instruction,
+    transaction::Transaction,
+};
+use std::str::FromStr;
+
+// Text encoding of a serialized transaction file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TxEncoding {
+    #[default]
+    Base64,
+    Base58,
+}
+
+impl FromStr for TxEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "base64" => Ok(Self::Base64),
+            "base58" => Ok(Self::Base58),
+            _ => bail!("expected `base64` or `base58`"),
+        }
+    }
+}
+
+// Serialize a (possibly partially signed) transaction for a file
+pub fn encode_transaction(tx: &Transaction, encoding: TxEncoding) -> Result<String> {
+    let bytes = bincode::serialize(tx)?;
+    Ok(match encoding {
+        TxEncoding::Base64 => BASE64.encode(bytes),
+        TxEncoding::Base58 => bs58::encode(bytes).into_string(),
+    })
+}
+
+// Inverse of `encode_transaction`; surrounding whitespace is ignored
+pub fn decode_transaction(encoded: &str, encoding: TxEncoding) -> Result<Transaction> {
+    let encoded = encoded.trim();
+    let bytes = match encoding {
+        TxEncoding::Base64 => BASE64.decode(encoded)?,
+        TxEncoding::Base58 => bs58::decode(encoded).into_vec()?,
+    };
+    let tx: Transaction =
+        bincode::deserialize(&bytes).context("file does not hold a serialized transaction")?;
+    if tx.signatures.len() != usize::from(tx.message.header.num_required_signatures) {
+        bail!("transaction signature count does not match its message header");
+    }
+    Ok(tx)
+}
+
+// SHA-256 of the serialized message, base58 encoded.
+// Every phase prints it so the operator can check the message they are about
+// to sign or submit is the one that was built.
+pub fn message_hash(tx: &Transaction) -> String {
+    let digest = Sha256::digest(tx.message_data());
+    bs58::encode(digest).into_string()
+}
+
+// Fail unless the transaction's message hash is `expected`
+pub fn check_message_hash(tx: &Transaction, expected: &str) -> Result<()> {
+    let actual = message_hash(tx);
+    if expected != actual {
+        bail!("message hash mismatch: expected {expected}, transaction has {actual}");
+    }
+    Ok(())
+}
+
+// The durable nonce currently stored in `nonce_account`, checked against the expected authority
+pub async fn fetch_nonce(
+    rpc: &RpcClient,
+    nonce_account: &Pubkey,
+    nonce_authority: &Pubkey,
+) -> Result<Hash> {
+    let account =
+        get_account_with_commitment(rpc, nonce_account, CommitmentConfig::confirmed()).await?;
+    let data = data_from_account(&account)?;
+    if data.authority != *nonce_authority {
+        bail!(
+            "nonce account {nonce_account} is controlled by {}, not {nonce_authority}",
+            data.authority
+        );
+    }
+    Ok(data.blockhash())
+}
+
+// Accounts and settings of a vote transaction built for offline signing
+#[derive(Debug, Clone)]
+pub struct OfflineVote {
+    pub program_id: Pubkey,
+    pub voter: Pubkey,
+    pub fee_payer: Pubkey,
+    pub nonce_account: Pubkey,
+    pub nonce_authority: Pubkey,
+    pub poll_id: u64,
+    pub candidate: String,
+}
+
+impl OfflineVote {
+    pub fn vote_instruction(&self) -> Instruction {
+        vote_instruction(&self.program_id, &self.voter, self.poll_id, &self.candidate)
+    }
+
+    // Build the unsigned vote transaction against the durable nonce `nonce`.
+    // `AdvanceNonceAccount` comes first, as the runtime requires; any compute
+    // budget instructions follow it.
+    pub fn build(&self, nonce: Hash, compute_budget: &[Instruction]) -> Transaction {
+        let mut instructions = vec![system_instruction::advance_nonce_account(
+            &self.nonce_account,
+            &self.nonce_authority,
+        )];
+        instructions.extend_from_slice(compute_budget);
+        instructions.push(self.vote_instruction());
+
+        let message = Message::new_with_blockhash(&instructions, Some(&self.fee_payer), &nonce);
+        Transaction::new_unsigned(message)
+    }
+}
+
+// Add `signer`'s signature to `tx` without touching the others.
+// Signatures already present must still verify, so a message altered after an
+// earlier signer approved it is rejected rather than signed.
+pub fn sign_offline(tx: &mut Transaction, signer: &dyn Signer) -> Result<Signature> {
+    let pubkey = signer.try_pubkey()?;
+    let position = tx
+        .get_signing_keypair_positions(&[pubkey])?
+        .first()
+        .copied()
+        .flatten()
+        .ok_or_else(|| anyhow!("{pubkey} is not a required signer of this transaction"))?;
+
+    let message = tx.message_data();
+    for (signature, key) in tx.signatures.iter().zip(&tx.message.account_keys) {
+        if *signature != Signature::default() && !signature.verify(key.as_ref(), &message) {
+            bail!("existing signature from {key} does not match the message");
+        }
+    }
+
+    let signature = signer.try_sign_message(&message)?;
+    tx.signatures[position] = signature;
+    Ok(signature)
+}
+
+// Signers that have not signed `tx` yet
+pub fn missing_signers(tx: &Transaction) -> Vec<Pubkey> {
+    tx.signatures
+        .iter()
+        .zip(&tx.message.account_keys)
+        .filter(|(signature, _)| **signature == Signature::default())
+        .map(|(_, key)| *key)
+        .collect()
+}
+
+// Fail unless every required signature is present and valid
+pub fn check_fully_signed(tx: &Transaction) -> Result<()> {
+    let missing = missing_signers(tx);
+    if !missing.is_empty() {
+        let missing: Vec<String> = missing.iter().map(Pubkey::to_string).collect();
+        bail!(
+            "transaction is missing signatures from {}",
+            missing.join(", ")
+        );
+    }
+    tx.verify()
+        .context("transaction has an invalid signature")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    fn offline_vote(voter: &Keypair, fee_payer: &Keypair) -> OfflineVote {
+        OfflineVote {
+            program_id: Pubkey::new_unique(),
+            voter: voter.pubkey(),
+            fee_payer: fee_payer.pubkey(),
+            nonce_account: Pubkey::new_unique(),
+            nonce_authority: fee_payer.pubkey(),
+            poll_id: 1,
+            candidate: "Smooth".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trips_both_encodings() {
+        let voter = Keypair::new();
+        let tx = offline_vote(&voter, &voter).build(Hash::new_unique(), &[]);
+
+        for encoding in [TxEncoding::Base64, TxEncoding::Base58] {
+            let encoded = encode_transaction(&tx, encoding).unwrap();
+            let decoded = decode_transaction(&format!("{encoded}\n"), encoding).unwrap();
+            assert_eq!(decoded, tx);
+            assert_eq!(message_hash(&decoded), message_hash(&tx));
+        }
+        assert!(decode_transaction("not a transaction", TxEncoding::Base64).is_err());
+    }
+
+    #[test]
+    fn builds_with_advance_nonce_first() {
+        let voter = Keypair::new();
+        let nonce = Hash::new_unique();
+        let vote = offline_vote(&voter, &voter);
+        let tx = vote.build(nonce, &[]);
+
+        assert_eq!(tx.message.recent_blockhash, nonce);
+        let first = &tx.message.instructions[0];
+        assert_eq!(
+            tx.message.account_keys[usize::from(first.program_id_index)],
+            solana_sdk::system_program::id()
+        );
+        assert_eq!(missing_signers(&tx), vec![voter.pubkey()]);
+    }
+
+    #[test]
+    fn signs_in_phases() {
+        let voter = Keypair::new();
+        let fee_payer = Keypair::new();
+        let mut tx = offline_vote(&voter, &fee_payer).build(Hash::new_unique(), &[]);
+        let hash = message_hash(&tx);
+
+        sign_offline(&mut tx, &voter).unwrap();
+        assert_eq!(missing_signers(&tx), vec![fee_payer.pubkey()]);
+        assert!(check_fully_signed(&tx).is_err());
+
+        check_message_hash(&tx, &hash).unwrap();
+        sign_offline(&mut tx, &fee_payer).unwrap();
+        check_fully_signed(&tx).unwrap();
+
+        assert!(sign_offline(&mut tx, &Keypair::new()).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_messages() {
+        let voter = Keypair::new();
+        let fee_payer = Keypair::new();
+        let mut tx = offline_vote(&voter, &fee_payer).build(Hash::new_unique(), &[]);
+        let hash = message_hash(&tx);
+        sign_offline(&mut tx, &voter).unwrap();
+
+        // Swap the candidate after the voter signed
+        let data = &mut tx.message.instructions.last_mut().unwrap().data;
+        data[8] ^= 1;
+
+        assert!(check_message_hash(&tx, &hash).is_err());
+        assert!(sign_offline(&mut tx, &fee_payer).is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::{anyhow, Context, Result};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+// A sealed ballot committed on-chain whose salt is kept until it is revealed.
+// Losing the salt means the ballot can never be revealed, so it is saved
+// before the commitment is sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedBallot {
+    pub program_id: String,
+    pub poll_id: u64,
+    pub voter: String,
+    pub candidate: String,
+    // base58
+    pub salt: String,
+}
+
+impl SealedBallot {
+    pub fn new(program_id: &Pubkey, poll_id: u64, voter: &Pubkey, candidate: &str) -> Self {
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self {
+            program_id: program_id.to_string(),
+            poll_id,
+            voter: voter.to_string(),
+            candidate: candidate.to_string(),
+            salt: bs58::encode(salt).into_string(),
+        }
+    }
+
+    pub fn salt(&self) -> Result<[u8; 32]> {
+        bs58::decode(&self.salt)
+            .into_vec()?
+            .try_into()
+            .map_err(|_| anyhow!("salt must be 32 bytes"))
+    }
+
+    // The commitment sent to `commit_vote`, matching the program's `ballot_commitment`
+    pub fn commitment(&self) -> Result<[u8; 32]> {
+        let voter: Pubkey = self.voter.parse()?;
+        Ok(voting::ballot_commitment(
+            self.poll_id,
+            &voter,
+            &self.candidate,
+            &self.salt()?,
+        ))
+    }
+
+    fn is_for(&self, program_id: &Pubkey, voter: &Pubkey) -> bool {
+        self.program_id == program_id.to_string() && self.voter == voter.to_string()
+    }
+}
+
+// Sealed ballots awaiting their reveal, kept in a JSON file
+#[derive(Debug, Default)]
+pub struct BallotStore {
+    path: PathBuf,
+    ballots: Vec<SealedBallot>,
+}
+
+impl BallotStore {
+    // Open the store at `path`; a missing file is an empty store
+    pub fn open(path: &Path) -> Result<Self> {
+        let ballots = match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Error parsing ballot store {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Error reading ballot store {}", path.display()))
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            ballots,
+        })
+    }
+
+    // `$HOME/.config/voting-client/ballots.json`
+    pub fn default_path() -> PathBuf {
+        let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+        PathBuf::from(home).join(".config/voting-client/ballots.json")
+    }
+
+    // Add `ballot`, replacing any earlier one for the same voter and poll, and save
+    pub fn insert(&mut self, ballot: SealedBallot) -> Result<()> {
+        self.ballots.retain(|b| {
+            !(b.program_id == ballot.program_id
+                && b.voter == ballot.voter
+                && b.poll_id == ballot.poll_id)
+        });
+        self.ballots.push(ballot);
+        self.save()
+    }
+
+    // Drop the ballot once it has been revealed, and save
+    pub fn remove(&mut self, ballot: &SealedBallot) -> Result<()> {
+        self.ballots.retain(|b| b != ballot);
+        self.save()
+    }
+
+    // Ballots of `voter` in polls of `program_id`
+    pub fn pending(&self, program_id: &Pubkey, voter: &Pubkey) -> Vec<SealedBallot> {
+        self.ballots
+            .iter()
+            .filter(|b| b.is_for(program_id, voter))
+            .cloned()
+            .collect()
+    }
+
+    // The salts are secrets: the store is only readable by its owner, and is
+    // replaced in one step so a failed write can't lose the earlier ballots
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string_pretty(&self.ballots)?;
+        let partial = self.path.with_extension("json.partial");
+        write_private(&partial, contents.as_bytes())
+            .and_then(|()| fs::rename(&partial, &self.path))
+            .with_context(|| format!("Error writing ballot store {}", self.path.display()))
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // A leftover from an interrupted save may have other permissions
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_ballots_until_removed() {
+        let path = std::env::temp_dir().join(format!("ballots-{}.json", Pubkey::new_unique()));
+        let program_id = Pubkey::new_unique();
+        let voter = Pubkey::new_unique();
+
+        let mut store = BallotStore::open(&path).unwrap();
+        let first = SealedBallot::new(&program_id, 1, &voter, "Smooth");
+        store.insert(first.clone()).unwrap();
+        // A new commitment in the same poll replaces the old salt
+        let second = SealedBallot::new(&program_id, 1, &voter, "Crunchy");
+        store.insert(second.clone()).unwrap();
+        store
+            .insert(SealedBallot::new(
+                &program_id,
+                2,
+                &Pubkey::new_unique(),
+                "Smooth",
+            ))
+            .unwrap();
+
+        let reopened = BallotStore::open(&path).unwrap();
+        assert_eq!(reopened.pending(&program_id, &voter), vec![second.clone()]);
+
+        let mut store = reopened;
+        store.remove(&second).unwrap();
+        assert!(BallotStore::open(&path)
+            .unwrap()
+            .pending(&program_id, &voter)
+            .is_empty());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_store_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("ballots-{}.json", Pubkey::new_unique()));
+        // An existing store readable by others is replaced
+        fs::write(&path, "[]").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut store = BallotStore::open(&path).unwrap();
+        let ballot = SealedBallot::new(&Pubkey::new_unique(), 1, &Pubkey::new_unique(), "Smooth");
+        store.insert(ballot).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!path.with_extension("json.partial").exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn commits_to_poll_voter_candidate_and_salt() {
+        let ballot = SealedBallot::new(&Pubkey::new_unique(), 1, &Pubkey::new_unique(), "Smooth");
+        let other = SealedBallot {
+            candidate: "Crunchy".to_string(),
+            ..ballot.clone()
+        };
+        let resalted = SealedBallot::new(&Pubkey::new_unique(), 1, &Pubkey::new_unique(), "Smooth");
+
+        assert_eq!(ballot.salt().unwrap().len(), 32);
+        assert_ne!(ballot.commitment().unwrap(), other.commitment().unwrap());
+        assert_ne!(ballot.commitment().unwrap(), resalted.commitment().unwrap());
+
+        // The same candidate and salt commit differently in another poll or
+        // for another voter
+        let other_poll = SealedBallot {
+            poll_id: 2,
+            ..ballot.clone()
+        };
+        let other_voter = SealedBallot {
+            voter: Pubkey::new_unique().to_string(),
+            ..ballot.clone()
+        };
+        assert_ne!(
+            ballot.commitment().unwrap(),
+            other_poll.commitment().unwrap()
+        );
+        assert_ne!(
+            ballot.commitment().unwrap(),
+            other_voter.commitment().unwrap()
+        );
+    }
+}
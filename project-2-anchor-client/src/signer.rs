@@ -0,0 +1,314 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use solana_remote_wallet::{
+    locator::Locator, remote_keypair::generate_remote_keypair, remote_wallet::maybe_wallet_manager,
+};
+use solana_sdk::{
+    derivation_path::DerivationPath,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signature},
+    signer::{keypair::keypair_from_seed, Signer, SignerError},
+};
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    str::FromStr,
+    sync::Mutex,
+};
+
+// A signer loaded from any `SignerSource`
+pub type VoterSigner = Box<dyn Signer>;
+
+// Where a voter's signing key lives
+//
+// - `usb://ledger?key=0/0` a hardware wallet (needs the `usb` feature)
+// - `env://NAME` a base58 secret key held in an environment variable
+// - `exec:COMMAND [ARGS...]` an external signer process, see `ExternalSigner`
+// - `file://PATH` or a bare path, a keypair file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerSource {
+    File(PathBuf),
+    Env(String),
+    External { program: String, args: Vec<String> },
+    Usb(String),
+}
+
+impl FromStr for SignerSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with("usb://") {
+            Ok(Self::Usb(s.to_string()))
+        } else if let Some(name) = s.strip_prefix("env://") {
+            if name.is_empty() {
+                bail!("`env://` needs an environment variable name");
+            }
+            Ok(Self::Env(name.to_string()))
+        } else if let Some(command) = s.strip_prefix("exec:") {
+            let mut words = command.split_whitespace().map(str::to_string);
+            let program = words
+                .next()
+                .ok_or_else(|| anyhow!("`exec:` needs a signer command"))?;
+            Ok(Self::External {
+                program,
+                args: words.collect(),
+            })
+        } else {
+            let path = s.strip_prefix("file://").unwrap_or(s);
+            Ok(Self::File(PathBuf::from(path)))
+        }
+    }
+}
+
+impl fmt::Display for SignerSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(name) => write!(f, "env://{name}"),
+            Self::External { program, args } => {
+                write!(f, "exec:{program}")?;
+                args.iter().try_for_each(|arg| write!(f, " {arg}"))
+            }
+            Self::Usb(uri) => write!(f, "{uri}"),
+        }
+    }
+}
+
+// Load the signer described by `source`
+pub fn load_signer(source: &SignerSource) -> Result<VoterSigner> {
+    match source {
+        SignerSource::File(path) => {
+            let keypair = read_keypair_file(path)
+                .map_err(|e| anyhow!("Error reading keypair file {}: {e}", path.display()))?;
+            Ok(Box::new(keypair))
+        }
+        SignerSource::Env(name) => {
+            let secret = std::env::var(name)
+                .with_context(|| format!("Error reading secret key from ${name}"))?;
+            Ok(Box::new(keypair_from_base58(secret.trim()).with_context(
+                || format!("${name} does not hold a base58 secret key"),
+            )?))
+        }
+        SignerSource::External { program, args } => {
+            Ok(Box::new(ExternalSigner::spawn(program, args)?))
+        }
+        SignerSource::Usb(uri) => {
+            let locator = Locator::new_from_path(uri)?;
+            let derivation_path = match uri.split_once("?key=") {
+                Some((_, key)) => DerivationPath::from_key_str(key)?,
+                None => DerivationPath::default(),
+            };
+            let wallet_manager = maybe_wallet_manager()?
+                .ok_or_else(|| anyhow!("No hardware wallet found for {uri}"))?;
+            let keypair =
+                generate_remote_keypair(locator, derivation_path, &wallet_manager, false, "voter")?;
+            Ok(Box::new(keypair))
+        }
+    }
+}
+
+// Decode a 64-byte base58 secret key, as printed by `solana-keygen`
+fn keypair_from_base58(secret: &str) -> Result<solana_sdk::signature::Keypair> {
+    let bytes = bs58::decode(secret).into_vec()?;
+    if bytes.len() != 64 {
+        bail!("expected 64 secret key bytes, got {}", bytes.len());
+    }
+    let keypair = keypair_from_seed(&bytes[..32]).map_err(|e| anyhow!("{e}"))?;
+    if keypair.pubkey().as_ref() != &bytes[32..] {
+        bail!("public key half does not match the secret key");
+    }
+    Ok(keypair)
+}
+
+// A request to an external signer, written as one line of JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SignerRequest {
+    GetPubkey,
+    // `message` is the base64-encoded serialized transaction message
+    SignMessage { message: String },
+}
+
+// An external signer's reply, written as one line of JSON
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SignerResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// A signer backed by a long-running child process.
+//
+// The process reads one `SignerRequest` per line on stdin and answers each
+// with one `SignerResponse` line on stdout:
+//
+//   -> {"method":"get_pubkey"}
+//   <- {"pubkey":"<base58>"}
+//   -> {"method":"sign_message","message":"<base64>"}
+//   <- {"signature":"<base58>"}
+//
+// Either reply may instead be `{"error":"<reason>"}`. Returned signatures are
+// verified against the public key before they are used.
+pub struct ExternalSigner {
+    pubkey: Pubkey,
+    process: Mutex<ExternalProcess>,
+}
+
+struct ExternalProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl ExternalSigner {
+    pub fn spawn(program: &str, args: &[String]) -> Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Error starting external signer `{program}`"))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut process = ExternalProcess {
+            child,
+            stdin,
+            stdout,
+        };
+
+        let pubkey = process
+            .call(&SignerRequest::GetPubkey)?
+            .pubkey
+            .ok_or_else(|| anyhow!("external signer did not return a pubkey"))?
+            .parse()
+            .context("external signer returned an invalid pubkey")?;
+
+        Ok(Self {
+            pubkey,
+            process: Mutex::new(process),
+        })
+    }
+}
+
+impl ExternalProcess {
+    fn call(&mut self, request: &SignerRequest) -> Result<SignerResponse, SignerError> {
+        let mut line =
+            serde_json::to_string(request).map_err(|e| SignerError::Custom(e.to_string()))?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| SignerError::Connection(e.to_string()))?;
+
+        let mut reply = String::new();
+        let read = self
+            .stdout
+            .read_line(&mut reply)
+            .map_err(|e| SignerError::Connection(e.to_string()))?;
+        if read == 0 {
+            return Err(SignerError::Connection(
+                "external signer closed its output".to_string(),
+            ));
+        }
+
+        let response: SignerResponse =
+            serde_json::from_str(&reply).map_err(|e| SignerError::Protocol(e.to_string()))?;
+        match response.error {
+            Some(error) => Err(SignerError::Custom(error)),
+            None => Ok(response),
+        }
+    }
+}
+
+impl Signer for ExternalSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let mut process = self
+            .process
+            .lock()
+            .map_err(|_| SignerError::Custom("external signer lock poisoned".to_string()))?;
+        let response = process.call(&SignerRequest::SignMessage {
+            message: BASE64.encode(message),
+        })?;
+
+        let signature: Signature = response
+            .signature
+            .ok_or_else(|| SignerError::Protocol("missing signature".to_string()))?
+            .parse()
+            .map_err(|_| SignerError::Protocol("invalid signature".to_string()))?;
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Protocol(
+                "signature does not verify against the signer's pubkey".to_string(),
+            ));
+        }
+        Ok(signature)
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+impl Drop for ExternalProcess {
+    fn drop(&mut self) {
+        // Don't leave the signer process running once the signer is gone
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+
+    #[test]
+    fn parses_signer_sources() {
+        assert_eq!(
+            "usb://ledger?key=0/0".parse::<SignerSource>().unwrap(),
+            SignerSource::Usb("usb://ledger?key=0/0".to_string())
+        );
+        assert_eq!(
+            "env://VOTER_SECRET".parse::<SignerSource>().unwrap(),
+            SignerSource::Env("VOTER_SECRET".to_string())
+        );
+        assert_eq!(
+            "exec:hsm-signer --slot 2".parse::<SignerSource>().unwrap(),
+            SignerSource::External {
+                program: "hsm-signer".to_string(),
+                args: vec!["--slot".to_string(), "2".to_string()],
+            }
+        );
+        assert_eq!(
+            "file://voters/a.json".parse::<SignerSource>().unwrap(),
+            SignerSource::File(PathBuf::from("voters/a.json"))
+        );
+        assert_eq!(
+            "voters/a.json".parse::<SignerSource>().unwrap(),
+            SignerSource::File(PathBuf::from("voters/a.json"))
+        );
+        assert!("env://".parse::<SignerSource>().is_err());
+        assert!("exec:".parse::<SignerSource>().is_err());
+    }
+
+    #[test]
+    fn decodes_base58_secret_keys() {
+        let keypair = Keypair::new();
+        let decoded = keypair_from_base58(&keypair.to_base58_string()).unwrap();
+        assert_eq!(decoded.pubkey(), keypair.pubkey());
+
+        let mut mismatched = keypair.to_bytes();
+        mismatched[32..].copy_from_slice(Keypair::new().pubkey().as_ref());
+        assert!(keypair_from_base58(&bs58::encode(mismatched).into_string()).is_err());
+        assert!(keypair_from_base58("not base58!").is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::vote::{tally_address, tally_ballots_instruction};
+use anchor_client::{ClientError, Program};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use std::ops::Deref;
+use voting::{Ballot, Candidate, PollKind, Tally};
+
+// Ballots counted per `tally_ballots` transaction, within the transaction
+// size and compute limits
+pub const BALLOTS_PER_CRANK: usize = 20;
+
+// One counting round; `counts` is indexed by candidate and eliminated
+// candidates count zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round {
+    pub counts: Vec<u64>,
+    // Candidate eliminated at the end of the round, if it didn't decide the poll
+    pub eliminated: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TallyResult {
+    pub rounds: Vec<Round>,
+    // `None` if no ballots were cast
+    pub winner: Option<u8>,
+}
+
+// Count approval or ranked-choice ballots off-chain, with the same
+// tie-breaking as the program's crank: the earliest-added candidate wins a tie
+// for first, and the latest-added of the trailing candidates is eliminated
+pub fn tally(kind: PollKind, candidates: usize, ballots: &[Vec<u8>]) -> TallyResult {
+    match kind {
+        PollKind::RankedChoice => instant_runoff(candidates, ballots),
+        PollKind::Approval | PollKind::SingleChoice => approval(candidates, ballots),
+    }
+}
+
+// Every candidate a ballot lists gets a vote; most votes wins
+pub fn approval(candidates: usize, ballots: &[Vec<u8>]) -> TallyResult {
+    let mut counts = vec![0u64; candidates];
+    for candidate in ballots.iter().flatten() {
+        counts[*candidate as usize] += 1;
+    }
+    let winner = leader(&counts, &vec![true; candidates]);
+    TallyResult {
+        rounds: vec![Round {
+            counts,
+            eliminated: None,
+        }],
+        winner,
+    }
+}
+
+// Each ballot counts for its highest-ranked remaining candidate. A candidate
+// with more than half of those votes wins; otherwise the candidate with the
+// fewest is eliminated and the ballots are counted again
+pub fn instant_runoff(candidates: usize, ballots: &[Vec<u8>]) -> TallyResult {
+    let mut standing = vec![true; candidates];
+    let mut rounds = Vec::new();
+    loop {
+        let mut counts = vec![0u64; candidates];
+        for ballot in ballots {
+            if let Some(&first) = ballot.iter().find(|&&c| standing[c as usize]) {
+                counts[first as usize] += 1;
+            }
+        }
+        let total: u64 = counts.iter().sum();
+
+        let winner = leader(&counts, &standing);
+        if winner.is_none_or(|winner| counts[winner as usize] * 2 > total) {
+            rounds.push(Round {
+                counts,
+                eliminated: None,
+            });
+            return TallyResult { rounds, winner };
+        }
+
+        // Scanning from the last candidate, the first lowest count is eliminated
+        let trailing = (0..candidates)
+            .rev()
+            .filter(|&c| standing[c])
+            .min_by_key(|&c| counts[c])
+            .unwrap();
+        standing[trailing] = false;
+        rounds.push(Round {
+            counts,
+            eliminated: Some(trailing as u8),
+        });
+    }
+}
+
+// The standing candidate with the most votes, the earliest-added on a tie, or
+// `None` if no standing candidate has any
+fn leader(counts: &[u64], standing: &[bool]) -> Option<u8> {
+    let mut leader: Option<usize> = None;
+    for candidate in (0..counts.len()).filter(|&c| standing[c] && counts[c] > 0) {
+        if leader.is_none_or(|l| counts[candidate] > counts[l]) {
+            leader = Some(candidate);
+        }
+    }
+    leader.map(|c| c as u8)
+}
+
+// Every ballot cast in poll `poll_id`
+pub async fn fetch_ballots<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Vec<(Pubkey, Ballot)>, ClientError> {
+    // `poll_id` follows the 8-byte account discriminator
+    let filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8, &poll_id.to_le_bytes()));
+    program.accounts(vec![filter]).await
+}
+
+// The candidates of poll `poll_id`, in the order they were added
+pub async fn fetch_candidates<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Vec<Candidate>, ClientError> {
+    let filter = RpcFilterType::Memcmp(Memcmp::new_base58_encoded(8, &poll_id.to_le_bytes()));
+    let mut candidates: Vec<Candidate> = program
+        .accounts::<Candidate>(vec![filter])
+        .await?
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect();
+    candidates.sort_by_key(|candidate| candidate.index);
+    Ok(candidates)
+}
+
+pub async fn fetch_tally<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Tally, ClientError> {
+    program.account(tally_address(&program.id(), poll_id)).await
+}
+
+// Crank `tally_ballots` until the poll's count is complete, returning the
+// final tally. Each round sends the ballots not yet counted in it, in chunks
+// of `BALLOTS_PER_CRANK`; a poll without ballots is settled by an empty crank
+pub async fn crank_tally<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Tally, ClientError> {
+    let mut tally = fetch_tally(program, poll_id).await?;
+    while !tally.complete {
+        let round = tally.round;
+        let pending: Vec<Pubkey> = fetch_ballots(program, poll_id)
+            .await?
+            .into_iter()
+            .filter(|(_, ballot)| ballot.counted_round != round)
+            .map(|(address, _)| address)
+            .collect();
+        let chunks: Vec<&[Pubkey]> = if pending.is_empty() {
+            vec![&[]]
+        } else {
+            pending.chunks(BALLOTS_PER_CRANK).collect()
+        };
+        for chunk in chunks {
+            let ix = tally_ballots_instruction(&program.id(), poll_id, chunk);
+            program.request().instruction(ix).send().await?;
+        }
+        tally = fetch_tally(program, poll_id).await?;
+    }
+    Ok(tally)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instant_runoff_transfers_eliminated_votes() {
+        // 0 leads the first round but 2's voters prefer 1 over 0
+        let ballots = [
+            vec![0],
+            vec![0],
+            vec![0, 1],
+            vec![1, 0],
+            vec![1],
+            vec![2, 1],
+            vec![2, 1],
+        ];
+        let result = instant_runoff(3, &ballots);
+        assert_eq!(
+            result.rounds,
+            vec![
+                Round {
+                    counts: vec![3, 2, 2],
+                    eliminated: Some(2),
+                },
+                Round {
+                    counts: vec![3, 4, 0],
+                    eliminated: None,
+                },
+            ]
+        );
+        assert_eq!(result.winner, Some(1));
+    }
+
+    #[test]
+    fn ties_favor_the_earliest_candidate() {
+        let ballots = [vec![0], vec![1]];
+        // A tied final round eliminates the later candidate
+        let result = instant_runoff(2, &ballots);
+        assert_eq!(result.rounds[0].eliminated, Some(1));
+        assert_eq!(result.winner, Some(0));
+
+        assert_eq!(approval(3, &[vec![2, 1], vec![1, 2]]).winner, Some(1));
+    }
+
+    #[test]
+    fn no_ballots_no_winner() {
+        assert_eq!(instant_runoff(3, &[]).winner, None);
+        assert_eq!(approval(3, &[]).winner, None);
+    }
+}
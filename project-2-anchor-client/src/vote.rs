@@ -0,0 +1,695 @@
+use crate::compute_budget::ComputeBudget;
+use accelerate_client::{nonblocking, Backoff};
+use anchor_client::{ClientError, Program};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signature,
+    signer::Signer,
+    system_program,
+    transaction::Transaction,
+};
+use std::ops::Deref;
+use voting::{Ballot, Poll, PollKind, VoterReceipt};
+
+// Derive the poll account for a given poll ID
+pub fn poll_address(program_id: &Pubkey, poll_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"poll", &poll_id.to_le_bytes()], program_id).0
+}
+
+// Derive the candidate account for a given poll ID and candidate name
+pub fn candidate_address(program_id: &Pubkey, poll_id: u64, candidate: &str) -> Pubkey {
+    Pubkey::find_program_address(&[&poll_id.to_le_bytes(), candidate.as_bytes()], program_id).0
+}
+
+// Derive the receipt recording `voter`'s vote in a given poll
+pub fn voter_receipt_address(program_id: &Pubkey, poll_id: u64, voter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[&poll_id.to_le_bytes(), voter.as_ref()], program_id).0
+}
+
+// Derive the approval or ranked-choice ballot `voter` cast in a given poll
+pub fn ballot_address(program_id: &Pubkey, poll_id: u64, voter: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"ballot", &poll_id.to_le_bytes(), voter.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+// Derive the account tracking an approval or ranked-choice poll's count
+pub fn tally_address(program_id: &Pubkey, poll_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"tally", &poll_id.to_le_bytes()], program_id).0
+}
+
+// Derive the token account holding a token-weighted poll's escrowed votes
+pub fn escrow_address(program_id: &Pubkey, poll_id: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"escrow", &poll_id.to_le_bytes()], program_id).0
+}
+
+// Governance token accounts of a token-weighted poll
+#[derive(Debug, Clone, Copy)]
+pub struct GovernanceTokens {
+    pub mint: Pubkey,
+    // SPL Token or Token-2022, whichever owns `mint`
+    pub token_program: Pubkey,
+}
+
+impl GovernanceTokens {
+    // The voter's associated token account for the governance mint
+    pub fn voter_account(&self, voter: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(voter, &self.mint, &self.token_program)
+    }
+}
+
+// Build the `initialize_poll` instruction, paid for by `signer`
+pub fn initialize_poll_instruction(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    poll_id: u64,
+    description: &str,
+    poll_start: u64,
+    poll_end: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::InitializePoll {
+            signer: *signer,
+            poll: poll_address(program_id, poll_id),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::InitializePoll {
+            poll_id,
+            description: description.to_string(),
+            poll_start,
+            poll_end,
+        }
+        .data(),
+    }
+}
+
+// Build the `initialize_token_poll` instruction, paid for by `signer`
+pub fn initialize_token_poll_instruction(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    poll_id: u64,
+    description: &str,
+    poll_start: u64,
+    poll_end: u64,
+    tokens: &GovernanceTokens,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::InitializeTokenPoll {
+            signer: *signer,
+            poll: poll_address(program_id, poll_id),
+            governance_mint: tokens.mint,
+            escrow: escrow_address(program_id, poll_id),
+            token_program: tokens.token_program,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::InitializeTokenPoll {
+            poll_id,
+            description: description.to_string(),
+            poll_start,
+            poll_end,
+        }
+        .data(),
+    }
+}
+
+// Build the `initialize_sealed_poll` instruction, paid for by `signer`
+pub fn initialize_sealed_poll_instruction(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    poll_id: u64,
+    description: &str,
+    poll_start: u64,
+    poll_end: u64,
+    reveal_end: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::InitializePoll {
+            signer: *signer,
+            poll: poll_address(program_id, poll_id),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::InitializeSealedPoll {
+            poll_id,
+            description: description.to_string(),
+            poll_start,
+            poll_end,
+            reveal_end,
+        }
+        .data(),
+    }
+}
+
+// Build the `initialize_ballot_poll` instruction for an approval or
+// ranked-choice poll, paid for by `signer`
+pub fn initialize_ballot_poll_instruction(
+    program_id: &Pubkey,
+    signer: &Pubkey,
+    poll_id: u64,
+    description: &str,
+    poll_start: u64,
+    poll_end: u64,
+    kind: PollKind,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::InitializeBallotPoll {
+            signer: *signer,
+            poll: poll_address(program_id, poll_id),
+            tally: tally_address(program_id, poll_id),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::InitializeBallotPoll {
+            poll_id,
+            description: description.to_string(),
+            poll_start,
+            poll_end,
+            kind,
+        }
+        .data(),
+    }
+}
+
+// Build the `initialize_candidate` instruction, paid for by `signer`
+pub fn initialize_candidate_instruction(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::InitializeCandidate {
+            authority: *authority,
+            poll: poll_address(program_id, poll_id),
+            candidate: candidate_address(program_id, poll_id, candidate),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::InitializeCandidate {
+            candidate_name: candidate.to_string(),
+            poll_id,
+        }
+        .data(),
+    }
+}
+
+// Build the `vote` instruction for the given voter, poll and candidate
+pub fn vote_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::Vote {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            candidate: candidate_address(program_id, poll_id, candidate),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::Vote {
+            candidate_name: candidate.to_string(),
+            poll_id,
+        }
+        .data(),
+    }
+}
+
+// Build the `vote_weighted` instruction, locking `amount` of the voter's
+// governance tokens (from their associated token account) in the poll's escrow
+pub fn vote_weighted_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+    tokens: &GovernanceTokens,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::VoteWeighted {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            candidate: candidate_address(program_id, poll_id, candidate),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+            governance_mint: tokens.mint,
+            voter_token_account: tokens.voter_account(voter),
+            escrow: escrow_address(program_id, poll_id),
+            token_program: tokens.token_program,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::VoteWeighted {
+            candidate_name: candidate.to_string(),
+            poll_id,
+            amount,
+        }
+        .data(),
+    }
+}
+
+// Build the `commit_vote` instruction sealing `voter`'s ballot in a commit-reveal poll
+pub fn commit_vote_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    commitment: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::CommitVote {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::CommitVote {
+            poll_id,
+            commitment,
+        }
+        .data(),
+    }
+}
+
+// Build the `reveal_vote` instruction opening `voter`'s sealed ballot
+pub fn reveal_vote_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+    salt: [u8; 32],
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::RevealVote {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            candidate: candidate_address(program_id, poll_id, candidate),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::RevealVote {
+            candidate_name: candidate.to_string(),
+            poll_id,
+            salt,
+        }
+        .data(),
+    }
+}
+
+// Build the `cast_ballot` instruction; `rankings` are candidate indexes,
+// most preferred first in a ranked-choice poll
+pub fn cast_ballot_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    rankings: &[u8],
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::CastBallot {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            ballot: ballot_address(program_id, poll_id, voter),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::CastBallot {
+            poll_id,
+            rankings: rankings.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// Build the `tally_ballots` crank instruction counting `ballots` into the current round
+pub fn tally_ballots_instruction(
+    program_id: &Pubkey,
+    poll_id: u64,
+    ballots: &[Pubkey],
+) -> Instruction {
+    let mut accounts = voting::accounts::TallyBallots {
+        poll: poll_address(program_id, poll_id),
+        tally: tally_address(program_id, poll_id),
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        ballots
+            .iter()
+            .map(|ballot| AccountMeta::new(*ballot, false)),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: voting::instruction::TallyBallots { poll_id }.data(),
+    }
+}
+
+// Build the `withdraw_escrow` instruction returning the voter's tokens after the poll
+pub fn withdraw_escrow_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    tokens: &GovernanceTokens,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::WithdrawEscrow {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+            governance_mint: tokens.mint,
+            voter_token_account: tokens.voter_account(voter),
+            escrow: escrow_address(program_id, poll_id),
+            token_program: tokens.token_program,
+        }
+        .to_account_metas(None),
+        data: voting::instruction::WithdrawEscrow { poll_id }.data(),
+    }
+}
+
+// Build the `change_vote` instruction moving `voter`'s vote from
+// `previous_candidate` (as recorded in their receipt) to `candidate`
+pub fn change_vote_instruction(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    previous_candidate: &str,
+    candidate: &str,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::ChangeVote {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+            previous_candidate: candidate_address(program_id, poll_id, previous_candidate),
+            candidate: candidate_address(program_id, poll_id, candidate),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::ChangeVote {
+            candidate_name: candidate.to_string(),
+            poll_id,
+        }
+        .data(),
+    }
+}
+
+// Build the `finalize_poll` crank instruction; `candidates` must name every
+// candidate of the poll
+pub fn finalize_poll_instruction(
+    program_id: &Pubkey,
+    poll_id: u64,
+    kind: PollKind,
+    candidates: &[&str],
+) -> Instruction {
+    // Anchor marks an omitted optional account with the program's own ID
+    let tally = match kind {
+        PollKind::SingleChoice => *program_id,
+        _ => tally_address(program_id, poll_id),
+    };
+    let mut accounts = voting::accounts::FinalizePoll {
+        poll: poll_address(program_id, poll_id),
+        tally: Some(tally),
+    }
+    .to_account_metas(None);
+    accounts.extend(candidates.iter().map(|candidate| {
+        AccountMeta::new_readonly(candidate_address(program_id, poll_id, candidate), false)
+    }));
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: voting::instruction::FinalizePoll { poll_id }.data(),
+    }
+}
+
+// Build the `close_candidate` instruction returning a finalized poll's
+// candidate rent to the poll's `authority`
+pub fn close_candidate_instruction(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    poll_id: u64,
+    candidate: &str,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::CloseCandidate {
+            authority: *authority,
+            poll: poll_address(program_id, poll_id),
+            candidate: candidate_address(program_id, poll_id, candidate),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::CloseCandidate {
+            _candidate_name: candidate.to_string(),
+            _poll_id: poll_id,
+        }
+        .data(),
+    }
+}
+
+// Build the `close_receipt` instruction returning `voter`'s receipt rent
+// once the poll is finalized
+pub fn close_receipt_instruction(program_id: &Pubkey, voter: &Pubkey, poll_id: u64) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::CloseReceipt {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            voter_receipt: voter_receipt_address(program_id, poll_id, voter),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::CloseReceipt { _poll_id: poll_id }.data(),
+    }
+}
+
+// Build the `close_ballot` instruction returning `voter`'s ballot rent once
+// the poll is finalized
+pub fn close_ballot_instruction(program_id: &Pubkey, voter: &Pubkey, poll_id: u64) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: voting::accounts::CloseBallot {
+            signer: *voter,
+            poll: poll_address(program_id, poll_id),
+            ballot: ballot_address(program_id, poll_id, voter),
+        }
+        .to_account_metas(None),
+        data: voting::instruction::CloseBallot { _poll_id: poll_id }.data(),
+    }
+}
+
+// The receipt of `voter`'s vote in a poll, or `None` if they haven't voted
+pub async fn fetch_receipt<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    voter: &Pubkey,
+) -> Result<Option<VoterReceipt>, ClientError> {
+    let address = voter_receipt_address(&program.id(), poll_id, voter);
+    match program.account::<VoterReceipt>(address).await {
+        Ok(receipt) => Ok(Some(receipt)),
+        Err(ClientError::AccountNotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// `voter`'s approval or ranked-choice ballot in a poll, or `None` if they haven't cast one
+pub async fn fetch_ballot<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    voter: &Pubkey,
+) -> Result<Option<Ballot>, ClientError> {
+    let address = ballot_address(&program.id(), poll_id, voter);
+    match program.account::<Ballot>(address).await {
+        Ok(ballot) => Ok(Some(ballot)),
+        Err(ClientError::AccountNotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn fetch_poll<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Poll, ClientError> {
+    program.account(poll_address(&program.id(), poll_id)).await
+}
+
+// Governance tokens of poll `poll_id`, or `None` for a one-wallet-one-vote poll
+pub async fn fetch_governance_tokens<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+) -> Result<Option<GovernanceTokens>, ClientError> {
+    let poll = fetch_poll(program, poll_id).await?;
+    governance_tokens(program, &poll).await
+}
+
+// Governance tokens of an already fetched poll
+pub async fn governance_tokens<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll: &Poll,
+) -> Result<Option<GovernanceTokens>, ClientError> {
+    let Some(mint) = poll.governance_mint else {
+        return Ok(None);
+    };
+    let token_program = program.rpc().get_account(&mint).await?.owner;
+    Ok(Some(GovernanceTokens {
+        mint,
+        token_program,
+    }))
+}
+
+// How a vote transaction is sent
+#[derive(Debug, Clone, Copy)]
+pub struct SendOptions {
+    // Attempts before giving up, each with a fresh blockhash
+    pub max_attempts: u32,
+    pub compute_budget: ComputeBudget,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            compute_budget: ComputeBudget::default(),
+        }
+    }
+}
+
+// Outcome of a vote submitted with retries
+#[derive(Debug)]
+pub struct VoteOutcome {
+    pub attempts: u32,
+    pub result: Result<Signature, accelerate_client::Error>,
+}
+
+// Send a vote, fetching a fresh blockhash (and re-evaluating the compute
+// budget) for every attempt.
+//
+// A vote is only signed again once the previous attempt's blockhash has
+// expired without it landing, so it can't be sent twice; a transaction that
+// the program rejects is returned as an error straight away.
+pub async fn send_vote<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    candidate: &str,
+    options: &SendOptions,
+) -> VoteOutcome {
+    let ix = vote_instruction(&program.id(), &program.payer(), poll_id, candidate);
+    send_with_retries(program, ix, options).await
+}
+
+// Send a vote that also counts `delegators`, retried like `send_vote`
+pub async fn send_delegated_vote<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    candidate: &str,
+    delegators: &[Pubkey],
+    options: &SendOptions,
+) -> VoteOutcome {
+    let ix = crate::delegation::delegated_vote_instruction(
+        &program.id(),
+        &program.payer(),
+        poll_id,
+        candidate,
+        delegators,
+    );
+    send_with_retries(program, ix, options).await
+}
+
+// Send a token-weighted vote locking `amount` governance tokens, retried like `send_vote`
+pub async fn send_weighted_vote<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    candidate: &str,
+    tokens: &GovernanceTokens,
+    amount: u64,
+    options: &SendOptions,
+) -> VoteOutcome {
+    let ix = vote_weighted_instruction(
+        &program.id(),
+        &program.payer(),
+        poll_id,
+        candidate,
+        tokens,
+        amount,
+    );
+    send_with_retries(program, ix, options).await
+}
+
+// Send a sealed ballot's commitment, retried like `send_vote`
+pub async fn send_sealed_vote<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    commitment: [u8; 32],
+    options: &SendOptions,
+) -> VoteOutcome {
+    let ix = commit_vote_instruction(&program.id(), &program.payer(), poll_id, commitment);
+    send_with_retries(program, ix, options).await
+}
+
+// Send an approval or ranked-choice ballot, retried like `send_vote`
+pub async fn send_ballot<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    poll_id: u64,
+    rankings: &[u8],
+    options: &SendOptions,
+) -> VoteOutcome {
+    let ix = cast_ballot_instruction(&program.id(), &program.payer(), poll_id, rankings);
+    send_with_retries(program, ix, options).await
+}
+
+async fn send_with_retries<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    ix: Instruction,
+    options: &SendOptions,
+) -> VoteOutcome {
+    let rpc = program.rpc();
+    let sign = || async {
+        sign_vote(program, &ix, options)
+            .await
+            .map_err(|err| accelerate_client::Error::Build(Box::new(err)))
+    };
+    let sent = nonblocking::send_signed_and_confirm(
+        &rpc,
+        rpc.commitment(),
+        &Backoff::default(),
+        options.max_attempts,
+        sign,
+    )
+    .await;
+    VoteOutcome {
+        attempts: sent.attempts,
+        result: sent.result,
+    }
+}
+
+// Sign `ix` with a fresh blockhash and compute budget
+async fn sign_vote<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    ix: &Instruction,
+    options: &SendOptions,
+) -> Result<Transaction, ClientError> {
+    let budget = options
+        .compute_budget
+        .instructions(&program.rpc(), &program.payer(), std::slice::from_ref(ix))
+        .await?;
+    let mut request = program.request();
+    for budget_ix in budget {
+        request = request.instruction(budget_ix);
+    }
+    request.instruction(ix.clone()).signed_transaction().await
+}
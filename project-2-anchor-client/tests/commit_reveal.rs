@@ -0,0 +1,169 @@
+// Sealed-ballot polls: commitments made while the poll is open only count
+// once revealed within the reveal window
+mod common;
+
+use common::{fetch, funded_voter, program_test, resend, send, set_time};
+use solana_sdk::{clock::Clock, signer::Signer};
+use voting::{Candidate, VoterReceipt};
+use voting_client::{
+    sealed::SealedBallot,
+    vote::{
+        candidate_address, change_vote_instruction, commit_vote_instruction,
+        initialize_candidate_instruction, initialize_sealed_poll_instruction,
+        reveal_vote_instruction, vote_instruction, voter_receipt_address,
+    },
+};
+
+#[tokio::test]
+async fn test_commit_reveal_counts_only_revealed_ballots() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let clock: Clock = banks.get_sysvar().await.unwrap();
+    let start = clock.unix_timestamp as u64;
+    let (poll_end, reveal_end) = (start + 100, start + 200);
+
+    let instructions = [
+        initialize_sealed_poll_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            "Sealed peanut butter poll",
+            start,
+            poll_end,
+            reveal_end,
+        ),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Crunchy"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    let alice = funded_voter(&mut banks, &payer).await;
+    let bob = funded_voter(&mut banks, &payer).await;
+    let carol = funded_voter(&mut banks, &payer).await;
+    let eve = funded_voter(&mut banks, &payer).await;
+
+    // Open ballots are refused in a sealed poll
+    let open = vote_instruction(&voting::ID, &alice.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[open], &alice, &[&alice]).await.is_err());
+
+    let ballots = [
+        (
+            &alice,
+            SealedBallot::new(&voting::ID, 1, &alice.pubkey(), "Smooth"),
+        ),
+        (
+            &bob,
+            SealedBallot::new(&voting::ID, 1, &bob.pubkey(), "Crunchy"),
+        ),
+        (
+            &carol,
+            SealedBallot::new(&voting::ID, 1, &carol.pubkey(), "Crunchy"),
+        ),
+    ];
+    for (voter, ballot) in &ballots {
+        let commit = commit_vote_instruction(
+            &voting::ID,
+            &voter.pubkey(),
+            1,
+            ballot.commitment().unwrap(),
+        );
+        send(&mut banks, &[commit], voter, &[voter]).await.unwrap();
+    }
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &alice.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.commitment, Some(ballots[0].1.commitment().unwrap()));
+
+    // Eve copies Alice's commitment, which is bound to Alice
+    let copy = commit_vote_instruction(
+        &voting::ID,
+        &eve.pubkey(),
+        1,
+        ballots[0].1.commitment().unwrap(),
+    );
+    send(&mut banks, &[copy], &eve, &[&eve]).await.unwrap();
+
+    // Nothing is tallied, and nothing can be revealed or changed, while ballots are sealed
+    let reveal = |(voter, ballot): &(&solana_sdk::signature::Keypair, SealedBallot)| {
+        reveal_vote_instruction(
+            &voting::ID,
+            &voter.pubkey(),
+            1,
+            &ballot.candidate,
+            ballot.salt().unwrap(),
+        )
+    };
+    assert!(send(&mut banks, &[reveal(&ballots[0])], &alice, &[&alice])
+        .await
+        .is_err());
+    let change = change_vote_instruction(&voting::ID, &alice.pubkey(), 1, "Smooth", "Crunchy");
+    assert!(send(&mut banks, &[change], &alice, &[&alice])
+        .await
+        .is_err());
+    let smooth: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Smooth")).await;
+    assert_eq!(smooth.candidate_votes, 0);
+
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let dave = funded_voter(&mut banks, &payer).await;
+    let late = SealedBallot::new(&voting::ID, 1, &dave.pubkey(), "Smooth");
+    let late = commit_vote_instruction(&voting::ID, &dave.pubkey(), 1, late.commitment().unwrap());
+    assert!(send(&mut banks, &[late], &dave, &[&dave]).await.is_err());
+
+    // A reveal must match the commitment
+    let lie = reveal_vote_instruction(
+        &voting::ID,
+        &alice.pubkey(),
+        1,
+        "Crunchy",
+        ballots[0].1.salt().unwrap(),
+    );
+    assert!(send(&mut banks, &[lie], &alice, &[&alice]).await.is_err());
+    let copied = reveal_vote_instruction(
+        &voting::ID,
+        &eve.pubkey(),
+        1,
+        &ballots[0].1.candidate,
+        ballots[0].1.salt().unwrap(),
+    );
+    assert!(send(&mut banks, &[copied], &eve, &[&eve]).await.is_err());
+
+    resend(&mut banks, &[reveal(&ballots[0])], &alice, &[&alice])
+        .await
+        .unwrap();
+    send(&mut banks, &[reveal(&ballots[1])], &bob, &[&bob])
+        .await
+        .unwrap();
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &alice.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.commitment, None);
+    assert_eq!(receipt.candidate_name, "Smooth");
+
+    // Carol misses the reveal window, so her ballot is discarded
+    set_time(&mut context, reveal_end as i64 + 1).await;
+    assert!(send(&mut banks, &[reveal(&ballots[2])], &carol, &[&carol])
+        .await
+        .is_err());
+
+    let smooth: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Smooth")).await;
+    let crunchy: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Crunchy")).await;
+    assert_eq!(smooth.candidate_votes, 1);
+    assert_eq!(crunchy.candidate_votes, 1);
+}
+
+#[tokio::test]
+async fn test_sealed_poll_needs_reveal_window_after_close() {
+    let (mut banks, payer) = common::start().await;
+    let backwards =
+        initialize_sealed_poll_instruction(&voting::ID, &payer.pubkey(), 1, "Poll", 0, 10, 10);
+    assert!(send(&mut banks, &[backwards], &payer, &[&payer])
+        .await
+        .is_err());
+}
@@ -0,0 +1,110 @@
+// Helpers shared by the in-process bank tests
+#![allow(dead_code)]
+
+use anchor_lang::AccountDeserialize;
+use solana_program_test::{
+    processor, BanksClient, BanksClientError, ProgramTest, ProgramTestContext,
+};
+use solana_sdk::{
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    system_instruction, transaction::Transaction,
+};
+use std::time::Duration;
+use voting_client::vote::{initialize_candidate_instruction, initialize_poll_instruction};
+
+// Anchor's entrypoint ties the account slice and account infos to a single
+// lifetime, which the native processor signature doesn't provide
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    voting::entry(program_id, accounts, data)
+}
+
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("voting", voting::ID, processor!(process_instruction))
+}
+
+pub async fn start() -> (BanksClient, Keypair) {
+    let (banks, payer, _) = program_test().start().await;
+    (banks, payer)
+}
+
+pub async fn send(
+    banks: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = banks.get_latest_blockhash().await?;
+    let tx =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), signers, blockhash);
+    banks.process_transaction(tx).await
+}
+
+// Send a transaction identical to one the bank already processed, such as
+// one that was expected to fail earlier, under a new blockhash; otherwise the
+// bank may answer with the earlier result
+pub async fn resend(
+    banks: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let previous = banks.get_latest_blockhash().await?;
+    let mut blockhash = previous;
+    while blockhash == previous {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        blockhash = banks.get_latest_blockhash().await?;
+    }
+    let tx =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), signers, blockhash);
+    banks.process_transaction(tx).await
+}
+
+pub async fn fetch<T: AccountDeserialize>(banks: &mut BanksClient, address: Pubkey) -> T {
+    let account = banks
+        .get_account(address)
+        .await
+        .unwrap()
+        .expect("account does not exist");
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+// Create poll `poll_id` with the given candidates
+pub async fn create_poll(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    poll_id: u64,
+    candidates: &[&str],
+) {
+    let mut instructions = vec![initialize_poll_instruction(
+        &voting::ID,
+        &payer.pubkey(),
+        poll_id,
+        "What is the best peanut butter?",
+        0,
+        u64::MAX,
+    )];
+    instructions.extend(candidates.iter().map(|candidate| {
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), poll_id, candidate)
+    }));
+    send(banks, &instructions, payer, &[payer]).await.unwrap();
+}
+
+pub async fn funded_voter(banks: &mut BanksClient, payer: &Keypair) -> Keypair {
+    let voter = Keypair::new();
+    let fund = system_instruction::transfer(&payer.pubkey(), &voter.pubkey(), LAMPORTS_PER_SOL);
+    send(banks, &[fund], payer, &[payer]).await.unwrap();
+    voter
+}
+
+// Move the bank's clock to `unix_timestamp`
+pub async fn set_time(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
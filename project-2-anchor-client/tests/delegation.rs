@@ -0,0 +1,269 @@
+// Delegated voting: set and clear delegations, and votes carrying the weight
+// of non-voting delegators, run in an in-process bank
+mod common;
+
+use common::{create_poll, fetch, funded_voter, resend, send, start};
+use solana_program_test::BanksClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use std::collections::HashSet;
+use voting::{Candidate, Delegation, VoterReceipt};
+use voting_client::{
+    delegation::{
+        clear_delegation_instruction, delegated_vote_instruction, delegation_address,
+        poll_delegations_address, set_delegation_instruction, DelegationGraph,
+        MAX_DELEGATORS_PER_VOTE,
+    },
+    vote::{candidate_address, change_vote_instruction, vote_instruction, voter_receipt_address},
+};
+
+// Delegate `delegator`'s vote to `delegate`, passing the chain accounts the
+// client derives from the delegations already made
+async fn delegate(
+    banks: &mut BanksClient,
+    delegations: &mut Vec<Delegation>,
+    delegator: &Keypair,
+    poll_id: Option<u64>,
+    delegate: &Pubkey,
+) {
+    let graph = DelegationGraph::new(delegations, poll_id);
+    let chain = graph
+        .chain_accounts(&voting::ID, &delegator.pubkey(), delegate)
+        .unwrap();
+    let ix =
+        set_delegation_instruction(&voting::ID, &delegator.pubkey(), poll_id, delegate, &chain);
+    send(banks, &[ix], delegator, &[delegator]).await.unwrap();
+    delegations.push(Delegation {
+        delegator: delegator.pubkey(),
+        delegate: *delegate,
+        poll_id,
+    });
+}
+
+async fn votes(banks: &mut BanksClient, candidate: &str) -> u64 {
+    fetch::<Candidate>(banks, candidate_address(&voting::ID, 1, candidate))
+        .await
+        .candidate_votes
+}
+
+#[tokio::test]
+async fn test_vote_counts_chained_delegators() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth", "Crunchy"]).await;
+    let [a, b, c, d] = [
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+    ];
+
+    // a -> b -> c globally, and d -> c only in poll 1
+    let mut delegations = Vec::new();
+    delegate(&mut banks, &mut delegations, &b, None, &c.pubkey()).await;
+    delegate(&mut banks, &mut delegations, &a, None, &b.pubkey()).await;
+    delegate(&mut banks, &mut delegations, &d, Some(1), &c.pubkey()).await;
+    let stored: Delegation = fetch(
+        &mut banks,
+        delegation_address(&voting::ID, Some(1), &d.pubkey()),
+    )
+    .await;
+    assert_eq!(stored.delegate, c.pubkey());
+
+    let graph = DelegationGraph::new(&delegations, Some(1));
+    let delegators = graph.delegators_of(&c.pubkey(), &HashSet::new());
+    assert_eq!(delegators.len(), 3);
+
+    // A delegator listed before its delegate isn't counted
+    let reversed: Vec<Pubkey> = delegators.iter().rev().copied().collect();
+    let vote = delegated_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", &reversed);
+    assert!(send(&mut banks, &[vote], &c, &[&c]).await.is_err());
+    // Nor is a member who doesn't delegate
+    let vote = delegated_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", &[payer.pubkey()]);
+    assert!(send(&mut banks, &[vote], &c, &[&c]).await.is_err());
+
+    let vote = delegated_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", &delegators);
+    send(&mut banks, &[vote], &c, &[&c]).await.unwrap();
+    assert_eq!(votes(&mut banks, "Smooth").await, 4);
+
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &c.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.weight, 4);
+    for delegator in [&a, &b, &d] {
+        let receipt: VoterReceipt = fetch(
+            &mut banks,
+            voter_receipt_address(&voting::ID, 1, &delegator.pubkey()),
+        )
+        .await;
+        assert_eq!(receipt.weight, 0);
+        assert_eq!(receipt.delegate, Some(c.pubkey()));
+        assert_eq!(receipt.candidate_name, "Smooth");
+    }
+
+    // Counted delegators can't vote again or move the delegate's vote
+    let vote = vote_instruction(&voting::ID, &a.pubkey(), 1, "Crunchy");
+    assert!(send(&mut banks, &[vote], &a, &[&a]).await.is_err());
+    let change = change_vote_instruction(&voting::ID, &a.pubkey(), 1, "Smooth", "Crunchy");
+    assert!(send(&mut banks, &[change], &a, &[&a]).await.is_err());
+
+    // The delegate moves the whole weight
+    let change = change_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", "Crunchy");
+    send(&mut banks, &[change], &c, &[&c]).await.unwrap();
+    assert_eq!(votes(&mut banks, "Smooth").await, 0);
+    assert_eq!(votes(&mut banks, "Crunchy").await, 4);
+}
+
+#[tokio::test]
+async fn test_delegators_who_voted_keep_their_vote() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth", "Crunchy"]).await;
+    let [a, b, c] = [
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+    ];
+    let mut delegations = Vec::new();
+    delegate(&mut banks, &mut delegations, &b, None, &c.pubkey()).await;
+    delegate(&mut banks, &mut delegations, &a, None, &b.pubkey()).await;
+
+    // b votes first, carrying a
+    let graph = DelegationGraph::new(&delegations, Some(1));
+    let delegators = graph.delegators_of(&b.pubkey(), &HashSet::new());
+    assert_eq!(delegators, vec![a.pubkey()]);
+    let vote = delegated_vote_instruction(&voting::ID, &b.pubkey(), 1, "Crunchy", &delegators);
+    send(&mut banks, &[vote], &b, &[&b]).await.unwrap();
+
+    // c can't count b (or a through b) any more
+    let vote = delegated_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", &[b.pubkey()]);
+    assert!(send(&mut banks, &[vote], &c, &[&c]).await.is_err());
+    let voted = HashSet::from([b.pubkey()]);
+    assert!(graph.delegators_of(&c.pubkey(), &voted).is_empty());
+    let vote = vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth");
+    send(&mut banks, &[vote], &c, &[&c]).await.unwrap();
+
+    assert_eq!(votes(&mut banks, "Crunchy").await, 2);
+    assert_eq!(votes(&mut banks, "Smooth").await, 1);
+}
+
+#[tokio::test]
+async fn test_set_delegation_rejects_cycles() {
+    let (mut banks, payer) = start().await;
+    let [a, b, c] = [
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+    ];
+    let ix = set_delegation_instruction(&voting::ID, &a.pubkey(), None, &a.pubkey(), &[]);
+    assert!(send(&mut banks, &[ix], &a, &[&a]).await.is_err());
+
+    let mut delegations = Vec::new();
+    delegate(&mut banks, &mut delegations, &a, None, &b.pubkey()).await;
+    delegate(&mut banks, &mut delegations, &b, None, &c.pubkey()).await;
+
+    // c -> a closes the cycle; the program follows the chain a -> b -> c
+    let chain: Vec<Pubkey> = [a.pubkey(), b.pubkey(), c.pubkey()]
+        .iter()
+        .flat_map(|member| {
+            [
+                delegation_address(&voting::ID, None, member),
+                poll_delegations_address(&voting::ID, member),
+            ]
+        })
+        .collect();
+    let ix = set_delegation_instruction(&voting::ID, &c.pubkey(), None, &a.pubkey(), &chain);
+    assert!(send(&mut banks, &[ix], &c, &[&c]).await.is_err());
+    // Leaving out the end of the chain doesn't get around the check
+    let ix = set_delegation_instruction(&voting::ID, &c.pubkey(), None, &a.pubkey(), &chain[..2]);
+    assert!(send(&mut banks, &[ix], &c, &[&c]).await.is_err());
+    let graph = DelegationGraph::new(&delegations, None);
+    assert!(graph
+        .chain_accounts(&voting::ID, &c.pubkey(), &a.pubkey())
+        .is_err());
+
+    // After a clears their delegation, c can delegate to a
+    let clear = clear_delegation_instruction(&voting::ID, &a.pubkey(), None);
+    send(&mut banks, &[clear], &a, &[&a]).await.unwrap();
+    assert!(banks
+        .get_account(delegation_address(&voting::ID, None, &a.pubkey()))
+        .await
+        .unwrap()
+        .is_none());
+    delegations.retain(|d| d.delegator != a.pubkey());
+    delegate(&mut banks, &mut delegations, &c, None, &a.pubkey()).await;
+
+    // Clearing again fails as there's nothing to close
+    let clear = clear_delegation_instruction(&voting::ID, &a.pubkey(), None);
+    assert!(resend(&mut banks, &[clear], &a, &[&a]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_set_delegation_rejects_cycles_across_scopes() {
+    let (mut banks, payer) = start().await;
+    let [a, b] = [
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+    ];
+    let mut delegations = Vec::new();
+    delegate(&mut banks, &mut delegations, &b, Some(1), &a.pubkey()).await;
+
+    // A global a -> b would close a -> b -> a in poll 1
+    let graph = DelegationGraph::new(&delegations, None);
+    assert!(graph
+        .chain_accounts(&voting::ID, &a.pubkey(), &b.pubkey())
+        .is_err());
+    let chain = [
+        delegation_address(&voting::ID, None, &b.pubkey()),
+        poll_delegations_address(&voting::ID, &b.pubkey()),
+    ];
+    let ix = set_delegation_instruction(&voting::ID, &a.pubkey(), None, &b.pubkey(), &chain);
+    assert!(send(&mut banks, &[ix], &a, &[&a]).await.is_err());
+    // Nor can the poll delegation's account be left out
+    let ix = set_delegation_instruction(&voting::ID, &a.pubkey(), None, &b.pubkey(), &chain[..1]);
+    assert!(send(&mut banks, &[ix], &a, &[&a]).await.is_err());
+
+    // Set the other way round, b's poll delegation is the one rejected
+    let clear = clear_delegation_instruction(&voting::ID, &b.pubkey(), Some(1));
+    send(&mut banks, &[clear], &b, &[&b]).await.unwrap();
+    delegations.clear();
+    delegate(&mut banks, &mut delegations, &a, None, &b.pubkey()).await;
+    assert!(DelegationGraph::new(&delegations, Some(1))
+        .chain_accounts(&voting::ID, &b.pubkey(), &a.pubkey())
+        .is_err());
+    let chain = [
+        delegation_address(&voting::ID, Some(1), &a.pubkey()),
+        delegation_address(&voting::ID, None, &a.pubkey()),
+        delegation_address(&voting::ID, Some(1), &b.pubkey()),
+        delegation_address(&voting::ID, None, &b.pubkey()),
+    ];
+    let ix = set_delegation_instruction(&voting::ID, &b.pubkey(), Some(1), &a.pubkey(), &chain);
+    assert!(send(&mut banks, &[ix], &b, &[&b]).await.is_err());
+}
+
+#[tokio::test]
+async fn test_max_delegators_fit_in_one_vote() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth"]).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+    let mut delegations = Vec::new();
+    for _ in 0..MAX_DELEGATORS_PER_VOTE {
+        let delegator = funded_voter(&mut banks, &payer).await;
+        delegate(
+            &mut banks,
+            &mut delegations,
+            &delegator,
+            Some(1),
+            &voter.pubkey(),
+        )
+        .await;
+    }
+
+    let graph = DelegationGraph::new(&delegations, Some(1));
+    let delegators = graph.delegators_of(&voter.pubkey(), &HashSet::new());
+    let vote = delegated_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", &delegators);
+    send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+    assert_eq!(
+        votes(&mut banks, "Smooth").await,
+        MAX_DELEGATORS_PER_VOTE as u64 + 1
+    );
+}
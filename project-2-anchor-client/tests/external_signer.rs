@@ -0,0 +1,90 @@
+use solana_sdk::{
+    hash::Hash,
+    signature::{write_keypair_file, Keypair},
+    signer::Signer,
+    system_instruction,
+    transaction::Transaction,
+};
+use std::path::{Path, PathBuf};
+use voting_client::signer::{load_signer, SignerSource};
+
+// Write a fresh keypair for the mock signer to serve
+fn mock_signer_keypair(name: &str) -> (Keypair, PathBuf) {
+    let keypair = Keypair::new();
+    let path = std::env::temp_dir().join(format!("voting-client-{name}-{}.json", keypair.pubkey()));
+    write_keypair_file(&keypair, &path).expect("Failed to write keypair file");
+    (keypair, path)
+}
+
+// `cargo test` builds the mock-signer example next to the test binaries,
+// unless only some test targets are selected
+fn mock_signer_path() -> PathBuf {
+    let deps = std::env::current_exe().unwrap();
+    let path = deps
+        .parent()
+        .and_then(Path::parent)
+        .unwrap()
+        .join("examples")
+        .join(format!("mock-signer{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{} is missing; run `cargo build --example mock-signer` first",
+        path.display()
+    );
+    path
+}
+
+fn mock_signer_source(keypair_path: &Path) -> SignerSource {
+    format!(
+        "exec:{} {}",
+        mock_signer_path().display(),
+        keypair_path.display()
+    )
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn test_external_signer_signs_transactions() {
+    let (keypair, path) = mock_signer_keypair("sign");
+    let signer = load_signer(&mock_signer_source(&path)).expect("Failed to start mock signer");
+    assert_eq!(signer.pubkey(), keypair.pubkey());
+
+    let ix = system_instruction::transfer(&signer.pubkey(), &Keypair::new().pubkey(), 1);
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&signer.pubkey()),
+        &[&signer],
+        Hash::new_unique(),
+    );
+    tx.verify().expect("External signature does not verify");
+
+    // The external signer must produce exactly what the keypair itself would
+    let mut expected = tx.clone();
+    expected.sign(&[&keypair], tx.message.recent_blockhash);
+    assert_eq!(tx.signatures, expected.signatures);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_external_signer_reports_startup_failure() {
+    let missing = std::env::temp_dir().join("voting-client-missing-keypair.json");
+    assert!(load_signer(&mock_signer_source(&missing)).is_err());
+
+    let source: SignerSource = "exec:/nonexistent/signer".parse().unwrap();
+    assert!(load_signer(&source).is_err());
+}
+
+#[test]
+fn test_env_signer() {
+    let keypair = Keypair::new();
+    let name = format!("VOTING_CLIENT_TEST_SECRET_{}", keypair.pubkey());
+    std::env::set_var(&name, keypair.to_base58_string());
+
+    let signer = load_signer(&SignerSource::Env(name.clone())).unwrap();
+    assert_eq!(signer.pubkey(), keypair.pubkey());
+
+    std::env::remove_var(&name);
+    assert!(load_signer(&SignerSource::Env(name)).is_err());
+}
@@ -0,0 +1,309 @@
+// The results indexer, fed the log messages of transactions run in an
+// in-process bank as a validator's RPC would return them
+mod common;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use common::{fetch, funded_voter, program_test, set_time};
+use solana_program_test::BanksClient;
+use solana_sdk::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::Transaction,
+};
+use std::sync::{Mutex, Once};
+use voting::{Candidate, PollKind};
+use voting_client::{
+    delegation::{
+        delegated_vote_instruction, delegation_address, poll_delegations_address,
+        set_delegation_instruction,
+    },
+    indexer::{parse_events, Database},
+    tally,
+    vote::{
+        candidate_address, cast_ballot_instruction, change_vote_instruction,
+        finalize_poll_instruction, initialize_ballot_poll_instruction,
+        initialize_candidate_instruction, initialize_poll_instruction, vote_instruction,
+    },
+};
+
+// The bank runs the program natively, where `emit!` goes through the syscall
+// stubs instead of the bank's log collector. These stubs capture what the
+// program emits and otherwise defer to the bank's
+static EMITTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct CapturingStubs(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for CapturingStubs {
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| BASE64.encode(field)).collect();
+        let line = format!("Program data: {}", fields.join(" "));
+        EMITTED.lock().unwrap().push(line);
+    }
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        self.0
+            .sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        self.0
+            .sol_get_sysvar(sysvar_id_addr, var_addr, offset, length)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    fn sol_get_epoch_stake(&self, vote_address: *const u8) -> u64 {
+        self.0.sol_get_epoch_stake(vote_address)
+    }
+    unsafe fn sol_memcpy(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memcpy(dst, src, n)
+    }
+    unsafe fn sol_memmove(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memmove(dst, src, n)
+    }
+    unsafe fn sol_memcmp(&self, s1: *const u8, s2: *const u8, n: usize, result: *mut i32) {
+        self.0.sol_memcmp(s1, s2, n, result)
+    }
+    unsafe fn sol_memset(&self, s: *mut u8, c: u8, n: usize) {
+        self.0.sol_memset(s, c, n)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+// Call after the bank has started and installed its own stubs
+fn capture_events() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let bank_stubs = set_syscall_stubs(Box::new(NoStubs));
+        set_syscall_stubs(Box::new(CapturingStubs(bank_stubs)));
+    });
+}
+
+struct NoStubs;
+impl SyscallStubs for NoStubs {}
+
+// Run a transaction and index it, returning whether it succeeded. The events
+// captured while it ran go into its logs where a validator would log them,
+// inside the program's invocation
+async fn index(
+    banks: &mut BanksClient,
+    db: &mut Database,
+    instructions: &[Instruction],
+    signer: &Keypair,
+) -> bool {
+    let blockhash = banks.get_latest_blockhash().await.unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&signer.pubkey()),
+        &[signer],
+        blockhash,
+    );
+    let signature = tx.signatures[0].to_string();
+    EMITTED.lock().unwrap().clear();
+    let result = banks.process_transaction_with_metadata(tx).await.unwrap();
+    let emitted = std::mem::take(&mut *EMITTED.lock().unwrap());
+    let slot = banks.get_root_slot().await.unwrap();
+    let events = match (&result.result, result.metadata) {
+        (Ok(()), Some(metadata)) => {
+            let mut logs = metadata.log_messages;
+            let invoked = format!("Program {} invoke [1]", voting::ID);
+            let at = logs.iter().position(|line| *line == invoked).unwrap() + 1;
+            logs.splice(at..at, emitted);
+            parse_events(&voting::ID, &logs)
+        }
+        _ => Vec::new(),
+    };
+    db.index_transaction(&signature, slot, None, &events)
+        .unwrap();
+    result.result.is_ok()
+}
+
+#[tokio::test]
+async fn test_indexer_rebuilds_results_from_events() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    capture_events();
+    let mut db = Database::open_in_memory().unwrap();
+    let clock: Clock = banks.get_sysvar().await.unwrap();
+    let poll_end = clock.unix_timestamp as u64 + 100;
+
+    let mut instructions = vec![initialize_poll_instruction(
+        &voting::ID,
+        &payer.pubkey(),
+        1,
+        "What is the best peanut butter?",
+        0,
+        poll_end,
+    )];
+    for candidate in ["Smooth", "Crunchy"] {
+        instructions.push(initialize_candidate_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            candidate,
+        ));
+    }
+    instructions.push(initialize_ballot_poll_instruction(
+        &voting::ID,
+        &payer.pubkey(),
+        2,
+        "Stock which spreads?",
+        0,
+        poll_end,
+        PollKind::RankedChoice,
+    ));
+    for candidate in ["Almond", "Cashew", "Hazelnut"] {
+        instructions.push(initialize_candidate_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            2,
+            candidate,
+        ));
+    }
+    assert!(index(&mut banks, &mut db, &instructions, &payer).await);
+
+    let [a, b, c] = [
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+        funded_voter(&mut banks, &payer).await,
+    ];
+    let vote = vote_instruction(&voting::ID, &a.pubkey(), 1, "Smooth");
+    assert!(index(&mut banks, &mut db, &[vote], &a).await);
+    // A second vote fails and leaves no trace
+    let vote = vote_instruction(&voting::ID, &a.pubkey(), 1, "Crunchy");
+    assert!(!index(&mut banks, &mut db, &[vote], &a).await);
+
+    // c doesn't delegate, so the chain ends at c's (empty) global delegation
+    let chain = [
+        delegation_address(&voting::ID, None, &c.pubkey()),
+        poll_delegations_address(&voting::ID, &c.pubkey()),
+    ];
+    let delegate = set_delegation_instruction(&voting::ID, &b.pubkey(), None, &c.pubkey(), &chain);
+    let vote = delegated_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", &[b.pubkey()]);
+    assert!(index(&mut banks, &mut db, &[delegate], &b).await);
+    assert!(index(&mut banks, &mut db, &[vote], &c).await);
+    let change = change_vote_instruction(&voting::ID, &c.pubkey(), 1, "Smooth", "Crunchy");
+    assert!(index(&mut banks, &mut db, &[change], &c).await);
+
+    let ballots = [vec![2u8, 0], vec![0], vec![1, 0]];
+    for (voter, rankings) in [&a, &b, &c].into_iter().zip(&ballots) {
+        let ballot = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 2, rankings);
+        assert!(index(&mut banks, &mut db, &[ballot], voter).await);
+    }
+
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let finalize = finalize_poll_instruction(
+        &voting::ID,
+        1,
+        PollKind::SingleChoice,
+        &["Smooth", "Crunchy"],
+    );
+    assert!(index(&mut banks, &mut db, &[finalize], &payer).await);
+
+    // Candidate counts match the program's
+    let candidates = db.candidates(1).unwrap();
+    assert_eq!(candidates.len(), 2);
+    for candidate in &candidates {
+        let account: Candidate = fetch(
+            &mut banks,
+            candidate_address(&voting::ID, 1, &candidate.name),
+        )
+        .await;
+        assert_eq!(candidate.votes, account.candidate_votes);
+    }
+    assert_eq!(candidates[1].name, "Crunchy");
+    assert_eq!(candidates[1].votes, 2);
+
+    let polls = db.polls().unwrap();
+    assert_eq!(polls.len(), 2);
+    assert_eq!(polls[0].voters, 3);
+    assert!(polls[0].finalized);
+    assert_eq!(polls[0].winner.as_deref(), Some("Crunchy"));
+    assert_eq!(polls[1].kind, PollKind::RankedChoice);
+    assert!(!polls[1].finalized);
+
+    // b was counted through c's vote and followed it to Crunchy
+    let voters = db.voters(1).unwrap();
+    let b_row = voters
+        .iter()
+        .find(|voter| voter.voter == b.pubkey().to_string())
+        .unwrap();
+    assert_eq!(b_row.candidate.as_deref(), Some("Crunchy"));
+    assert_eq!(b_row.delegate, Some(c.pubkey().to_string()));
+
+    assert_eq!(
+        db.tally(2).unwrap(),
+        Some(tally::tally(PollKind::RankedChoice, 3, &ballots))
+    );
+    assert_eq!(db.tally(1).unwrap(), None);
+
+    let history = db.history(&c.pubkey()).unwrap();
+    let actions: Vec<&str> = history.iter().map(|vote| vote.action.as_str()).collect();
+    assert_eq!(actions, ["vote", "change", "ballot"]);
+    assert_eq!(history[0].weight, 2);
+    assert_eq!(history[1].previous.as_deref(), Some("Smooth"));
+}
+
+#[tokio::test]
+async fn test_transactions_are_indexed_once() {
+    let mut db = Database::open_in_memory().unwrap();
+    assert_eq!(db.last_signature().unwrap(), None);
+    db.index_transaction("first", 1, Some(10), &[]).unwrap();
+    db.index_transaction("second", 2, None, &[]).unwrap();
+
+    assert!(db.is_indexed("first").unwrap());
+    assert_eq!(db.last_signature().unwrap().as_deref(), Some("second"));
+    assert!(db.index_transaction("first", 1, Some(10), &[]).is_err());
+}
@@ -0,0 +1,285 @@
+// Poll lifecycle: the voting window, finalization and closing accounts for
+// their rent, run in an in-process bank
+mod common;
+
+use common::{fetch, funded_voter, program_test, resend, send, set_time};
+use solana_program_test::BanksClient;
+use solana_sdk::{clock::Clock, pubkey::Pubkey, signer::Signer};
+use voting::{Poll, PollKind};
+use voting_client::vote::{
+    ballot_address, candidate_address, cast_ballot_instruction, change_vote_instruction,
+    close_ballot_instruction, close_candidate_instruction, close_receipt_instruction,
+    finalize_poll_instruction, initialize_ballot_poll_instruction,
+    initialize_candidate_instruction, initialize_poll_instruction,
+    initialize_sealed_poll_instruction, poll_address, tally_ballots_instruction, vote_instruction,
+    voter_receipt_address,
+};
+
+const FEE: u64 = 5_000;
+
+async fn lamports(banks: &mut BanksClient, address: Pubkey) -> u64 {
+    banks
+        .get_account(address)
+        .await
+        .unwrap()
+        .map_or(0, |account| account.lamports)
+}
+
+async fn now(banks: &mut BanksClient) -> u64 {
+    let clock: Clock = banks.get_sysvar().await.unwrap();
+    clock.unix_timestamp as u64
+}
+
+#[tokio::test]
+async fn test_votes_only_count_while_poll_is_open() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let start = now(&mut banks).await + 100;
+    let instructions = [
+        initialize_poll_instruction(&voting::ID, &payer.pubkey(), 1, "Poll", start, start + 100),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Crunchy"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    let early = funded_voter(&mut banks, &payer).await;
+    let vote = vote_instruction(&voting::ID, &early.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[vote], &early, &[&early]).await.is_err());
+
+    set_time(&mut context, start as i64 + 50).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+    let vote = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+
+    set_time(&mut context, start as i64 + 101).await;
+    let late = funded_voter(&mut banks, &payer).await;
+    let vote = vote_instruction(&voting::ID, &late.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[vote], &late, &[&late]).await.is_err());
+    let change = change_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", "Crunchy");
+    assert!(send(&mut banks, &[change], &voter, &[&voter])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_only_the_poll_authority_adds_candidates() {
+    let (mut banks, payer) = common::start().await;
+    let start = now(&mut banks).await;
+    let create =
+        initialize_poll_instruction(&voting::ID, &payer.pubkey(), 1, "Poll", start, start + 100);
+    send(&mut banks, &[create], &payer, &[&payer])
+        .await
+        .unwrap();
+
+    // Anyone else padding the poll with candidates would keep it from ever
+    // being finalized
+    let stranger = funded_voter(&mut banks, &payer).await;
+    let junk = initialize_candidate_instruction(&voting::ID, &stranger.pubkey(), 1, "Junk");
+    assert!(send(&mut banks, &[junk], &stranger, &[&stranger])
+        .await
+        .is_err());
+
+    let add = initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth");
+    send(&mut banks, &[add], &payer, &[&payer]).await.unwrap();
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert_eq!(poll.candidate_amount, 1);
+}
+
+#[tokio::test]
+async fn test_finalize_writes_winner_and_close_reclaims_rent() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let poll_end = now(&mut banks).await + 100;
+    let candidates = ["Smooth", "Crunchy", "Almond"];
+    let mut instructions = vec![initialize_poll_instruction(
+        &voting::ID,
+        &payer.pubkey(),
+        1,
+        "What is the best peanut butter?",
+        0,
+        poll_end,
+    )];
+    instructions.extend(candidates.iter().map(|candidate| {
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, candidate)
+    }));
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    let mut voters = Vec::new();
+    for candidate in ["Crunchy", "Smooth", "Crunchy"] {
+        let voter = funded_voter(&mut banks, &payer).await;
+        let vote = vote_instruction(&voting::ID, &voter.pubkey(), 1, candidate);
+        send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+        voters.push(voter);
+    }
+
+    let finalize = finalize_poll_instruction(&voting::ID, 1, PollKind::SingleChoice, &candidates);
+    assert!(send(
+        &mut banks,
+        std::slice::from_ref(&finalize),
+        &payer,
+        &[&payer]
+    )
+    .await
+    .is_err());
+
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let voter = &voters[0];
+    let close_receipt = close_receipt_instruction(&voting::ID, &voter.pubkey(), 1);
+    assert!(send(
+        &mut banks,
+        std::slice::from_ref(&close_receipt),
+        voter,
+        &[voter]
+    )
+    .await
+    .is_err());
+
+    // Every candidate must be counted, each once
+    for partial in [&candidates[..2], &["Smooth", "Smooth", "Crunchy"]] {
+        let partial = finalize_poll_instruction(&voting::ID, 1, PollKind::SingleChoice, partial);
+        assert!(send(&mut banks, &[partial], &payer, &[&payer])
+            .await
+            .is_err());
+    }
+    resend(&mut banks, &[finalize], &payer, &[&payer])
+        .await
+        .unwrap();
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert!(poll.finalized);
+    assert_eq!(poll.winner.as_deref(), Some("Crunchy"));
+
+    let again = finalize_poll_instruction(&voting::ID, 1, PollKind::SingleChoice, &candidates);
+    assert!(resend(&mut banks, &[again], &payer, &[&payer])
+        .await
+        .is_err());
+
+    // Only the poll's creator reclaims the candidates
+    let stranger = close_candidate_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[stranger], voter, &[voter])
+        .await
+        .is_err());
+    let candidate = candidate_address(&voting::ID, 1, "Smooth");
+    let rent = lamports(&mut banks, candidate).await;
+    let before = lamports(&mut banks, payer.pubkey()).await;
+    let close = close_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth");
+    send(&mut banks, &[close], &payer, &[&payer]).await.unwrap();
+    assert_eq!(lamports(&mut banks, candidate).await, 0);
+    assert_eq!(
+        lamports(&mut banks, payer.pubkey()).await,
+        before + rent - FEE
+    );
+
+    let receipt = voter_receipt_address(&voting::ID, 1, &voter.pubkey());
+    let rent = lamports(&mut banks, receipt).await;
+    let before = lamports(&mut banks, voter.pubkey()).await;
+    resend(&mut banks, &[close_receipt], voter, &[voter])
+        .await
+        .unwrap();
+    assert_eq!(lamports(&mut banks, receipt).await, 0);
+    assert_eq!(
+        lamports(&mut banks, voter.pubkey()).await,
+        before + rent - FEE
+    );
+}
+
+#[tokio::test]
+async fn test_finalize_ballot_poll_after_tally() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let poll_end = now(&mut banks).await + 100;
+    let instructions = [
+        initialize_ballot_poll_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            "Poll",
+            0,
+            poll_end,
+            PollKind::RankedChoice,
+        ),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Crunchy"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+    let voter = funded_voter(&mut banks, &payer).await;
+    let ballot = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 1, &[1, 0]);
+    send(&mut banks, &[ballot], &voter, &[&voter])
+        .await
+        .unwrap();
+    set_time(&mut context, poll_end as i64 + 1).await;
+
+    // The tally has to complete first
+    let candidates = ["Smooth", "Crunchy"];
+    let finalize = finalize_poll_instruction(&voting::ID, 1, PollKind::RankedChoice, &candidates);
+    assert!(send(
+        &mut banks,
+        std::slice::from_ref(&finalize),
+        &payer,
+        &[&payer]
+    )
+    .await
+    .is_err());
+    let ballots = [ballot_address(&voting::ID, 1, &voter.pubkey())];
+    let tally = tally_ballots_instruction(&voting::ID, 1, &ballots);
+    send(&mut banks, &[tally], &payer, &[&payer]).await.unwrap();
+    resend(&mut banks, &[finalize], &payer, &[&payer])
+        .await
+        .unwrap();
+
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert_eq!(poll.winner.as_deref(), Some("Crunchy"));
+    let close = close_ballot_instruction(&voting::ID, &voter.pubkey(), 1);
+    send(&mut banks, &[close], &voter, &[&voter]).await.unwrap();
+    assert_eq!(lamports(&mut banks, ballots[0]).await, 0);
+}
+
+#[tokio::test]
+async fn test_sealed_poll_finalizes_after_reveal_window() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let poll_end = now(&mut banks).await + 100;
+    let instructions = [
+        initialize_sealed_poll_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            "Poll",
+            0,
+            poll_end,
+            poll_end + 100,
+        ),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let finalize = finalize_poll_instruction(&voting::ID, 1, PollKind::SingleChoice, &["Smooth"]);
+    assert!(send(
+        &mut banks,
+        std::slice::from_ref(&finalize),
+        &payer,
+        &[&payer]
+    )
+    .await
+    .is_err());
+
+    set_time(&mut context, poll_end as i64 + 101).await;
+    resend(&mut banks, &[finalize], &payer, &[&payer])
+        .await
+        .unwrap();
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert!(poll.finalized);
+    assert_eq!(poll.winner, None);
+}
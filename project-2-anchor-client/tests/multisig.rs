@@ -0,0 +1,215 @@
+// Voting through a Squads v4 multisig proposal, run in an in-process bank
+// against the multisig program's binary, dumped into tests/fixtures with
+// `anchor run fixtures` (see the README).
+mod common;
+
+use common::{create_poll, fetch, program_test, resend, send};
+use solana_program_test::ProgramTest;
+use solana_sdk::{
+    account::Account, bpf_loader, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, rent::Rent,
+    signature::Keypair, signer::Signer, system_instruction, system_program,
+};
+use std::str::FromStr;
+use voting::{Candidate, VoterReceipt};
+use voting_client::{
+    multisig::{
+        permissions, program_config_address, transaction_address, Member, Multisig, MultisigState,
+        ProgramConfigState, VaultTransactionMessage, VaultTransactionState, SQUADS_PROGRAM_ID,
+    },
+    vote::{candidate_address, vote_instruction, voter_receipt_address},
+};
+
+const CREATION_FEE: u64 = LAMPORTS_PER_SOL / 10;
+
+const PROGRAM: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/fixtures/squads_multisig_program.so"
+);
+
+// Anchor's discriminator of the program's `ProgramConfig` account
+const PROGRAM_CONFIG_DISCRIMINATOR: [u8; 8] = [196, 210, 90, 231, 144, 149, 140, 63];
+
+// Add the multisig program and the program config account it reads on
+// creation, which charges `creation_fee` into `treasury`
+fn add_squads(
+    program_test: &mut ProgramTest,
+    program_id: Pubkey,
+    treasury: Pubkey,
+    creation_fee: u64,
+) {
+    let elf = std::fs::read(PROGRAM).unwrap_or_else(|e| {
+        panic!(
+            "Error reading the multisig program from {PROGRAM} ({e}); \
+             dump it with `anchor run fixtures` in project-2-anchor-client"
+        )
+    });
+    program_test.add_account(
+        program_id,
+        Account {
+            lamports: Rent::default().minimum_balance(elf.len()),
+            data: elf,
+            owner: bpf_loader::ID,
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+
+    // authority, multisig_creation_fee, treasury and 64 reserved bytes
+    let mut data = PROGRAM_CONFIG_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(&creation_fee.to_le_bytes());
+    data.extend_from_slice(treasury.as_ref());
+    data.extend_from_slice(&[0; 64]);
+    program_test.add_account(
+        program_config_address(&program_id),
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    program_test.add_account(
+        treasury,
+        Account {
+            lamports: Rent::default().minimum_balance(0),
+            owner: system_program::ID,
+            ..Account::default()
+        },
+    );
+}
+
+#[tokio::test]
+async fn test_vote_through_multisig_proposal() {
+    let squads = Pubkey::from_str(SQUADS_PROGRAM_ID).unwrap();
+    let mut program_test = program_test();
+    add_squads(
+        &mut program_test,
+        squads,
+        Pubkey::new_unique(),
+        CREATION_FEE,
+    );
+    let (mut banks, payer, _) = program_test.start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth"]).await;
+
+    let [alice, bob, eve] = [(); 3].map(|_| Keypair::new());
+    let fund = [&alice, &bob, &eve].map(|member| {
+        system_instruction::transfer(&payer.pubkey(), &member.pubkey(), LAMPORTS_PER_SOL)
+    });
+    send(&mut banks, &fund, &payer, &[&payer]).await.unwrap();
+
+    // A 2-of-2 multisig of Alice and Bob, paying the program's creation fee
+    let config = banks
+        .get_account(program_config_address(&squads))
+        .await
+        .unwrap()
+        .expect("Multisig program config is not deployed");
+    let treasury = ProgramConfigState::from_account_data(&config.data)
+        .unwrap()
+        .treasury;
+    let treasury_before = banks.get_balance(treasury).await.unwrap();
+    let create_key = Keypair::new();
+    let members = [&alice, &bob].map(|member| Member {
+        key: member.pubkey(),
+        permissions: permissions::ALL,
+    });
+    let (multisig, create_ix) = Multisig::create(
+        squads,
+        &create_key.pubkey(),
+        &alice.pubkey(),
+        &treasury,
+        members.to_vec(),
+        2,
+    );
+    let vault = multisig.vault(0);
+    let fund_vault = system_instruction::transfer(&alice.pubkey(), &vault, LAMPORTS_PER_SOL / 10);
+    send(
+        &mut banks,
+        &[create_ix, fund_vault],
+        &alice,
+        &[&alice, &create_key],
+    )
+    .await
+    .expect("Failed to create multisig");
+    assert_eq!(
+        banks.get_balance(treasury).await.unwrap(),
+        treasury_before + CREATION_FEE
+    );
+
+    // Alice proposes the vault's vote
+    let account = banks.get_account(multisig.address).await.unwrap().unwrap();
+    let state = MultisigState::from_account_data(&account.data).unwrap();
+    assert_eq!(state.threshold, 2);
+    let transaction_index = state.transaction_index + 1;
+    let vote = vote_instruction(&voting::ID, &vault, 1, "Smooth");
+    let message = VaultTransactionMessage::compile(&vault, &[vote]).unwrap();
+    let propose = [
+        multisig
+            .create_transaction(&alice.pubkey(), transaction_index, 0, &message)
+            .unwrap(),
+        multisig.create_proposal(&alice.pubkey(), transaction_index),
+    ];
+    send(&mut banks, &propose, &alice, &[&alice])
+        .await
+        .expect("Failed to create proposal");
+
+    let account = banks
+        .get_account(transaction_address(
+            &squads,
+            &multisig.address,
+            transaction_index,
+        ))
+        .await
+        .unwrap()
+        .unwrap();
+    let stored = VaultTransactionState::from_account_data(&account.data).unwrap();
+    assert_eq!(stored.message, message);
+
+    // Executing before the threshold is met must fail
+    let execute = [multisig
+        .execute(&bob.pubkey(), transaction_index, &message)
+        .unwrap()];
+    assert!(send(&mut banks, &execute, &bob, &[&bob]).await.is_err());
+
+    // Only members may approve
+    assert!(send(
+        &mut banks,
+        &[multisig.approve(&eve.pubkey(), transaction_index)],
+        &eve,
+        &[&eve],
+    )
+    .await
+    .is_err());
+
+    send(
+        &mut banks,
+        &[multisig.approve(&alice.pubkey(), transaction_index)],
+        &alice,
+        &[&alice],
+    )
+    .await
+    .expect("Alice failed to approve");
+    send(
+        &mut banks,
+        &[multisig.approve(&bob.pubkey(), transaction_index)],
+        &bob,
+        &[&bob],
+    )
+    .await
+    .expect("Bob failed to approve");
+
+    resend(&mut banks, &execute, &bob, &[&bob])
+        .await
+        .expect("Failed to execute the vote proposal");
+
+    let smooth: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Smooth")).await;
+    assert_eq!(smooth.candidate_votes, 1);
+    let receipt: VoterReceipt =
+        fetch(&mut banks, voter_receipt_address(&voting::ID, 1, &vault)).await;
+    assert_eq!(receipt.voter, vault);
+    assert_eq!(receipt.candidate_name, "Smooth");
+
+    // An executed proposal can't be executed again
+    assert!(resend(&mut banks, &execute, &bob, &[&bob]).await.is_err());
+}
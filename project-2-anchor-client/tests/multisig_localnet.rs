@@ -0,0 +1,181 @@
+// End-to-end multisig voting against a local validator.
+//
+// Needs a validator on 127.0.0.1:8899 with the voting program, the multisig
+// program and its program config account, as `anchor test` starts it (see
+// Anchor.toml and the README).
+use accelerate_client::{nonblocking, Backoff, ClusterConfig};
+use solana_sdk::{
+    native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    system_instruction,
+};
+use std::{rc::Rc, str::FromStr};
+use voting::Candidate;
+use voting_client::{
+    multisig::{
+        permissions, program_config_address, Member, Multisig, MultisigState, ProgramConfigState,
+        VaultTransactionMessage, VaultTransactionState, SQUADS_PROGRAM_ID,
+    },
+    vote::{
+        candidate_address, initialize_candidate_instruction, initialize_poll_instruction,
+        vote_instruction,
+    },
+    VOTING_PROGRAM_ID,
+};
+
+#[tokio::test]
+#[ignore = "requires a local validator with the multisig and voting programs; run with `anchor test`"]
+async fn test_vote_through_multisig_proposal() {
+    let squads = Pubkey::from_str(SQUADS_PROGRAM_ID).unwrap();
+    let voting = Pubkey::from_str(VOTING_PROGRAM_ID).unwrap();
+    let alice = Rc::new(Keypair::new());
+    let bob = Rc::new(Keypair::new());
+
+    let config = ClusterConfig::localnet();
+    let alice_client = config.client(alice.clone());
+    let bob_client = config.client(bob.clone());
+    let as_alice = alice_client.program(voting).unwrap();
+    let as_bob = bob_client.program(voting).unwrap();
+    let rpc = as_alice.rpc();
+
+    for member in [&alice, &bob] {
+        nonblocking::airdrop(
+            &rpc,
+            &member.pubkey(),
+            2 * LAMPORTS_PER_SOL,
+            config.commitment,
+            &Backoff::default(),
+        )
+        .await
+        .expect("Failed to airdrop SOL");
+    }
+
+    // A poll of its own, so the test can run against the same validator again
+    let poll_id = rand::random::<u64>();
+    as_alice
+        .request()
+        .instruction(initialize_poll_instruction(
+            &voting,
+            &alice.pubkey(),
+            poll_id,
+            "What is the best peanut butter?",
+            0,
+            u64::MAX,
+        ))
+        .instruction(initialize_candidate_instruction(
+            &voting,
+            &alice.pubkey(),
+            poll_id,
+            "Smooth",
+        ))
+        .send()
+        .await
+        .expect("Failed to create poll");
+
+    // A 2-of-2 multisig of Alice and Bob
+    let config_data = rpc
+        .get_account_data(&program_config_address(&squads))
+        .await
+        .expect("Multisig program config is not deployed");
+    let treasury = ProgramConfigState::from_account_data(&config_data)
+        .unwrap()
+        .treasury;
+    let create_key = Keypair::new();
+    let members = [&alice, &bob].map(|member| Member {
+        key: member.pubkey(),
+        permissions: permissions::ALL,
+    });
+    let (multisig, create_ix) = Multisig::create(
+        squads,
+        &create_key.pubkey(),
+        &alice.pubkey(),
+        &treasury,
+        members.to_vec(),
+        2,
+    );
+    let vault = multisig.vault(0);
+    as_alice
+        .request()
+        .instruction(create_ix)
+        .instruction(system_instruction::transfer(
+            &alice.pubkey(),
+            &vault,
+            LAMPORTS_PER_SOL / 10,
+        ))
+        .signer(create_key)
+        .send()
+        .await
+        .expect("Failed to create multisig");
+
+    // Alice proposes the vault's vote
+    let state =
+        MultisigState::from_account_data(&rpc.get_account_data(&multisig.address).await.unwrap())
+            .unwrap();
+    let transaction_index = state.transaction_index + 1;
+    let vote = vote_instruction(&voting, &vault, poll_id, "Smooth");
+    let message = VaultTransactionMessage::compile(&vault, &[vote]).unwrap();
+    as_alice
+        .request()
+        .instruction(
+            multisig
+                .create_transaction(&alice.pubkey(), transaction_index, 0, &message)
+                .unwrap(),
+        )
+        .instruction(multisig.create_proposal(&alice.pubkey(), transaction_index))
+        .send()
+        .await
+        .expect("Failed to create proposal");
+
+    let stored = VaultTransactionState::from_account_data(
+        &rpc.get_account_data(&voting_client::multisig::transaction_address(
+            &squads,
+            &multisig.address,
+            transaction_index,
+        ))
+        .await
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(stored.message, message);
+
+    // Executing before the threshold is met must fail
+    assert!(as_alice
+        .request()
+        .instruction(
+            multisig
+                .execute(&alice.pubkey(), transaction_index, &message)
+                .unwrap()
+        )
+        .send()
+        .await
+        .is_err());
+
+    as_alice
+        .request()
+        .instruction(multisig.approve(&alice.pubkey(), transaction_index))
+        .send()
+        .await
+        .expect("Alice failed to approve");
+    as_bob
+        .request()
+        .instruction(multisig.approve(&bob.pubkey(), transaction_index))
+        .send()
+        .await
+        .expect("Bob failed to approve");
+
+    as_bob
+        .request()
+        .instruction(
+            multisig
+                .execute(&bob.pubkey(), transaction_index, &message)
+                .unwrap(),
+        )
+        .send()
+        .await
+        .expect("Failed to execute the vote proposal");
+
+    let smooth: Candidate = as_alice
+        .account(candidate_address(&voting, poll_id, "Smooth"))
+        .await
+        .unwrap();
+    assert_eq!(smooth.candidate_votes, 1);
+}
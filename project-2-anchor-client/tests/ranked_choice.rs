@@ -0,0 +1,214 @@
+// Approval and ranked-choice polls: ballots cast in an in-process bank and
+// counted by cranking `tally_ballots`, checked against the client's
+// off-chain tally
+mod common;
+
+use common::{fetch, funded_voter, program_test, send, set_time};
+use proptest::{collection::vec, prelude::*, sample::subsequence};
+use solana_program_test::{BanksClient, ProgramTestContext};
+use solana_sdk::{
+    clock::Clock, compute_budget::ComputeBudgetInstruction, signature::Keypair, signer::Signer,
+};
+use voting::{Ballot, PollKind, Tally};
+use voting_client::{
+    tally::{self, TallyResult},
+    vote::{
+        ballot_address, cast_ballot_instruction, initialize_ballot_poll_instruction,
+        initialize_candidate_instruction, tally_address, tally_ballots_instruction,
+        vote_instruction,
+    },
+};
+
+const CANDIDATES: [&str; 5] = ["Smooth", "Crunchy", "Almond", "Cashew", "Hazelnut"];
+
+// Create a poll of `kind` with the first `candidates` names, open for 100 seconds;
+// returns when it closes
+async fn create_ballot_poll(
+    context: &mut ProgramTestContext,
+    kind: PollKind,
+    candidates: usize,
+) -> u64 {
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let clock: Clock = banks.get_sysvar().await.unwrap();
+    let poll_end = clock.unix_timestamp as u64 + 100;
+
+    let mut instructions = vec![initialize_ballot_poll_instruction(
+        &voting::ID,
+        &payer.pubkey(),
+        1,
+        "Which spreads should the cafe stock?",
+        0,
+        poll_end,
+        kind,
+    )];
+    instructions.extend(CANDIDATES[..candidates].iter().map(|candidate| {
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, candidate)
+    }));
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+    poll_end
+}
+
+async fn cast(banks: &mut BanksClient, payer: &Keypair, rankings: &[u8]) -> Keypair {
+    let voter = funded_voter(banks, payer).await;
+    let ballot = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 1, rankings);
+    send(banks, &[ballot], &voter, &[&voter]).await.unwrap();
+    voter
+}
+
+// Crank `tally_ballots` a few ballots at a time until the count completes
+async fn crank(banks: &mut BanksClient, payer: &Keypair, voters: &[Keypair]) -> Tally {
+    let ballots: Vec<_> = voters
+        .iter()
+        .map(|voter| ballot_address(&voting::ID, 1, &voter.pubkey()))
+        .collect();
+    loop {
+        let tally: Tally = fetch(banks, tally_address(&voting::ID, 1)).await;
+        if tally.complete {
+            return tally;
+        }
+        let mut pending = Vec::new();
+        for &address in &ballots {
+            let ballot: Ballot = fetch(banks, address).await;
+            if ballot.counted_round != tally.round {
+                pending.push(address);
+            }
+        }
+        let chunks: Vec<&[_]> = if pending.is_empty() {
+            vec![&[]]
+        } else {
+            pending.chunks(3).collect()
+        };
+        for chunk in chunks {
+            // Rounds send the same ballots; the compute limit keeps each
+            // round's transactions distinct under the same blockhash
+            let instructions = [
+                ComputeBudgetInstruction::set_compute_unit_limit(400_000 + tally.round as u32),
+                tally_ballots_instruction(&voting::ID, 1, chunk),
+            ];
+            send(banks, &instructions, payer, &[payer]).await.unwrap();
+        }
+    }
+}
+
+async fn tally_on_chain(kind: PollKind, candidates: usize, ballots: &[Vec<u8>]) -> Tally {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let poll_end = create_ballot_poll(&mut context, kind, candidates).await;
+
+    let mut voters = Vec::new();
+    for rankings in ballots {
+        voters.push(cast(&mut banks, &payer, rankings).await);
+    }
+    set_time(&mut context, poll_end as i64 + 1).await;
+    crank(&mut banks, &payer, &voters).await
+}
+
+fn assert_same_tally(on_chain: &Tally, off_chain: &TallyResult, candidates: usize) {
+    assert_eq!(on_chain.winner, off_chain.winner);
+    assert_eq!(on_chain.round as usize, off_chain.rounds.len());
+    let last = off_chain.rounds.last().unwrap();
+    assert_eq!(on_chain.counts[..candidates], last.counts[..]);
+    for round in &off_chain.rounds {
+        if let Some(eliminated) = round.eliminated {
+            assert!(on_chain.is_eliminated(eliminated));
+        }
+    }
+}
+
+fn run(future: impl std::future::Future<Output = Tally>) -> Tally {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+// A ranking of some of `candidates`, most preferred first
+fn ranking(candidates: usize) -> impl Strategy<Value = Vec<u8>> {
+    Just((0..candidates as u8).collect::<Vec<_>>())
+        .prop_shuffle()
+        .prop_flat_map(|order| {
+            let len = order.len();
+            (1..=len).prop_map(move |ranked| order[..ranked].to_vec())
+        })
+}
+
+fn approvals(candidates: usize) -> impl Strategy<Value = Vec<u8>> {
+    subsequence((0..candidates as u8).collect::<Vec<_>>(), 1..=candidates)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn ranked_choice_crank_matches_off_chain_tally(
+        (candidates, ballots) in (2..=5usize).prop_flat_map(|n| (Just(n), vec(ranking(n), 0..=12)))
+    ) {
+        let on_chain = run(tally_on_chain(PollKind::RankedChoice, candidates, &ballots));
+        let off_chain = tally::instant_runoff(candidates, &ballots);
+        assert_same_tally(&on_chain, &off_chain, candidates);
+    }
+
+    #[test]
+    fn approval_crank_matches_off_chain_tally(
+        (candidates, ballots) in (2..=5usize).prop_flat_map(|n| (Just(n), vec(approvals(n), 0..=12)))
+    ) {
+        let on_chain = run(tally_on_chain(PollKind::Approval, candidates, &ballots));
+        let off_chain = tally::approval(candidates, &ballots);
+        assert_same_tally(&on_chain, &off_chain, candidates);
+    }
+}
+
+#[tokio::test]
+async fn test_ballot_validation() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let poll_end = create_ballot_poll(&mut context, PollKind::RankedChoice, 3).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+
+    // Single-choice votes don't apply
+    let single = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[single], &voter, &[&voter])
+        .await
+        .is_err());
+
+    for rankings in [vec![], vec![0, 3], vec![1, 0, 1]] {
+        let invalid = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 1, &rankings);
+        assert!(send(&mut banks, &[invalid], &voter, &[&voter])
+            .await
+            .is_err());
+    }
+    let ballot = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 1, &[2, 0]);
+    send(&mut banks, &[ballot], &voter, &[&voter])
+        .await
+        .unwrap();
+    let again = cast_ballot_instruction(&voting::ID, &voter.pubkey(), 1, &[1]);
+    assert!(send(&mut banks, &[again], &voter, &[&voter]).await.is_err());
+
+    // The candidate list is fixed once ballots refer to it by index
+    let late = initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Hazelnut");
+    assert!(send(&mut banks, &[late], &payer, &[&payer]).await.is_err());
+
+    // Counting waits for the poll to close, which also closes it to ballots
+    let ballots = [ballot_address(&voting::ID, 1, &voter.pubkey())];
+    let early = tally_ballots_instruction(&voting::ID, 1, &ballots);
+    assert!(send(&mut banks, &[early], &payer, &[&payer]).await.is_err());
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let latecomer = funded_voter(&mut banks, &payer).await;
+    let closed = cast_ballot_instruction(&voting::ID, &latecomer.pubkey(), 1, &[0]);
+    assert!(send(&mut banks, &[closed], &latecomer, &[&latecomer])
+        .await
+        .is_err());
+
+    let tally = crank(&mut banks, &payer, &[voter]).await;
+    assert_eq!(tally.winner, Some(2));
+    let finished = tally_ballots_instruction(&voting::ID, 1, &ballots);
+    assert!(send(&mut banks, &[finished], &payer, &[&payer])
+        .await
+        .is_err());
+}
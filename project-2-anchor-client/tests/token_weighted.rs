@@ -0,0 +1,278 @@
+// Token-weighted polls with SPL Token and Token-2022 governance mints, run in
+// an in-process bank
+mod common;
+
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account,
+    token::spl_token,
+    token_2022::spl_token_2022::{self, instruction as token_instruction},
+    token_interface::TokenAccount,
+};
+use common::{fetch, funded_voter, program_test, resend, send, set_time};
+use solana_program_test::BanksClient;
+use solana_sdk::{
+    clock::Clock, program_pack::Pack, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    system_instruction,
+};
+use voting::{Candidate, Poll, PollKind, VoterReceipt};
+use voting_client::vote::{
+    candidate_address, change_vote_instruction, close_receipt_instruction, escrow_address,
+    finalize_poll_instruction, initialize_candidate_instruction, initialize_token_poll_instruction,
+    poll_address, vote_instruction, vote_weighted_instruction, voter_receipt_address,
+    withdraw_escrow_instruction, GovernanceTokens,
+};
+
+const DECIMALS: u8 = 6;
+
+async fn create_mint(banks: &mut BanksClient, payer: &Keypair, token_program: Pubkey) -> Pubkey {
+    let mint = Keypair::new();
+    // Neither program needs extensions here, so both use the base mint size
+    let space = spl_token::state::Mint::LEN;
+    let rent = banks.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            &token_program,
+        ),
+        token_instruction::initialize_mint2(
+            &token_program,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+    ];
+    send(banks, &instructions, payer, &[payer, &mint])
+        .await
+        .unwrap();
+    mint.pubkey()
+}
+
+// A funded wallet holding `amount` governance tokens in its associated token account
+async fn token_holder(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    tokens: &GovernanceTokens,
+    amount: u64,
+) -> Keypair {
+    let holder = funded_voter(banks, payer).await;
+    let instructions = [
+        create_associated_token_account(
+            &payer.pubkey(),
+            &holder.pubkey(),
+            &tokens.mint,
+            &tokens.token_program,
+        ),
+        token_instruction::mint_to(
+            &tokens.token_program,
+            &tokens.mint,
+            &tokens.voter_account(&holder.pubkey()),
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(banks, &instructions, payer, &[payer]).await.unwrap();
+    holder
+}
+
+async fn balance(banks: &mut BanksClient, account: Pubkey) -> u64 {
+    fetch::<TokenAccount>(banks, account).await.amount
+}
+
+async fn votes(banks: &mut BanksClient, candidate: &str) -> u64 {
+    fetch::<Candidate>(banks, candidate_address(&voting::ID, 1, candidate))
+        .await
+        .candidate_votes
+}
+
+async fn weighted_voting(token_program: Pubkey) {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let now: Clock = banks.get_sysvar().await.unwrap();
+    let poll_end = now.unix_timestamp as u64 + 3_600;
+
+    let tokens = GovernanceTokens {
+        mint: create_mint(&mut banks, &payer, token_program).await,
+        token_program,
+    };
+    let alice = token_holder(&mut banks, &payer, &tokens, 100).await;
+    let bob = token_holder(&mut banks, &payer, &tokens, 30).await;
+
+    let instructions = [
+        initialize_token_poll_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            "Which spread should the DAO fund?",
+            0,
+            poll_end,
+            &tokens,
+        ),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Crunchy"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    // Token-weighted polls don't take one-wallet-one-vote ballots
+    let unweighted = vote_instruction(&voting::ID, &alice.pubkey(), 1, "Smooth");
+    assert!(send(&mut banks, &[unweighted], &alice, &[&alice])
+        .await
+        .is_err());
+
+    let vote = vote_weighted_instruction(&voting::ID, &alice.pubkey(), 1, "Smooth", &tokens, 100);
+    send(&mut banks, &[vote], &alice, &[&alice]).await.unwrap();
+    assert_eq!(votes(&mut banks, "Smooth").await, 100);
+    assert_eq!(
+        balance(&mut banks, tokens.voter_account(&alice.pubkey())).await,
+        0
+    );
+    assert_eq!(
+        balance(&mut banks, escrow_address(&voting::ID, 1)).await,
+        100
+    );
+
+    // Bob votes with part of his balance and hands the rest to Carol; tokens
+    // only ever count once, so the tally can't exceed the supply
+    let vote = vote_weighted_instruction(&voting::ID, &bob.pubkey(), 1, "Crunchy", &tokens, 10);
+    send(&mut banks, &[vote], &bob, &[&bob]).await.unwrap();
+    let carol = token_holder(&mut banks, &payer, &tokens, 0).await;
+    let handoff = token_instruction::transfer_checked(
+        &token_program,
+        &tokens.voter_account(&bob.pubkey()),
+        &tokens.mint,
+        &tokens.voter_account(&carol.pubkey()),
+        &bob.pubkey(),
+        &[],
+        20,
+        DECIMALS,
+    )
+    .unwrap();
+    send(&mut banks, &[handoff], &bob, &[&bob]).await.unwrap();
+    let too_much =
+        vote_weighted_instruction(&voting::ID, &carol.pubkey(), 1, "Crunchy", &tokens, 21);
+    assert!(send(&mut banks, &[too_much], &carol, &[&carol])
+        .await
+        .is_err());
+    let vote = vote_weighted_instruction(&voting::ID, &carol.pubkey(), 1, "Crunchy", &tokens, 20);
+    send(&mut banks, &[vote], &carol, &[&carol]).await.unwrap();
+    assert_eq!(votes(&mut banks, "Crunchy").await, 30);
+
+    // Changing a weighted vote moves its whole weight
+    let change = change_vote_instruction(&voting::ID, &alice.pubkey(), 1, "Smooth", "Crunchy");
+    send(&mut banks, &[change], &alice, &[&alice])
+        .await
+        .unwrap();
+    assert_eq!(votes(&mut banks, "Smooth").await, 0);
+    assert_eq!(votes(&mut banks, "Crunchy").await, 130);
+
+    // Escrowed tokens stay locked until the poll ends
+    let withdraw = withdraw_escrow_instruction(&voting::ID, &alice.pubkey(), 1, &tokens);
+    assert!(send(
+        &mut banks,
+        std::slice::from_ref(&withdraw),
+        &alice,
+        &[&alice]
+    )
+    .await
+    .is_err());
+
+    set_time(&mut context, poll_end as i64 + 1).await;
+    let finalize = finalize_poll_instruction(
+        &voting::ID,
+        1,
+        PollKind::SingleChoice,
+        &["Smooth", "Crunchy"],
+    );
+    send(&mut banks, &[finalize], &payer, &[&payer])
+        .await
+        .unwrap();
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert_eq!(poll.winner.as_deref(), Some("Crunchy"));
+
+    // The receipt stays until its tokens are withdrawn
+    let close = close_receipt_instruction(&voting::ID, &alice.pubkey(), 1);
+    assert!(
+        send(&mut banks, std::slice::from_ref(&close), &alice, &[&alice])
+            .await
+            .is_err()
+    );
+
+    resend(&mut banks, &[withdraw], &alice, &[&alice])
+        .await
+        .unwrap();
+    assert_eq!(
+        balance(&mut banks, tokens.voter_account(&alice.pubkey())).await,
+        100
+    );
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &alice.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.weight, 100);
+    assert_eq!(receipt.escrowed, 0);
+
+    // A second withdrawal has nothing left to return
+    let again = withdraw_escrow_instruction(&voting::ID, &alice.pubkey(), 1, &tokens);
+    let again = [
+        again,
+        system_instruction::transfer(&alice.pubkey(), &payer.pubkey(), 1),
+    ];
+    assert!(send(&mut banks, &again, &alice, &[&alice]).await.is_err());
+
+    resend(&mut banks, &[close], &alice, &[&alice])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_weighted_voting_with_spl_token() {
+    weighted_voting(spl_token::ID).await;
+}
+
+#[tokio::test]
+async fn test_weighted_voting_with_token_2022() {
+    weighted_voting(spl_token_2022::ID).await;
+}
+
+#[tokio::test]
+async fn test_weighted_vote_rejects_other_mints() {
+    let (mut banks, payer) = common::start().await;
+    let tokens = GovernanceTokens {
+        mint: create_mint(&mut banks, &payer, spl_token::ID).await,
+        token_program: spl_token::ID,
+    };
+    let other = GovernanceTokens {
+        mint: create_mint(&mut banks, &payer, spl_token::ID).await,
+        token_program: spl_token::ID,
+    };
+    let voter = token_holder(&mut banks, &payer, &other, 50).await;
+
+    let instructions = [
+        initialize_token_poll_instruction(
+            &voting::ID,
+            &payer.pubkey(),
+            1,
+            "Poll",
+            0,
+            u64::MAX,
+            &tokens,
+        ),
+        initialize_candidate_instruction(&voting::ID, &payer.pubkey(), 1, "Smooth"),
+    ];
+    send(&mut banks, &instructions, &payer, &[&payer])
+        .await
+        .unwrap();
+
+    let vote = vote_weighted_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", &other, 50);
+    assert!(send(&mut banks, &[vote], &voter, &[&voter]).await.is_err());
+}
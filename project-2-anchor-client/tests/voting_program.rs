@@ -0,0 +1,204 @@
+// The client's instruction builders against the voting program, run in an
+// in-process bank so no validator or devnet access is needed.
+mod common;
+
+use common::{create_poll, fetch, funded_voter, program_test, send, start};
+use solana_sdk::{
+    nonce::{self, state::Versions},
+    signature::Keypair,
+    signer::Signer,
+    system_instruction,
+};
+use voting::{Candidate, Poll, VoterReceipt};
+use voting_client::{
+    offline::{check_fully_signed, sign_offline, OfflineVote},
+    vote::{
+        candidate_address, change_vote_instruction, initialize_poll_instruction, poll_address,
+        vote_instruction, voter_receipt_address,
+    },
+};
+
+#[tokio::test]
+async fn test_vote_counts_for_candidate() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth", "Crunchy"]).await;
+
+    let poll: Poll = fetch(&mut banks, poll_address(&voting::ID, 1)).await;
+    assert_eq!(poll.poll_id, 1);
+    assert_eq!(poll.candidate_amount, 2);
+
+    let voter = funded_voter(&mut banks, &payer).await;
+    let vote = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+
+    let smooth: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Smooth")).await;
+    let crunchy: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Crunchy")).await;
+    assert_eq!(smooth.candidate_name, "Smooth");
+    assert_eq!(smooth.candidate_votes, 1);
+    assert_eq!(crunchy.candidate_votes, 0);
+}
+
+#[tokio::test]
+async fn test_vote_rejects_unknown_candidate_and_poll() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth"]).await;
+
+    let unknown_candidate = vote_instruction(&voting::ID, &payer.pubkey(), 1, "Chunky");
+    assert!(send(&mut banks, &[unknown_candidate], &payer, &[&payer])
+        .await
+        .is_err());
+
+    let unknown_poll = vote_instruction(&voting::ID, &payer.pubkey(), 2, "Smooth");
+    assert!(send(&mut banks, &[unknown_poll], &payer, &[&payer])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_initialize_rejects_invalid_polls() {
+    let (mut banks, payer) = start().await;
+
+    let backwards = initialize_poll_instruction(&voting::ID, &payer.pubkey(), 1, "Poll", 10, 5);
+    assert!(send(&mut banks, &[backwards], &payer, &[&payer])
+        .await
+        .is_err());
+
+    let description = "x".repeat(voting::MAX_DESCRIPTION_LEN + 1);
+    let too_long = initialize_poll_instruction(&voting::ID, &payer.pubkey(), 1, &description, 0, 5);
+    assert!(send(&mut banks, &[too_long], &payer, &[&payer])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_offline_vote_with_durable_nonce() {
+    let mut context = program_test().start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    create_poll(&mut banks, &payer, 1, &["Crunchy"]).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+
+    let nonce_account = Keypair::new();
+    let rent = banks.get_rent().await.unwrap();
+    let create_nonce = system_instruction::create_nonce_account(
+        &payer.pubkey(),
+        &nonce_account.pubkey(),
+        &payer.pubkey(),
+        rent.minimum_balance(nonce::State::size()),
+    );
+    send(&mut banks, &create_nonce, &payer, &[&payer, &nonce_account])
+        .await
+        .unwrap();
+
+    let account = banks
+        .get_account(nonce_account.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    let nonce = match bincode::deserialize::<Versions>(&account.data)
+        .unwrap()
+        .state()
+    {
+        nonce::State::Initialized(data) => data.blockhash(),
+        nonce::State::Uninitialized => panic!("nonce account is not initialized"),
+    };
+
+    // The nonce can only be advanced once the bank has moved past its blockhash
+    context.warp_to_slot(2).unwrap();
+
+    let vote = OfflineVote {
+        program_id: voting::ID,
+        voter: voter.pubkey(),
+        fee_payer: payer.pubkey(),
+        nonce_account: nonce_account.pubkey(),
+        nonce_authority: payer.pubkey(),
+        poll_id: 1,
+        candidate: "Crunchy".to_string(),
+    };
+    let mut tx = vote.build(nonce, &[]);
+    sign_offline(&mut tx, &voter).unwrap();
+    sign_offline(&mut tx, &payer).unwrap();
+    check_fully_signed(&tx).unwrap();
+    // Durable nonce transactions skip the recent blockhash check, so they
+    // are processed directly instead of through `process_transaction`
+    banks
+        .process_transaction_with_metadata(tx)
+        .await
+        .unwrap()
+        .result
+        .unwrap();
+
+    let crunchy: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Crunchy")).await;
+    assert_eq!(crunchy.candidate_votes, 1);
+}
+
+#[tokio::test]
+async fn test_vote_records_receipt_and_rejects_second_vote() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth", "Crunchy"]).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+
+    let vote = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &voter.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.poll_id, 1);
+    assert_eq!(receipt.voter, voter.pubkey());
+    assert_eq!(receipt.candidate_name, "Smooth");
+
+    let again = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Crunchy");
+    assert!(send(&mut banks, &[again], &voter, &[&voter]).await.is_err());
+    let crunchy: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Crunchy")).await;
+    assert_eq!(crunchy.candidate_votes, 0);
+
+    // The receipt is per poll, so the same wallet can vote elsewhere
+    create_poll(&mut banks, &payer, 2, &["Smooth"]).await;
+    let other_poll = vote_instruction(&voting::ID, &voter.pubkey(), 2, "Smooth");
+    send(&mut banks, &[other_poll], &voter, &[&voter])
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_change_vote_moves_tally() {
+    let (mut banks, payer) = start().await;
+    create_poll(&mut banks, &payer, 1, &["Smooth", "Crunchy"]).await;
+    let voter = funded_voter(&mut banks, &payer).await;
+
+    // Changing before voting fails: there is no receipt yet
+    let early = change_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", "Crunchy");
+    assert!(send(&mut banks, &[early], &voter, &[&voter]).await.is_err());
+
+    let vote = vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth");
+    send(&mut banks, &[vote], &voter, &[&voter]).await.unwrap();
+
+    let same = change_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", "Smooth");
+    assert!(send(&mut banks, &[same], &voter, &[&voter]).await.is_err());
+
+    // The previous candidate must be the one on the receipt
+    let wrong_previous =
+        change_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Crunchy", "Smooth");
+    assert!(send(&mut banks, &[wrong_previous], &voter, &[&voter])
+        .await
+        .is_err());
+
+    let change = change_vote_instruction(&voting::ID, &voter.pubkey(), 1, "Smooth", "Crunchy");
+    send(&mut banks, &[change], &voter, &[&voter])
+        .await
+        .unwrap();
+
+    let smooth: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Smooth")).await;
+    let crunchy: Candidate = fetch(&mut banks, candidate_address(&voting::ID, 1, "Crunchy")).await;
+    assert_eq!(smooth.candidate_votes, 0);
+    assert_eq!(crunchy.candidate_votes, 1);
+    let receipt: VoterReceipt = fetch(
+        &mut banks,
+        voter_receipt_address(&voting::ID, 1, &voter.pubkey()),
+    )
+    .await;
+    assert_eq!(receipt.candidate_name, "Crunchy");
+}
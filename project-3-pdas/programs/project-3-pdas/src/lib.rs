@@ -1,3 +1,4 @@
+#![allow(unexpected_cfgs)]
 use anchor_lang::prelude::*;
 
 // This is your program's public key and it will update
@@ -63,7 +64,7 @@ pub struct CreateEntry<'info> {
         seeds = [title.as_bytes(), owner.key().as_ref()], 
         bump, 
         payer = owner, 
-        space = 8 + 32 + 4 + title.len() + 4 + message.len()
+        space = 8 + 32 + 4 + title.len() + 4 + message.len() + 8
     )]
     pub journal_entry: Account<'info, JournalEntryState>,
     #[account(mut)]
@@ -78,7 +79,7 @@ pub struct UpdateEntry<'info> {
         mut,
         seeds = [title.as_bytes(), owner.key().as_ref()], 
         bump, 
-        realloc = 8 + 32 + 4 + title.len() + 4 + message.len(),
+        realloc = 8 + 32 + 4 + title.len() + 4 + message.len() + 8,
         realloc::payer = owner, 
         realloc::zero = true, 
     )]
@@ -89,11 +90,11 @@ pub struct UpdateEntry<'info> {
 }
 
 #[derive(Accounts)]
-#[instruction(count: u64,title: String)]
+#[instruction(title: String)]
 pub struct DeleteEntry<'info> {
     #[account( 
         mut, 
-        seeds = [&count.to_be_bytes(), title.as_bytes(), owner.key().as_ref()], 
+        seeds = [title.as_bytes(), owner.key().as_ref()], 
         bump, 
         close= owner,
     )]
@@ -0,0 +1,123 @@
+// The journal program in an in-process bank, so `cargo test` needs no
+// validator. The fixtures are shared with the other journal program's suite
+use journal_test_support::{entry_address, fetch_entry, funded, instruction, send};
+use project_3_pdas::JournalEntryState;
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::{
+    account_info::AccountInfo, entrypoint::ProgramResult, pubkey::Pubkey, signature::Keypair,
+    signer::Signer,
+};
+
+// Anchor's entrypoint ties the account slice and account infos to a single
+// lifetime, which the native processor signature doesn't provide
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    project_3_pdas::entry(program_id, accounts, data)
+}
+
+async fn start() -> (BanksClient, Keypair) {
+    let program_test = ProgramTest::new(
+        "project_3_pdas",
+        project_3_pdas::ID,
+        processor!(process_instruction),
+    );
+    let (banks, payer, _) = program_test.start().await;
+    (banks, payer)
+}
+
+#[tokio::test]
+async fn test_create_update_and_delete_entry() {
+    let (mut banks, owner) = start().await;
+    let address = entry_address(&project_3_pdas::ID, "Day one", &owner.pubkey());
+
+    let create = project_3_pdas::instruction::CreateJournalEntry {
+        title: "Day one".to_string(),
+        message: "Hello".to_string(),
+        count: 1,
+    };
+    send(
+        &mut banks,
+        instruction(&project_3_pdas::ID, &owner.pubkey(), "Day one", create),
+        &owner,
+    )
+    .await
+    .unwrap();
+    let entry = fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .unwrap();
+    assert_eq!(entry.owner, owner.pubkey());
+    assert_eq!(entry.title, "Day one");
+    assert_eq!(entry.message, "Hello");
+    assert_eq!(entry.count, 1);
+
+    let update = project_3_pdas::instruction::UpdateJournalEntry {
+        title: "Day one".to_string(),
+        message: "A much longer message than the first one".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&project_3_pdas::ID, &owner.pubkey(), "Day one", update),
+        &owner,
+    )
+    .await
+    .unwrap();
+    let entry = fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .unwrap();
+    assert_eq!(entry.message, "A much longer message than the first one");
+    assert_eq!(entry.count, 1);
+
+    let delete = project_3_pdas::instruction::DeleteJournalEntry {
+        title: "Day one".to_string(),
+    };
+    send(
+        &mut banks,
+        instruction(&project_3_pdas::ID, &owner.pubkey(), "Day one", delete),
+        &owner,
+    )
+    .await
+    .unwrap();
+    assert!(fetch_entry::<JournalEntryState>(&mut banks, address)
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_entries_are_per_owner() {
+    let (mut banks, owner) = start().await;
+    let other = funded(&mut banks, &owner).await;
+    let create = project_3_pdas::instruction::CreateJournalEntry {
+        title: "Shared title".to_string(),
+        message: "Mine".to_string(),
+        count: 1,
+    };
+    send(
+        &mut banks,
+        instruction(&project_3_pdas::ID, &owner.pubkey(), "Shared title", create),
+        &owner,
+    )
+    .await
+    .unwrap();
+
+    // Another wallet can't update an entry it doesn't own, whose address it
+    // can't derive from its own key
+    let update = project_3_pdas::instruction::UpdateJournalEntry {
+        title: "Shared title".to_string(),
+        message: "Not yours".to_string(),
+    };
+    let mut ix = instruction(&project_3_pdas::ID, &other.pubkey(), "Shared title", update);
+    ix.accounts[0].pubkey = entry_address(&project_3_pdas::ID, "Shared title", &owner.pubkey());
+    assert!(send(&mut banks, ix, &other).await.is_err());
+
+    let entry = fetch_entry::<JournalEntryState>(
+        &mut banks,
+        entry_address(&project_3_pdas::ID, "Shared title", &owner.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(entry.message, "Mine");
+}
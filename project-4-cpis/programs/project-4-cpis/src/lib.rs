@@ -1,47 +1,456 @@
 #![allow(unexpected_cfgs)]
 use anchor_lang::prelude::*;
+// Every instruction works with mints and accounts of either the legacy token
+// program or Token-2022
 use anchor_spl::{
-    token::{Mint, Token, TokenAccount},
-    token_interface::{self, TokenInterface},
+    associated_token::AssociatedToken,
+    metadata::{mpl_token_metadata::types::DataV2, Metadata},
+    token_2022::Token2022,
+    token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
 declare_id!("Eau8idrUiVQD3r9eEcqhxdhCZVdXAcxQQ4YWNFFnC42P");
 
+// The most decimals a mint may have; 10^18 base units still fit in a u64
+pub const MAX_DECIMALS: u8 = 18;
+
+// Length of the window a daily mint limit applies to
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
 #[program]
 pub mod project_4_cpis {
-    use anchor_spl::token_interface::{self, MintTo, TransferChecked};
+    use anchor_lang::system_program::{self, CreateAccount};
+    use anchor_spl::metadata::{
+        self as token_metadata, CreateMetadataAccountsV3, UpdateMetadataAccountsV2,
+    };
+    use anchor_spl::token_interface::{
+        self,
+        spl_token_2022::instruction::AuthorityType,
+        spl_token_2022::{
+            self,
+            extension::{transfer_fee::TransferFeeConfig, ExtensionType},
+        },
+        spl_token_metadata_interface::state::TokenMetadata,
+        BurnChecked, CloseAccount, FreezeAccount, InitializeMint2, InterestBearingMintInitialize,
+        MetadataPointerInitialize, MintTo, NonTransferableMintInitialize,
+        PermanentDelegateInitialize, SetAuthority, ThawAccount, TokenMetadataInitialize,
+        TransferChecked, TransferCheckedWithFee, TransferFeeInitialize,
+        WithdrawWithheldTokensFromAccounts, WithdrawWithheldTokensFromMint,
+    };
 
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let token_project = &mut ctx.accounts.token_project;
-        token_project.mint_authority = ctx.accounts.mint_authority.key();
+        token_project.admin = ctx.accounts.admin.key();
+        token_project.creator = ctx.accounts.admin.key();
+        token_project.mint = ctx.accounts.mint.key();
+        token_project.bump = ctx.bumps.token_project;
+        Ok(())
+    }
+
+    // Offer the admin role to `new_admin`, who has to accept it. A new
+    // proposal replaces any earlier one
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.token_project.pending_admin = Some(new_admin);
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let token_project = &mut ctx.accounts.token_project;
+        token_project.admin = ctx.accounts.new_admin.key();
+        token_project.pending_admin = None;
+        Ok(())
+    }
+
+    // Remove the mint's mint authority for good, fixing its supply
+    pub fn renounce_authority(ctx: Context<RenounceAuthority>) -> Result<()> {
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.token_project.to_account_info(),
+            account_or_mint: ctx.accounts.mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_interface::set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+
+        let token_project = &mut ctx.accounts.token_project;
+        token_project.renounced = true;
+        token_project.pending_admin = None;
+        token_project.pending_mint_authority = None;
+        Ok(())
+    }
+
+    // Offer the mint authority itself to `new_authority`, who has to accept
+    // it. A new proposal replaces any earlier one
+    pub fn propose_mint_authority(
+        ctx: Context<ProposeMintAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.token_project.pending_mint_authority = Some(new_authority);
+        Ok(())
+    }
+
+    // Move the mint authority to the accepting key. It then mints directly,
+    // outside the project's supply cap and rate limits
+    pub fn accept_mint_authority(ctx: Context<AcceptMintAuthority>) -> Result<()> {
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.token_project.to_account_info(),
+            account_or_mint: ctx.accounts.mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_interface::set_authority(
+            cpi_ctx,
+            AuthorityType::MintTokens,
+            Some(ctx.accounts.new_authority.key()),
+        )?;
+
+        ctx.accounts.token_project.pending_mint_authority = None;
+        Ok(())
+    }
+
+    pub fn create_mint(ctx: Context<CreateMint>, decimals: u8) -> Result<()> {
+        // Anchor already initialized it with the mint:: constraint, at the
+        // address the project was initialized with
+        ctx.accounts.token_project.decimals = decimals;
+        Ok(())
+    }
+
+    // Create a Token-2022 mint with the chosen extensions, all under the
+    // token project's authority
+    pub fn create_mint_with_extensions(
+        ctx: Context<CreateMintWithExtensions>,
+        decimals: u8,
+        extensions: MintExtensions,
+    ) -> Result<()> {
+        let token_project = ctx.accounts.token_project.to_account_info();
+        let mint = ctx.accounts.mint.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let authority = token_project.key();
+
+        // Extensions are initialized before the mint, in an account sized
+        // for exactly those extensions
+        let mut extension_types = Vec::new();
+        if extensions.transfer_fee.is_some() {
+            extension_types.push(ExtensionType::TransferFeeConfig);
+        }
+        if extensions.interest_rate.is_some() {
+            extension_types.push(ExtensionType::InterestBearingConfig);
+        }
+        if extensions.non_transferable {
+            extension_types.push(ExtensionType::NonTransferable);
+        }
+        if extensions.permanent_delegate.is_some() {
+            extension_types.push(ExtensionType::PermanentDelegate);
+        }
+        if extensions.metadata.is_some() {
+            extension_types.push(ExtensionType::MetadataPointer);
+        }
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(
+            &extension_types,
+        )?;
+        // The embedded metadata is appended once the mint is initialized, so
+        // the account is funded for it up front
+        let metadata_len = match &extensions.metadata {
+            Some(metadata) => TokenMetadata {
+                name: metadata.name.clone(),
+                symbol: metadata.symbol.clone(),
+                uri: metadata.uri.clone(),
+                ..Default::default()
+            }
+            .tlv_size_of()?,
+            None => 0,
+        };
+        let lamports = Rent::get()?.minimum_balance(space + metadata_len);
+        let create_account = CreateAccount {
+            from: ctx.accounts.admin.to_account_info(),
+            to: mint.clone(),
+        };
+        system_program::create_account(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                create_account,
+            ),
+            lamports,
+            space as u64,
+            token_program.key,
+        )?;
+
+        if let Some(fee) = &extensions.transfer_fee {
+            let cpi_accounts = TransferFeeInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::transfer_fee_initialize(
+                CpiContext::new(token_program.clone(), cpi_accounts),
+                Some(&authority),
+                Some(&authority),
+                fee.basis_points,
+                fee.maximum_fee,
+            )?;
+        }
+        if let Some(rate) = extensions.interest_rate {
+            let cpi_accounts = InterestBearingMintInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::interest_bearing_mint_initialize(
+                CpiContext::new(token_program.clone(), cpi_accounts),
+                Some(authority),
+                rate,
+            )?;
+        }
+        if extensions.non_transferable {
+            let cpi_accounts = NonTransferableMintInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::non_transferable_mint_initialize(CpiContext::new(
+                token_program.clone(),
+                cpi_accounts,
+            ))?;
+        }
+        if let Some(delegate) = &extensions.permanent_delegate {
+            let cpi_accounts = PermanentDelegateInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::permanent_delegate_initialize(
+                CpiContext::new(token_program.clone(), cpi_accounts),
+                delegate,
+            )?;
+        }
+        if extensions.metadata.is_some() {
+            // The metadata lives in the mint account itself
+            let cpi_accounts = MetadataPointerInitialize {
+                token_program_id: token_program.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::metadata_pointer_initialize(
+                CpiContext::new(token_program.clone(), cpi_accounts),
+                Some(authority),
+                Some(mint.key()),
+            )?;
+        }
+
+        let cpi_accounts = InitializeMint2 { mint: mint.clone() };
+        token_interface::initialize_mint2(
+            CpiContext::new(token_program.clone(), cpi_accounts),
+            decimals,
+            &authority,
+            Some(&authority),
+        )?;
+
+        if let Some(metadata) = &extensions.metadata {
+            // Only the mint authority may initialize the metadata, so the
+            // token project signs for itself
+            let seeds = ctx.accounts.token_project.signer_seeds();
+            let signer_seeds = &[&seeds[..]];
+            let cpi_accounts = TokenMetadataInitialize {
+                program_id: token_program.clone(),
+                metadata: mint.clone(),
+                update_authority: token_project.clone(),
+                mint_authority: token_project.clone(),
+                mint: mint.clone(),
+            };
+            token_interface::token_metadata_initialize(
+                CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds),
+                metadata.name.clone(),
+                metadata.symbol.clone(),
+                metadata.uri.clone(),
+            )?;
+        }
+
+        let token_project = &mut ctx.accounts.token_project;
+        token_project.decimals = decimals;
+        token_project.extensions = EnabledExtensions {
+            transfer_fee: extensions.transfer_fee.is_some(),
+            interest_bearing: extensions.interest_rate.is_some(),
+            non_transferable: extensions.non_transferable,
+            permanent_delegate: extensions.permanent_delegate.is_some(),
+            metadata: extensions.metadata.is_some(),
+        };
+        Ok(())
+    }
+
+    // Move the transfer fees withheld in the mint and in the given token
+    // accounts (remaining accounts) to the destination
+    pub fn withdraw_withheld<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawWithheld<'info>>,
+    ) -> Result<()> {
+        let token_program = ctx.accounts.token_program.to_account_info();
+        // The token project is the withdraw withheld authority
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = WithdrawWithheldTokensFromMint {
+            token_program_id: token_program.clone(),
+            mint: ctx.accounts.mint.to_account_info(),
+            destination: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.token_project.to_account_info(),
+        };
+        token_interface::withdraw_withheld_tokens_from_mint(CpiContext::new_with_signer(
+            token_program.clone(),
+            cpi_accounts,
+            signer_seeds,
+        ))?;
+
+        if !ctx.remaining_accounts.is_empty() {
+            let cpi_accounts = WithdrawWithheldTokensFromAccounts {
+                token_program_id: token_program.clone(),
+                mint: ctx.accounts.mint.to_account_info(),
+                destination: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.token_project.to_account_info(),
+            };
+            token_interface::withdraw_withheld_tokens_from_accounts(
+                CpiContext::new_with_signer(token_program, cpi_accounts, signer_seeds),
+                ctx.remaining_accounts.to_vec(),
+            )?;
+        }
+        Ok(())
+    }
+
+    // Attach Metaplex metadata to the project's mint. The token project signs
+    // as mint authority and becomes the metadata's update authority
+    pub fn create_metadata(
+        ctx: Context<CreateMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = CreateMetadataAccountsV3 {
+            metadata: ctx.accounts.metadata.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            mint_authority: ctx.accounts.token_project.to_account_info(),
+            payer: ctx.accounts.admin.to_account_info(),
+            update_authority: ctx.accounts.token_project.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_metadata_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_metadata::create_metadata_accounts_v3(
+            cpi_ctx,
+            metadata_data(name, symbol, uri),
+            true,
+            true,
+            None,
+        )?;
         Ok(())
     }
 
-    pub fn create_mint(_ctx: Context<CreateMint>, _decimals: u8) -> Result<()> {
-        // Anchor already initialized it with the mint:: constraint
+    pub fn update_metadata(
+        ctx: Context<UpdateMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        // The token project is the update authority
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = UpdateMetadataAccountsV2 {
+            metadata: ctx.accounts.metadata.to_account_info(),
+            update_authority: ctx.accounts.token_project.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_metadata_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_metadata::update_metadata_accounts_v2(
+            cpi_ctx,
+            None,
+            Some(metadata_data(name, symbol, uri)),
+            None,
+            None,
+        )?;
         Ok(())
     }
 
     pub fn create_token_account(_ctx: Context<CreateTokenAccount>) -> Result<()> {
-        // Anchor already initialized it with the token:: constraint
+        // Anchor already created the owner's associated token account, unless
+        // it existed
+        Ok(())
+    }
+
+    // Cap the total supply minted through the project and how much may be
+    // minted per epoch and per day; `None` lifts a limit
+    pub fn set_mint_policy(
+        ctx: Context<SetMintPolicy>,
+        max_supply: Option<u64>,
+        epoch_limit: Option<u64>,
+        daily_limit: Option<u64>,
+    ) -> Result<()> {
+        let token_project = &mut ctx.accounts.token_project;
+        // A cap below what was already minted could never be honored
+        if let Some(max_supply) = max_supply {
+            require!(
+                token_project.minted <= max_supply,
+                ErrorCode::SupplyCapExceeded
+            );
+        }
+        token_project.max_supply = max_supply;
+        token_project.epoch_limit = epoch_limit;
+        token_project.daily_limit = daily_limit;
         Ok(())
     }
 
     pub fn mint_tokens(ctx: Context<MintTokens>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        ctx.accounts
+            .token_project
+            .record_mint(amount, clock.epoch, clock.unix_timestamp)?;
+
         let cpi_accounts = MintTo {
             mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.token_account.to_account_info(),
-            authority: ctx.accounts.mint_authority.to_account_info(),
+            authority: ctx.accounts.token_project.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        // The token project PDA is the mint authority and signs for itself
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
         token_interface::mint_to(cpi_ctx, amount)?;
         Ok(())
     }
 
     pub fn transfer_tokens(ctx: Context<TokenTransfer>, amount: u64) -> Result<()> {
+        let decimals = ctx.accounts.mint.decimals;
+        // A mint with a transfer fee withholds part of the amount, which
+        // transfer_checked_with_fee makes the sender acknowledge
+        let mint = ctx.accounts.mint.to_account_info();
+        if let Ok(config) = token_interface::get_mint_extension_data::<TransferFeeConfig>(&mint) {
+            let fee = config
+                .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                .ok_or(ErrorCode::TransferFeeOverflow)?;
+            let cpi_accounts = TransferCheckedWithFee {
+                token_program_id: ctx.accounts.token_program.to_account_info(),
+                source: ctx.accounts.from.to_account_info(),
+                mint,
+                destination: ctx.accounts.to.to_account_info(),
+                authority: ctx.accounts.signer.to_account_info(),
+            };
+            let cpi_ctx =
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+            return token_interface::transfer_checked_with_fee(cpi_ctx, amount, decimals, fee);
+        }
+
         let cpi_accounts = TransferChecked {
             from: ctx.accounts.from.to_account_info(),
             mint: ctx.accounts.mint.to_account_info(),
@@ -51,10 +460,105 @@ pub mod project_4_cpis {
 
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        let decimals = ctx.accounts.mint.decimals;
         token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
         Ok(())
     }
+
+    // Burn tokens from the signer's own account
+    pub fn burn_tokens(ctx: Context<BurnTokens>, amount: u64) -> Result<()> {
+        let cpi_accounts = BurnChecked {
+            mint: ctx.accounts.mint.to_account_info(),
+            from: ctx.accounts.token_account.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::burn_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+        Ok(())
+    }
+
+    pub fn freeze_token_account(ctx: Context<FreezeTokenAccount>) -> Result<()> {
+        // The token project PDA is the freeze authority and signs for itself
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = FreezeAccount {
+            account: ctx.accounts.token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            authority: ctx.accounts.token_project.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_interface::freeze_account(cpi_ctx)?;
+        Ok(())
+    }
+
+    pub fn thaw_token_account(ctx: Context<ThawTokenAccount>) -> Result<()> {
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = ThawAccount {
+            account: ctx.accounts.token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            authority: ctx.accounts.token_project.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_interface::thaw_account(cpi_ctx)?;
+        Ok(())
+    }
+
+    // Close the signer's empty token account, refunding its rent to
+    // `destination`
+    pub fn close_token_account(ctx: Context<CloseTokenAccount>) -> Result<()> {
+        let cpi_accounts = CloseAccount {
+            account: ctx.accounts.token_account.to_account_info(),
+            destination: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+        token_interface::close_account(cpi_ctx)?;
+        Ok(())
+    }
+
+    // Hand the freeze authority from the token project to another key, or
+    // remove it for good with `None`. The mint authority leaves the project
+    // only through accept_mint_authority or renounce_authority, so it can't
+    // be handed to a key that never agreed to hold it
+    pub fn set_freeze_authority(
+        ctx: Context<SetFreezeAuthority>,
+        new_authority: Option<Pubkey>,
+    ) -> Result<()> {
+        let seeds = ctx.accounts.token_project.signer_seeds();
+        let signer_seeds = &[&seeds[..]];
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.token_project.to_account_info(),
+            account_or_mint: ctx.accounts.mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        );
+        token_interface::set_authority(cpi_ctx, AuthorityType::FreezeAccount, new_authority)?;
+        Ok(())
+    }
+}
+
+// Fungible tokens carry no royalties, creators, collection or uses
+fn metadata_data(name: String, symbol: String, uri: String) -> DataV2 {
+    DataV2 {
+        name,
+        symbol,
+        uri,
+        seller_fee_basis_points: 0,
+        creators: None,
+        collection: None,
+        uses: None,
+    }
 }
 
 #[derive(Accounts)]
@@ -62,74 +566,604 @@ pub struct Initialize<'info> {
     #[account(
         init,
         payer = payer,
-        space = 8 + 32,
-        seeds = [b"token-project", mint_authority.key().as_ref()],
+        space = 8 + TokenProjectAccount::INIT_SPACE,
+        seeds = [b"token-project", admin.key().as_ref(), mint.key().as_ref()],
         bump,
     )]
     pub token_project: Account<'info, TokenProjectAccount>,
+    /// CHECK: The address of the project's mint, recorded here and created
+    /// by create_mint or create_mint_with_extensions
+    pub mint: UncheckedAccount<'info>,
     #[account(mut)]
     pub payer: Signer<'info>,
-    pub mint_authority: Signer<'info>,
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        constraint = token_project.pending_admin == Some(new_admin.key())
+            @ ErrorCode::NotPendingAuthority,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeMintAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(mint::authority = token_project)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptMintAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = mint,
+        constraint = token_project.pending_mint_authority == Some(new_authority.key())
+            @ ErrorCode::NotPendingAuthority,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(
+        mut,
+        mint::authority = token_project,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub new_authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct RenounceAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(
+        mut,
+        mint::authority = token_project,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(decimals: u8)]
 pub struct CreateMint<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+        constraint = decimals <= MAX_DECIMALS @ ErrorCode::InvalidDecimals,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
     #[account(
         init,
-        payer = mint_authority,
-        mint::decimals = 9,
-        mint::authority = mint_authority,
+        payer = admin,
+        mint::decimals = decimals,
+        mint::authority = token_project,
+        mint::freeze_authority = token_project,
+        mint::token_program = token_program,
     )]
-    pub mint: Account<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
     #[account(mut)]
-    pub mint_authority: Signer<'info>,
+    pub admin: Signer<'info>,
     pub rent: Sysvar<'info, Rent>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CreateTokenAccount<'info> {
+#[instruction(decimals: u8)]
+pub struct CreateMintWithExtensions<'info> {
     #[account(
-        init,
-        payer = owner,
-        token::mint = mint,
-        token::authority = owner,
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+        constraint = decimals <= MAX_DECIMALS @ ErrorCode::InvalidDecimals,
     )]
-    pub token_account: Account<'info, TokenAccount>,
-    pub mint: Account<'info, Mint>,
+    pub token_project: Account<'info, TokenProjectAccount>,
+    // Created by the instruction, sized for its extensions
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub mint: Signer<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    // Extensions only exist under Token-2022
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawWithheld<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint, token::token_program = token_program)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    pub admin: Signer<'info>,
+    pub token_program: Program<'info, Token2022>,
+}
+
+// Token-2022 extensions to create a mint with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct MintExtensions {
+    pub transfer_fee: Option<TransferFeeArgs>,
+    // Yearly rate in basis points
+    pub interest_rate: Option<i16>,
+    pub non_transferable: bool,
+    // May transfer or burn tokens from any account of the mint
+    pub permanent_delegate: Option<Pubkey>,
+    // Embedded in the mint, which the metadata pointer points at
+    pub metadata: Option<MetadataArgs>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TransferFeeArgs {
+    pub basis_points: u16,
+    // Cap on the fee of a single transfer, in base units
+    pub maximum_fee: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MetadataArgs {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+#[derive(Accounts)]
+pub struct CreateMetadata<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(mint::authority = token_project)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: The mint's metadata PDA, created by the token metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
     pub rent: Sysvar<'info, Rent>,
-    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct MintTokens<'info> {
+pub struct UpdateMetadata<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: The mint's metadata PDA, owned and checked by the token
+    /// metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTokenAccount<'info> {
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Any wallet or program may own an associated token account
+    pub owner: UncheckedAccount<'info>,
     #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    pub payer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMintPolicy<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MintTokens<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+        constraint = !token_project.renounced @ ErrorCode::AuthorityRenounced,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    // The owner's associated token account, created if it doesn't exist yet
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Any wallet or program may receive the minted tokens
+    pub owner: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
+    // Pays for the token account when it has to be created
     #[account(mut)]
-    pub token_account: Account<'info, TokenAccount>,
-    pub mint_authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
+    pub payer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct TokenProjectAccount {
-    pub mint_authority: Pubkey,
+    // May create the project's mint and mint tokens; the account itself is
+    // the mint authority
+    pub admin: Pubkey,
+    // The first admin, whose key seeds the project's address for good
+    pub creator: Pubkey,
+    // The project's mint, which also seeds its address, registered by
+    // create_mint
+    pub mint: Pubkey,
+    // Offered the admin role, until they accept it
+    pub pending_admin: Option<Pubkey>,
+    // Offered the mint authority, until they accept it
+    pub pending_mint_authority: Option<Pubkey>,
+    // Set once the mint authority has been removed and the supply is fixed
+    pub renounced: bool,
+    pub bump: u8,
+    // Decimals of the mint created for the project
+    pub decimals: u8,
+    // Token-2022 extensions the mint was created with
+    pub extensions: EnabledExtensions,
+    // Mint policy: caps on the total minted and on what may be minted per
+    // epoch and per day
+    pub max_supply: Option<u64>,
+    pub epoch_limit: Option<u64>,
+    pub daily_limit: Option<u64>,
+    // Everything minted through the project so far
+    pub minted: u64,
+    // Minted in the current epoch and day, and which ones those are
+    pub epoch_minted: u64,
+    pub current_epoch: u64,
+    pub day_minted: u64,
+    pub current_day: i64,
+}
+
+impl TokenProjectAccount {
+    // Seeds the project signs with, as mint, freeze and fee authority
+    fn signer_seeds(&self) -> [&[u8]; 4] {
+        [
+            b"token-project",
+            self.creator.as_ref(),
+            self.mint.as_ref(),
+            std::slice::from_ref(&self.bump),
+        ]
+    }
+
+    // Count `amount` against the supply cap and the rate limits, failing if
+    // it would exceed any of them
+    fn record_mint(&mut self, amount: u64, epoch: u64, unix_timestamp: i64) -> Result<()> {
+        let minted = self
+            .minted
+            .checked_add(amount)
+            .ok_or(ErrorCode::SupplyCapExceeded)?;
+        if let Some(max_supply) = self.max_supply {
+            require!(minted <= max_supply, ErrorCode::SupplyCapExceeded);
+        }
+
+        // The per-epoch and per-day counters start over in each new window
+        if epoch != self.current_epoch {
+            self.current_epoch = epoch;
+            self.epoch_minted = 0;
+        }
+        let day = unix_timestamp.div_euclid(SECONDS_PER_DAY);
+        if day != self.current_day {
+            self.current_day = day;
+            self.day_minted = 0;
+        }
+        let epoch_minted = self
+            .epoch_minted
+            .checked_add(amount)
+            .ok_or(ErrorCode::RateLimited)?;
+        let day_minted = self
+            .day_minted
+            .checked_add(amount)
+            .ok_or(ErrorCode::RateLimited)?;
+        if let Some(epoch_limit) = self.epoch_limit {
+            require!(epoch_minted <= epoch_limit, ErrorCode::RateLimited);
+        }
+        if let Some(daily_limit) = self.daily_limit {
+            require!(day_minted <= daily_limit, ErrorCode::RateLimited);
+        }
+
+        self.minted = minted;
+        self.epoch_minted = epoch_minted;
+        self.day_minted = day_minted;
+        Ok(())
+    }
+}
+
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct EnabledExtensions {
+    pub transfer_fee: bool,
+    pub interest_bearing: bool,
+    pub non_transferable: bool,
+    pub permanent_delegate: bool,
+    pub metadata: bool,
 }
 
 #[derive(Accounts)]
-pub struct TokenTransfer<'info> {
+pub struct BurnTokens<'info> {
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = owner,
+        token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    pub owner: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeTokenAccount<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(
+        mint::freeze_authority = token_project,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint, token::token_program = token_program)]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ThawTokenAccount<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(
+        mint::freeze_authority = token_project,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint, token::token_program = token_program)]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTokenAccount<'info> {
+    #[account(
+        mut,
+        token::authority = owner,
+        token::token_program = token_program,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Only receives the account's lamports
     #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+    pub owner: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetFreezeAuthority<'info> {
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = admin,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
+    #[account(mut, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub admin: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct TokenTransfer<'info> {
+    // Only the project's own mint moves through it
+    #[account(
+        seeds = [
+            b"token-project",
+            token_project.creator.as_ref(),
+            token_project.mint.as_ref(),
+        ],
+        bump = token_project.bump,
+        has_one = mint,
+    )]
+    pub token_project: Account<'info, TokenProjectAccount>,
     pub signer: Signer<'info>,
-    pub mint: InterfaceAccount<'info, token_interface::Mint>,
+    // Pays for the recipient's token account when it has to be created
     #[account(mut)]
-    pub from: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub payer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
     #[account(mut)]
-    pub to: InterfaceAccount<'info, token_interface::TokenAccount>,
+    pub from: InterfaceAccount<'info, TokenAccount>,
+    // The recipient's associated token account, created if it doesn't exist
+    // yet
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub to: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Any wallet or program may receive the tokens
+    pub recipient: UncheckedAccount<'info>,
     pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
+
+#[error_code]
+pub enum ErrorCode {
+    #[msg("A mint can have at most 18 decimals.")]
+    InvalidDecimals,
+    #[msg("The transfer fee could not be calculated.")]
+    TransferFeeOverflow,
+    #[msg("Minting this amount would exceed the project's maximum supply.")]
+    SupplyCapExceeded,
+    #[msg("Minting this amount would exceed the project's mint rate limit.")]
+    RateLimited,
+    #[msg("Only the proposed authority can accept the role.")]
+    NotPendingAuthority,
+    #[msg("The mint authority was renounced; the supply is fixed.")]
+    AuthorityRenounced,
+}
@@ -0,0 +1,664 @@
+// Helpers shared by the in-process bank tests
+use anchor_lang::{error::ERROR_CODE_OFFSET, AccountDeserialize, InstructionData, ToAccountMetas};
+use project_4_cpis::{ErrorCode, MintExtensions, TokenProjectAccount};
+use solana_program_test::{
+    processor, BanksClient, BanksClientError, ProgramTest, ProgramTestContext,
+};
+use solana_sdk::{
+    account::Account as SolanaAccount,
+    account_info::AccountInfo,
+    bpf_loader,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account, Mint},
+};
+use std::time::Duration;
+
+// Every flow runs against both token programs
+pub const TOKEN_PROGRAMS: [Pubkey; 2] = [spl_token::ID, spl_token_2022::ID];
+
+// Anchor's entrypoint ties the account slice and account infos to a single
+// lifetime, which the native processor signature doesn't provide
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    project_4_cpis::entry(program_id, accounts, data)
+}
+
+fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "project_4_cpis",
+        project_4_cpis::ID,
+        processor!(process_instruction),
+    )
+}
+
+pub async fn start() -> (BanksClient, Keypair) {
+    let (banks, payer, _) = program_test().start().await;
+    (banks, payer)
+}
+
+// For tests that move the clock
+pub async fn start_with_context() -> ProgramTestContext {
+    program_test().start_with_context().await
+}
+
+// The token metadata program only exists as a deployed binary, dumped from a
+// cluster into the fixtures directory with `anchor run fixtures` (see the README)
+pub const TOKEN_METADATA_PROGRAM: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/mpl_token_metadata.so"
+);
+
+pub async fn start_with_token_metadata() -> (BanksClient, Keypair) {
+    let elf = std::fs::read(TOKEN_METADATA_PROGRAM).unwrap_or_else(|e| {
+        panic!(
+            "Error reading the token metadata program from {TOKEN_METADATA_PROGRAM} ({e}); \
+             dump it with `anchor run fixtures` in project-4-cpis"
+        )
+    });
+    let mut program_test = program_test();
+    program_test.add_account(
+        mpl_token_metadata::ID,
+        SolanaAccount {
+            lamports: Rent::default().minimum_balance(elf.len()),
+            data: elf,
+            owner: bpf_loader::ID,
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+    let (banks, payer, _) = program_test.start().await;
+    (banks, payer)
+}
+
+pub async fn send(
+    banks: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = banks.get_latest_blockhash().await?;
+    let tx =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), signers, blockhash);
+    banks.process_transaction(tx).await
+}
+
+// Send a transaction identical to one the bank already processed, such as
+// one that was expected to fail earlier, under a new blockhash; otherwise the
+// bank may answer with the earlier result
+pub async fn resend(
+    banks: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let previous = banks.get_latest_blockhash().await?;
+    let mut blockhash = previous;
+    while blockhash == previous {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        blockhash = banks.get_latest_blockhash().await?;
+    }
+    let tx =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), signers, blockhash);
+    banks.process_transaction(tx).await
+}
+
+pub fn program_error(code: ErrorCode) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(ERROR_CODE_OFFSET + code as u32))
+}
+
+pub async fn funded(banks: &mut BanksClient, payer: &Keypair) -> Keypair {
+    let wallet = Keypair::new();
+    let fund = system_instruction::transfer(&payer.pubkey(), &wallet.pubkey(), LAMPORTS_PER_SOL);
+    send(banks, &[fund], payer, &[payer]).await.unwrap();
+    wallet
+}
+
+// Reads legacy and Token-2022 token accounts alike
+pub async fn token_account(banks: &mut BanksClient, address: Pubkey) -> Account {
+    let account = banks.get_account(address).await.unwrap().unwrap();
+    StateWithExtensions::<Account>::unpack(&account.data)
+        .unwrap()
+        .base
+}
+
+pub async fn token_balance(banks: &mut BanksClient, address: Pubkey) -> u64 {
+    token_account(banks, address).await.amount
+}
+
+pub async fn mint_state(banks: &mut BanksClient, address: Pubkey) -> Mint {
+    let account = banks.get_account(address).await.unwrap().unwrap();
+    StateWithExtensions::<Mint>::unpack(&account.data)
+        .unwrap()
+        .base
+}
+
+pub async fn fetch_token_project(banks: &mut BanksClient, address: Pubkey) -> TokenProjectAccount {
+    let account = banks.get_account(address).await.unwrap().unwrap();
+    TokenProjectAccount::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+// Each project is seeded by its first admin and its mint, so one admin can
+// run several
+pub fn token_project_address(creator: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"token-project", creator.as_ref(), mint.as_ref()],
+        &project_4_cpis::ID,
+    )
+    .0
+}
+
+pub fn initialize_instruction(payer: &Pubkey, admin: &Pubkey, mint: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::Initialize {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            payer: *payer,
+            admin: *admin,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::Initialize {}.data(),
+    }
+}
+
+// The project's address stays derived from its first admin, its creator,
+// after the admin role changes hands
+pub fn propose_authority_instruction(
+    creator: &Pubkey,
+    mint: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::ProposeAuthority {
+            token_project: token_project_address(creator, mint),
+            admin: *admin,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::ProposeAuthority {
+            new_admin: *new_admin,
+        }
+        .data(),
+    }
+}
+
+pub fn accept_authority_instruction(
+    creator: &Pubkey,
+    mint: &Pubkey,
+    new_admin: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::AcceptAuthority {
+            token_project: token_project_address(creator, mint),
+            new_admin: *new_admin,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::AcceptAuthority {}.data(),
+    }
+}
+
+pub fn renounce_authority_instruction(
+    token_program: &Pubkey,
+    creator: &Pubkey,
+    mint: &Pubkey,
+    admin: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::RenounceAuthority {
+            token_project: token_project_address(creator, mint),
+            mint: *mint,
+            admin: *admin,
+            token_program: *token_program,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::RenounceAuthority {}.data(),
+    }
+}
+
+pub fn propose_mint_authority_instruction(
+    creator: &Pubkey,
+    mint: &Pubkey,
+    admin: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::ProposeMintAuthority {
+            token_project: token_project_address(creator, mint),
+            mint: *mint,
+            admin: *admin,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::ProposeMintAuthority {
+            new_authority: *new_authority,
+        }
+        .data(),
+    }
+}
+
+pub fn accept_mint_authority_instruction(
+    token_program: &Pubkey,
+    creator: &Pubkey,
+    mint: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::AcceptMintAuthority {
+            token_project: token_project_address(creator, mint),
+            mint: *mint,
+            new_authority: *new_authority,
+            token_program: *token_program,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::AcceptMintAuthority {}.data(),
+    }
+}
+
+pub fn create_mint_instruction(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    admin: &Pubkey,
+    decimals: u8,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::CreateMint {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            admin: *admin,
+            rent: sysvar::rent::ID,
+            token_program: *token_program,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::CreateMint { decimals }.data(),
+    }
+}
+
+pub fn create_mint_with_extensions_instruction(
+    mint: &Pubkey,
+    admin: &Pubkey,
+    decimals: u8,
+    extensions: MintExtensions,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::CreateMintWithExtensions {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            admin: *admin,
+            token_program: spl_token_2022::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::CreateMintWithExtensions {
+            decimals,
+            extensions,
+        }
+        .data(),
+    }
+}
+
+// Withdraw the fees withheld in the mint and in `sources` to `destination`
+pub fn withdraw_withheld_instruction(
+    mint: &Pubkey,
+    destination: &Pubkey,
+    admin: &Pubkey,
+    sources: &[Pubkey],
+) -> Instruction {
+    let mut accounts = project_4_cpis::accounts::WithdrawWithheld {
+        token_project: token_project_address(admin, mint),
+        mint: *mint,
+        destination: *destination,
+        admin: *admin,
+        token_program: spl_token_2022::ID,
+    }
+    .to_account_metas(None);
+    accounts.extend(
+        sources
+            .iter()
+            .map(|source| AccountMeta::new(*source, false)),
+    );
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts,
+        data: project_4_cpis::instruction::WithdrawWithheld {}.data(),
+    }
+}
+
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    mpl_token_metadata::accounts::Metadata::find_pda(mint).0
+}
+
+pub fn create_metadata_instruction(
+    mint: &Pubkey,
+    admin: &Pubkey,
+    name: &str,
+    symbol: &str,
+    uri: &str,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::CreateMetadata {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            metadata: metadata_address(mint),
+            admin: *admin,
+            token_metadata_program: mpl_token_metadata::ID,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::CreateMetadata {
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn update_metadata_instruction(
+    mint: &Pubkey,
+    admin: &Pubkey,
+    name: &str,
+    symbol: &str,
+    uri: &str,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::UpdateMetadata {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            metadata: metadata_address(mint),
+            admin: *admin,
+            token_metadata_program: mpl_token_metadata::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::UpdateMetadata {
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn create_token_account_instruction(
+    token_program: &Pubkey,
+    payer: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::CreateTokenAccount {
+            token_account: get_associated_token_address_with_program_id(owner, mint, token_program),
+            mint: *mint,
+            owner: *owner,
+            payer: *payer,
+            token_program: *token_program,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::CreateTokenAccount {}.data(),
+    }
+}
+
+pub fn set_mint_policy_instruction(
+    mint: &Pubkey,
+    admin: &Pubkey,
+    max_supply: Option<u64>,
+    epoch_limit: Option<u64>,
+    daily_limit: Option<u64>,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::SetMintPolicy {
+            token_project: token_project_address(admin, mint),
+            admin: *admin,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::SetMintPolicy {
+            max_supply,
+            epoch_limit,
+            daily_limit,
+        }
+        .data(),
+    }
+}
+
+// Mint into `owner`'s associated token account, which the admin pays for
+// if it has to be created
+pub fn mint_tokens_instruction(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    admin: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::MintTokens {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            token_account: get_associated_token_address_with_program_id(owner, mint, token_program),
+            owner: *owner,
+            admin: *admin,
+            payer: *admin,
+            token_program: *token_program,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::MintTokens { amount }.data(),
+    }
+}
+
+// `creator` is the project's first admin, whose key seeds its address. The
+// tokens land in `recipient`'s associated token account, which the signer
+// pays for if it has to be created
+pub fn transfer_tokens_instruction(
+    token_program: &Pubkey,
+    creator: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    from: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::TokenTransfer {
+            token_project: token_project_address(creator, mint),
+            signer: *owner,
+            payer: *owner,
+            mint: *mint,
+            from: *from,
+            to: get_associated_token_address_with_program_id(recipient, mint, token_program),
+            recipient: *recipient,
+            token_program: *token_program,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::TransferTokens { amount }.data(),
+    }
+}
+
+pub fn burn_tokens_instruction(
+    token_program: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::BurnTokens {
+            mint: *mint,
+            token_account: *token_account,
+            owner: *owner,
+            token_program: *token_program,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::BurnTokens { amount }.data(),
+    }
+}
+
+// Freeze `token_account`, or thaw it if `freeze` is false
+pub fn freeze_instruction(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    token_account: &Pubkey,
+    admin: &Pubkey,
+    freeze: bool,
+) -> Instruction {
+    let token_project = token_project_address(admin, mint);
+    let (accounts, data) = if freeze {
+        (
+            project_4_cpis::accounts::FreezeTokenAccount {
+                token_project,
+                mint: *mint,
+                token_account: *token_account,
+                admin: *admin,
+                token_program: *token_program,
+            }
+            .to_account_metas(None),
+            project_4_cpis::instruction::FreezeTokenAccount {}.data(),
+        )
+    } else {
+        (
+            project_4_cpis::accounts::ThawTokenAccount {
+                token_project,
+                mint: *mint,
+                token_account: *token_account,
+                admin: *admin,
+                token_program: *token_program,
+            }
+            .to_account_metas(None),
+            project_4_cpis::instruction::ThawTokenAccount {}.data(),
+        )
+    };
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts,
+        data,
+    }
+}
+
+pub fn close_token_account_instruction(
+    token_program: &Pubkey,
+    token_account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::CloseTokenAccount {
+            token_account: *token_account,
+            destination: *destination,
+            owner: *owner,
+            token_program: *token_program,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::CloseTokenAccount {}.data(),
+    }
+}
+
+pub fn set_freeze_authority_instruction(
+    token_program: &Pubkey,
+    mint: &Pubkey,
+    admin: &Pubkey,
+    new_authority: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: project_4_cpis::ID,
+        accounts: project_4_cpis::accounts::SetFreezeAuthority {
+            token_project: token_project_address(admin, mint),
+            mint: *mint,
+            admin: *admin,
+            token_program: *token_program,
+        }
+        .to_account_metas(None),
+        data: project_4_cpis::instruction::SetFreezeAuthority { new_authority }.data(),
+    }
+}
+
+// Initialize a token project for a new admin and create its mint
+pub async fn create_project(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    token_program: &Pubkey,
+    decimals: u8,
+) -> (Keypair, Pubkey) {
+    let admin = funded(banks, payer).await;
+    let mint = Keypair::new();
+    let initialize = initialize_instruction(&admin.pubkey(), &admin.pubkey(), &mint.pubkey());
+    let create_mint =
+        create_mint_instruction(token_program, &mint.pubkey(), &admin.pubkey(), decimals);
+    send(banks, &[initialize, create_mint], &admin, &[&admin, &mint])
+        .await
+        .unwrap();
+    (admin, mint.pubkey())
+}
+
+// Initialize a token project for a new admin and create its Token-2022 mint
+// with `extensions`
+pub async fn create_project_with_extensions(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    decimals: u8,
+    extensions: MintExtensions,
+) -> (Keypair, Pubkey) {
+    let admin = funded(banks, payer).await;
+    let mint = Keypair::new();
+    let initialize = initialize_instruction(&admin.pubkey(), &admin.pubkey(), &mint.pubkey());
+    let create_mint = create_mint_with_extensions_instruction(
+        &mint.pubkey(),
+        &admin.pubkey(),
+        decimals,
+        extensions,
+    );
+    send(banks, &[initialize, create_mint], &admin, &[&admin, &mint])
+        .await
+        .unwrap();
+    (admin, mint.pubkey())
+}
+
+// Create `owner`'s associated token account for `mint`
+pub async fn create_ata(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    token_program: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Pubkey {
+    let create = create_associated_token_account(&payer.pubkey(), owner, mint, token_program);
+    send(banks, &[create], payer, &[payer]).await.unwrap();
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
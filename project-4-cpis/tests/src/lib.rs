@@ -1,4 +1,18 @@
 #[cfg(test)]
+mod bank;
+#[cfg(test)]
+mod test_authority;
+#[cfg(test)]
+mod test_bank;
+#[cfg(test)]
 mod test_e2e;
 #[cfg(test)]
+mod test_extensions;
+#[cfg(test)]
 mod test_initialize;
+#[cfg(test)]
+mod test_metadata;
+#[cfg(test)]
+mod test_mint_policy;
+#[cfg(test)]
+mod test_token_lifecycle;
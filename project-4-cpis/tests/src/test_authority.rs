@@ -0,0 +1,371 @@
+// Handing the admin role and the mint authority over in two steps, and
+// renouncing the mint authority
+use crate::bank::*;
+use project_4_cpis::ErrorCode;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+#[tokio::test]
+async fn test_two_step_handover() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 0).await;
+        let project = token_project_address(&admin.pubkey(), &mint);
+        let account = create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+        let successor = funded(&mut banks, &payer).await;
+
+        let propose = propose_authority_instruction(
+            &admin.pubkey(),
+            &mint,
+            &admin.pubkey(),
+            &successor.pubkey(),
+        );
+        send(&mut banks, &[propose], &admin, &[&admin])
+            .await
+            .unwrap();
+        let token_project = fetch_token_project(&mut banks, project).await;
+        assert_eq!(token_project.pending_admin, Some(successor.pubkey()));
+        assert_eq!(token_project.admin, admin.pubkey());
+
+        // Until they accept, the successor holds no power
+        let mut mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &mint,
+            &payer.pubkey(),
+            &successor.pubkey(),
+            1,
+        );
+        mint_tokens.accounts[0].pubkey = project;
+        assert!(send(
+            &mut banks,
+            &[mint_tokens.clone()],
+            &successor,
+            &[&successor]
+        )
+        .await
+        .is_err());
+
+        let accept = accept_authority_instruction(&admin.pubkey(), &mint, &successor.pubkey());
+        send(&mut banks, &[accept], &successor, &[&successor])
+            .await
+            .unwrap();
+        let token_project = fetch_token_project(&mut banks, project).await;
+        assert_eq!(token_project.admin, successor.pubkey());
+        assert_eq!(token_project.creator, admin.pubkey());
+        assert_eq!(token_project.pending_admin, None);
+
+        // The project keeps its address and still signs as mint authority,
+        // now for the successor only
+        resend(&mut banks, &[mint_tokens], &successor, &[&successor])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, account).await, 1);
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 2);
+        assert!(send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .is_err());
+        let propose =
+            propose_authority_instruction(&admin.pubkey(), &mint, &admin.pubkey(), &admin.pubkey());
+        assert!(send(&mut banks, &[propose], &admin, &[&admin])
+            .await
+            .is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_only_the_proposed_key_accepts() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let first = funded(&mut banks, &payer).await;
+    let second = funded(&mut banks, &payer).await;
+
+    // Nothing to accept before a proposal
+    let accept = accept_authority_instruction(&admin.pubkey(), &mint, &first.pubkey());
+    let err = send(&mut banks, &[accept], &first, &[&first])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::NotPendingAuthority));
+
+    let propose =
+        propose_authority_instruction(&admin.pubkey(), &mint, &admin.pubkey(), &first.pubkey());
+    send(&mut banks, &[propose], &admin, &[&admin])
+        .await
+        .unwrap();
+    let accept = accept_authority_instruction(&admin.pubkey(), &mint, &second.pubkey());
+    let err = send(&mut banks, &[accept], &second, &[&second])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::NotPendingAuthority));
+
+    // A new proposal replaces the first
+    let propose =
+        propose_authority_instruction(&admin.pubkey(), &mint, &admin.pubkey(), &second.pubkey());
+    send(&mut banks, &[propose], &admin, &[&admin])
+        .await
+        .unwrap();
+    // Both accepts were already rejected under this blockhash
+    let accept = accept_authority_instruction(&admin.pubkey(), &mint, &first.pubkey());
+    let err = resend(&mut banks, &[accept], &first, &[&first])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::NotPendingAuthority));
+    let accept = accept_authority_instruction(&admin.pubkey(), &mint, &second.pubkey());
+    resend(&mut banks, &[accept], &second, &[&second])
+        .await
+        .unwrap();
+    let token_project =
+        fetch_token_project(&mut banks, token_project_address(&admin.pubkey(), &mint)).await;
+    assert_eq!(token_project.admin, second.pubkey());
+}
+
+#[tokio::test]
+async fn test_project_records_its_mint_before_it_is_created() {
+    let (mut banks, payer) = start().await;
+    let admin = funded(&mut banks, &payer).await;
+    let mint = Keypair::new();
+    let project = token_project_address(&admin.pubkey(), &mint.pubkey());
+    let initialize = initialize_instruction(&admin.pubkey(), &admin.pubkey(), &mint.pubkey());
+    send(&mut banks, &[initialize], &admin, &[&admin])
+        .await
+        .unwrap();
+    assert_eq!(
+        fetch_token_project(&mut banks, project).await.mint,
+        mint.pubkey()
+    );
+
+    // The project already answers to its admin
+    let successor = funded(&mut banks, &payer).await;
+    let propose = propose_authority_instruction(
+        &admin.pubkey(),
+        &mint.pubkey(),
+        &admin.pubkey(),
+        &successor.pubkey(),
+    );
+    send(&mut banks, &[propose], &admin, &[&admin])
+        .await
+        .unwrap();
+
+    // Only the recorded mint can be created for it
+    let other = Keypair::new();
+    let mut create_mint =
+        create_mint_instruction(&spl_token::ID, &other.pubkey(), &admin.pubkey(), 6);
+    create_mint.accounts[0].pubkey = project;
+    assert!(send(&mut banks, &[create_mint], &admin, &[&admin, &other])
+        .await
+        .is_err());
+    let create_mint = create_mint_instruction(&spl_token::ID, &mint.pubkey(), &admin.pubkey(), 6);
+    send(&mut banks, &[create_mint], &admin, &[&admin, &mint])
+        .await
+        .unwrap();
+    let token_project = fetch_token_project(&mut banks, project).await;
+    assert_eq!(token_project.mint, mint.pubkey());
+    assert_eq!(token_project.decimals, 6);
+}
+
+#[tokio::test]
+async fn test_only_the_admin_proposes() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let impostor = funded(&mut banks, &payer).await;
+
+    let propose = propose_authority_instruction(
+        &admin.pubkey(),
+        &mint,
+        &impostor.pubkey(),
+        &impostor.pubkey(),
+    );
+    assert!(send(&mut banks, &[propose], &impostor, &[&impostor])
+        .await
+        .is_err());
+    let token_project =
+        fetch_token_project(&mut banks, token_project_address(&admin.pubkey(), &mint)).await;
+    assert_eq!(token_project.pending_admin, None);
+}
+
+#[tokio::test]
+async fn test_renounce_fixes_the_supply() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 0).await;
+        let account = create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 10);
+        send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap();
+
+        // Only the admin can renounce
+        let impostor = funded(&mut banks, &payer).await;
+        let renounce = renounce_authority_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &mint,
+            &impostor.pubkey(),
+        );
+        assert!(send(&mut banks, &[renounce], &impostor, &[&impostor])
+            .await
+            .is_err());
+
+        let renounce =
+            renounce_authority_instruction(&token_program, &admin.pubkey(), &mint, &admin.pubkey());
+        send(&mut banks, &[renounce], &admin, &[&admin])
+            .await
+            .unwrap();
+        let token_project =
+            fetch_token_project(&mut banks, token_project_address(&admin.pubkey(), &mint)).await;
+        assert!(token_project.renounced);
+        let state = mint_state(&mut banks, mint).await;
+        assert_eq!(state.mint_authority, None.into());
+        assert_eq!(state.supply, 10);
+
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 1);
+        let err = send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap_err()
+            .unwrap();
+        assert_eq!(err, program_error(ErrorCode::AuthorityRenounced));
+        assert_eq!(token_balance(&mut banks, account).await, 10);
+    }
+}
+
+#[tokio::test]
+async fn test_two_step_mint_authority_handover() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 0).await;
+        let project = token_project_address(&admin.pubkey(), &mint);
+        let account = create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+        let treasury = funded(&mut banks, &payer).await;
+        let impostor = funded(&mut banks, &payer).await;
+
+        // Only the admin proposes
+        let propose = propose_mint_authority_instruction(
+            &admin.pubkey(),
+            &mint,
+            &impostor.pubkey(),
+            &impostor.pubkey(),
+        );
+        assert!(send(&mut banks, &[propose], &impostor, &[&impostor])
+            .await
+            .is_err());
+
+        let propose = propose_mint_authority_instruction(
+            &admin.pubkey(),
+            &mint,
+            &admin.pubkey(),
+            &treasury.pubkey(),
+        );
+        send(&mut banks, &[propose], &admin, &[&admin])
+            .await
+            .unwrap();
+        let token_project = fetch_token_project(&mut banks, project).await;
+        assert_eq!(
+            token_project.pending_mint_authority,
+            Some(treasury.pubkey())
+        );
+        assert_eq!(
+            mint_state(&mut banks, mint).await.mint_authority,
+            Some(project).into()
+        );
+
+        // Only the proposed key accepts
+        let accept = accept_mint_authority_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &mint,
+            &impostor.pubkey(),
+        );
+        let err = send(&mut banks, &[accept], &impostor, &[&impostor])
+            .await
+            .unwrap_err()
+            .unwrap();
+        assert_eq!(err, program_error(ErrorCode::NotPendingAuthority));
+
+        let accept = accept_mint_authority_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &mint,
+            &treasury.pubkey(),
+        );
+        send(&mut banks, &[accept], &treasury, &[&treasury])
+            .await
+            .unwrap();
+        let token_project = fetch_token_project(&mut banks, project).await;
+        assert_eq!(token_project.pending_mint_authority, None);
+        assert_eq!(token_project.admin, admin.pubkey());
+        assert_eq!(
+            mint_state(&mut banks, mint).await.mint_authority,
+            Some(treasury.pubkey()).into()
+        );
+
+        // The treasury mints directly, and the project no longer can
+        let mint_to = spl_token_2022::instruction::mint_to(
+            &token_program,
+            &mint,
+            &account,
+            &treasury.pubkey(),
+            &[],
+            5,
+        )
+        .unwrap();
+        send(&mut banks, &[mint_to], &treasury, &[&treasury])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, account).await, 5);
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 1);
+        assert!(send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .is_err());
+
+        // Nor can it propose the authority it gave away
+        let propose = propose_mint_authority_instruction(
+            &admin.pubkey(),
+            &mint,
+            &admin.pubkey(),
+            &admin.pubkey(),
+        );
+        assert!(send(&mut banks, &[propose], &admin, &[&admin])
+            .await
+            .is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_renounce_withdraws_the_mint_authority_proposal() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let treasury = funded(&mut banks, &payer).await;
+
+    let propose = propose_mint_authority_instruction(
+        &admin.pubkey(),
+        &mint,
+        &admin.pubkey(),
+        &treasury.pubkey(),
+    );
+    let renounce =
+        renounce_authority_instruction(&spl_token::ID, &admin.pubkey(), &mint, &admin.pubkey());
+    send(&mut banks, &[propose, renounce], &admin, &[&admin])
+        .await
+        .unwrap();
+
+    let accept = accept_mint_authority_instruction(
+        &spl_token::ID,
+        &admin.pubkey(),
+        &mint,
+        &treasury.pubkey(),
+    );
+    let err = send(&mut banks, &[accept], &treasury, &[&treasury])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::NotPendingAuthority));
+    assert_eq!(
+        mint_state(&mut banks, mint).await.mint_authority,
+        None.into()
+    );
+}
@@ -0,0 +1,606 @@
+// The end-to-end flow against the program in an in-process bank, so
+// `cargo test` needs no validator. Every flow runs once per token program
+use crate::bank::*;
+use project_4_cpis::ErrorCode;
+use solana_sdk::{instruction::AccountMeta, pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account::get_associated_token_address_with_program_id;
+
+#[tokio::test]
+async fn test_initialize_records_admin() {
+    let (mut banks, payer) = start().await;
+    let admin = Keypair::new();
+    let mint = Pubkey::new_unique();
+
+    let initialize = initialize_instruction(&payer.pubkey(), &admin.pubkey(), &mint);
+    send(&mut banks, &[initialize], &payer, &[&payer, &admin])
+        .await
+        .unwrap();
+
+    let (address, bump) = Pubkey::find_program_address(
+        &[b"token-project", admin.pubkey().as_ref(), mint.as_ref()],
+        &project_4_cpis::ID,
+    );
+    let token_project = fetch_token_project(&mut banks, address).await;
+    assert_eq!(token_project.admin, admin.pubkey());
+    assert_eq!(token_project.creator, admin.pubkey());
+    assert_eq!(token_project.mint, mint);
+    assert_eq!(token_project.bump, bump);
+}
+
+// The flow `anchor test` used to run against a validator: each step in its
+// own transaction, with the admin paying for the owner's account and the
+// transfer creating the recipient's
+#[tokio::test]
+async fn test_end_to_end() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let admin = funded(&mut banks, &payer).await;
+        let owner = funded(&mut banks, &payer).await;
+        let recipient = funded(&mut banks, &payer).await;
+        let mint = Keypair::new();
+        let project = token_project_address(&admin.pubkey(), &mint.pubkey());
+
+        let initialize = initialize_instruction(&admin.pubkey(), &admin.pubkey(), &mint.pubkey());
+        send(&mut banks, &[initialize], &admin, &[&admin])
+            .await
+            .unwrap();
+        let create_mint =
+            create_mint_instruction(&token_program, &mint.pubkey(), &admin.pubkey(), 9);
+        send(&mut banks, &[create_mint], &admin, &[&admin, &mint])
+            .await
+            .unwrap();
+        let mint = mint.pubkey();
+        let state = mint_state(&mut banks, mint).await;
+        assert_eq!(state.decimals, 9);
+        assert_eq!(state.mint_authority, Some(project).into());
+        assert_eq!(fetch_token_project(&mut banks, project).await.decimals, 9);
+
+        let create_token_account = create_token_account_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &mint,
+            &owner.pubkey(),
+        );
+        send(&mut banks, &[create_token_account], &admin, &[&admin])
+            .await
+            .unwrap();
+        let owner_account =
+            get_associated_token_address_with_program_id(&owner.pubkey(), &mint, &token_program);
+        assert_eq!(
+            token_account(&mut banks, owner_account).await.owner,
+            owner.pubkey()
+        );
+
+        // 1 token with 9 decimals
+        let mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &mint,
+            &owner.pubkey(),
+            &admin.pubkey(),
+            1_000_000_000,
+        );
+        send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap();
+
+        let recipient_account = get_associated_token_address_with_program_id(
+            &recipient.pubkey(),
+            &mint,
+            &token_program,
+        );
+        assert!(banks
+            .get_account(recipient_account)
+            .await
+            .unwrap()
+            .is_none());
+        let transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint,
+            &owner_account,
+            &recipient.pubkey(),
+            500_000_000,
+        );
+        send(&mut banks, &[transfer], &owner, &[&owner])
+            .await
+            .unwrap();
+
+        assert_eq!(token_balance(&mut banks, owner_account).await, 500_000_000);
+        assert_eq!(
+            token_balance(&mut banks, recipient_account).await,
+            500_000_000
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_mint_and_transfer() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+        let owner = funded(&mut banks, &payer).await;
+        let recipient = funded(&mut banks, &payer).await;
+
+        // The mint and both accounts belong to the token program in use
+        let account = banks.get_account(mint).await.unwrap().unwrap();
+        assert_eq!(account.owner, token_program);
+
+        // The owner's associated token account is created client-side, the
+        // recipient's through the program
+        let owner_account =
+            create_ata(&mut banks, &payer, &token_program, &owner.pubkey(), &mint).await;
+        let create_token_account = create_token_account_instruction(
+            &token_program,
+            &recipient.pubkey(),
+            &mint,
+            &recipient.pubkey(),
+        );
+        send(
+            &mut banks,
+            &[create_token_account],
+            &recipient,
+            &[&recipient],
+        )
+        .await
+        .unwrap();
+        let recipient_account = get_associated_token_address_with_program_id(
+            &recipient.pubkey(),
+            &mint,
+            &token_program,
+        );
+        let account = banks.get_account(recipient_account).await.unwrap().unwrap();
+        assert_eq!(account.owner, token_program);
+
+        let mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &mint,
+            &owner.pubkey(),
+            &admin.pubkey(),
+            1_000_000_000,
+        );
+        send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap();
+
+        let transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint,
+            &owner_account,
+            &recipient.pubkey(),
+            400_000_000,
+        );
+        send(&mut banks, &[transfer], &owner, &[&owner])
+            .await
+            .unwrap();
+
+        assert_eq!(token_balance(&mut banks, owner_account).await, 600_000_000);
+        assert_eq!(
+            token_balance(&mut banks, recipient_account).await,
+            400_000_000
+        );
+        assert_eq!(mint_state(&mut banks, mint).await.supply, 1_000_000_000);
+    }
+}
+
+#[tokio::test]
+async fn test_rejects_a_mismatched_token_program() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint) = create_project(&mut banks, &payer, &spl_token::ID, 9).await;
+    let owner_account =
+        create_ata(&mut banks, &payer, &spl_token::ID, &admin.pubkey(), &mint).await;
+
+    // A legacy mint can't be driven through Token-2022
+    let mint_tokens = mint_tokens_instruction(
+        &spl_token_2022::ID,
+        &mint,
+        &admin.pubkey(),
+        &admin.pubkey(),
+        1,
+    );
+    assert!(send(&mut banks, &[mint_tokens], &admin, &[&admin])
+        .await
+        .is_err());
+    assert_eq!(token_balance(&mut banks, owner_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_mints_honor_decimals() {
+    for token_program in TOKEN_PROGRAMS {
+        for decimals in [0u8, 6, 9] {
+            let (mut banks, payer) = start().await;
+            let (admin, mint) = create_project(&mut banks, &payer, &token_program, decimals).await;
+
+            assert_eq!(mint_state(&mut banks, mint).await.decimals, decimals);
+            let token_project =
+                fetch_token_project(&mut banks, token_project_address(&admin.pubkey(), &mint))
+                    .await;
+            assert_eq!(token_project.decimals, decimals);
+
+            let owner = funded(&mut banks, &payer).await;
+            let owner_account =
+                create_ata(&mut banks, &payer, &token_program, &owner.pubkey(), &mint).await;
+            let recipient_account =
+                create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+            let one_token = 10u64.pow(decimals.into());
+            let mint_tokens = mint_tokens_instruction(
+                &token_program,
+                &mint,
+                &owner.pubkey(),
+                &admin.pubkey(),
+                5 * one_token,
+            );
+            send(&mut banks, &[mint_tokens], &admin, &[&admin])
+                .await
+                .unwrap();
+
+            // transfer_tokens passes the mint's own decimals to transfer_checked
+            let transfer = transfer_tokens_instruction(
+                &token_program,
+                &admin.pubkey(),
+                &owner.pubkey(),
+                &mint,
+                &owner_account,
+                &payer.pubkey(),
+                2 * one_token,
+            );
+            send(&mut banks, &[transfer], &owner, &[&owner])
+                .await
+                .unwrap();
+            assert_eq!(
+                token_balance(&mut banks, owner_account).await,
+                3 * one_token
+            );
+            assert_eq!(
+                token_balance(&mut banks, recipient_account).await,
+                2 * one_token
+            );
+
+            // while transfer_checked with any other decimals is refused
+            let mismatched = spl_token_2022::instruction::transfer_checked(
+                &token_program,
+                &owner_account,
+                &mint,
+                &recipient_account,
+                &owner.pubkey(),
+                &[],
+                one_token,
+                decimals + 1,
+            )
+            .unwrap();
+            assert!(send(&mut banks, &[mismatched], &owner, &[&owner])
+                .await
+                .is_err());
+            assert_eq!(
+                token_balance(&mut banks, owner_account).await,
+                3 * one_token
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_rejects_more_than_max_decimals() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let mint = Keypair::new();
+        let initialize = initialize_instruction(&payer.pubkey(), &payer.pubkey(), &mint.pubkey());
+        let create_mint = create_mint_instruction(
+            &token_program,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            project_4_cpis::MAX_DECIMALS + 1,
+        );
+        send(&mut banks, &[initialize], &payer, &[&payer])
+            .await
+            .unwrap();
+
+        let err = send(&mut banks, &[create_mint], &payer, &[&payer, &mint])
+            .await
+            .unwrap_err()
+            .unwrap();
+        assert_eq!(err, program_error(ErrorCode::InvalidDecimals));
+        assert!(banks.get_account(mint.pubkey()).await.unwrap().is_none());
+
+        let create_mint = create_mint_instruction(
+            &token_program,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            project_4_cpis::MAX_DECIMALS,
+        );
+        send(&mut banks, &[create_mint], &payer, &[&payer, &mint])
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_the_project_is_the_mint_authority() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+        assert_eq!(
+            mint_state(&mut banks, mint).await.mint_authority,
+            Some(token_project_address(&admin.pubkey(), &mint)).into()
+        );
+
+        // The admin holds no mint power of their own: only the program can
+        // mint, signing for the project
+        let owner_account =
+            create_ata(&mut banks, &payer, &token_program, &admin.pubkey(), &mint).await;
+        let direct = spl_token_2022::instruction::mint_to(
+            &token_program,
+            &mint,
+            &owner_account,
+            &admin.pubkey(),
+            &[],
+            1,
+        )
+        .unwrap();
+        assert!(send(&mut banks, &[direct], &admin, &[&admin])
+            .await
+            .is_err());
+        assert_eq!(token_balance(&mut banks, owner_account).await, 0);
+    }
+}
+
+#[tokio::test]
+async fn test_only_the_admin_can_mint() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+        let owner_account =
+            create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+        let impostor = funded(&mut banks, &payer).await;
+
+        // Signing as the admin of someone else's project
+        let mut mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &mint,
+            &payer.pubkey(),
+            &impostor.pubkey(),
+            1,
+        );
+        mint_tokens.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint);
+        assert!(send(&mut banks, &[mint_tokens], &impostor, &[&impostor])
+            .await
+            .is_err());
+
+        // Through a project of their own, which isn't the mint's authority
+        let initialize = initialize_instruction(&impostor.pubkey(), &impostor.pubkey(), &mint);
+        send(&mut banks, &[initialize], &impostor, &[&impostor])
+            .await
+            .unwrap();
+        let mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &mint,
+            &payer.pubkey(),
+            &impostor.pubkey(),
+            1,
+        );
+        assert!(send(&mut banks, &[mint_tokens], &impostor, &[&impostor])
+            .await
+            .is_err());
+        assert_eq!(token_balance(&mut banks, owner_account).await, 0);
+
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 1);
+        send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, owner_account).await, 1);
+    }
+}
+
+#[tokio::test]
+async fn test_one_admin_runs_many_projects() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, first_mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+
+        // A second project for the same admin, keyed by its own mint
+        let second = Keypair::new();
+        let second_mint = second.pubkey();
+        let initialize = initialize_instruction(&admin.pubkey(), &admin.pubkey(), &second_mint);
+        let create_mint = create_mint_instruction(&token_program, &second_mint, &admin.pubkey(), 6);
+        send(
+            &mut banks,
+            &[initialize, create_mint],
+            &admin,
+            &[&admin, &second],
+        )
+        .await
+        .unwrap();
+
+        let first_project = token_project_address(&admin.pubkey(), &first_mint);
+        let second_project = token_project_address(&admin.pubkey(), &second_mint);
+        assert_ne!(first_project, second_project);
+        for (project, mint, decimals) in [
+            (first_project, first_mint, 9),
+            (second_project, second_mint, 6),
+        ] {
+            let token_project = fetch_token_project(&mut banks, project).await;
+            assert_eq!(token_project.mint, mint);
+            assert_eq!(token_project.admin, admin.pubkey());
+            assert_eq!(token_project.decimals, decimals);
+            assert_eq!(
+                mint_state(&mut banks, mint).await.mint_authority,
+                Some(project).into()
+            );
+        }
+
+        let owner = funded(&mut banks, &payer).await;
+        let first_account = create_ata(
+            &mut banks,
+            &payer,
+            &token_program,
+            &owner.pubkey(),
+            &first_mint,
+        )
+        .await;
+        let second_account = create_ata(
+            &mut banks,
+            &payer,
+            &token_program,
+            &owner.pubkey(),
+            &second_mint,
+        )
+        .await;
+        let mint_tokens = [
+            mint_tokens_instruction(
+                &token_program,
+                &first_mint,
+                &owner.pubkey(),
+                &admin.pubkey(),
+                1,
+            ),
+            mint_tokens_instruction(
+                &token_program,
+                &second_mint,
+                &owner.pubkey(),
+                &admin.pubkey(),
+                2,
+            ),
+        ];
+        send(&mut banks, &mint_tokens, &admin, &[&admin])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, first_account).await, 1);
+        assert_eq!(token_balance(&mut banks, second_account).await, 2);
+
+        // Neither project can be used with the other's mint
+        let mut mint_tokens = mint_tokens_instruction(
+            &token_program,
+            &second_mint,
+            &owner.pubkey(),
+            &admin.pubkey(),
+            1,
+        );
+        mint_tokens.accounts[0].pubkey = first_project;
+        assert!(send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .is_err());
+        let mut transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &second_mint,
+            &second_account,
+            &payer.pubkey(),
+            1,
+        );
+        transfer.accounts[0].pubkey = first_project;
+        assert!(send(&mut banks, &[transfer], &owner, &[&owner])
+            .await
+            .is_err());
+        assert_eq!(token_balance(&mut banks, second_account).await, 2);
+        assert_eq!(mint_state(&mut banks, second_mint).await.supply, 2);
+    }
+}
+
+#[tokio::test]
+async fn test_create_token_account_is_idempotent() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (_, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+
+        // The payer needn't be the owner, and creating it again is a no-op
+        let owner = Pubkey::new_unique();
+        let address = get_associated_token_address_with_program_id(&owner, &mint, &token_program);
+        for _ in 0..2 {
+            let create_token_account =
+                create_token_account_instruction(&token_program, &payer.pubkey(), &mint, &owner);
+            send(&mut banks, &[create_token_account], &payer, &[&payer])
+                .await
+                .unwrap();
+            let account = token_account(&mut banks, address).await;
+            assert_eq!(account.owner, owner);
+            assert_eq!(account.mint, mint);
+        }
+
+        // Only the owner's associated token account is accepted
+        let mut create_token_account =
+            create_token_account_instruction(&token_program, &payer.pubkey(), &mint, &owner);
+        create_token_account.accounts[0].pubkey = Pubkey::new_unique();
+        assert!(send(&mut banks, &[create_token_account], &payer, &[&payer])
+            .await
+            .is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_mint_and_transfer_create_the_destination() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+        let sponsor = funded(&mut banks, &payer).await;
+        let owner = funded(&mut banks, &payer).await;
+        let recipient = Pubkey::new_unique();
+        let owner_account =
+            get_associated_token_address_with_program_id(&owner.pubkey(), &mint, &token_program);
+        let recipient_account =
+            get_associated_token_address_with_program_id(&recipient, &mint, &token_program);
+        assert!(banks.get_account(owner_account).await.unwrap().is_none());
+
+        // The sponsor, standing in as payer, pays for the owner's account;
+        // the admin only signs
+        let admin_lamports = banks.get_balance(admin.pubkey()).await.unwrap();
+        let sponsor_lamports = banks.get_balance(sponsor.pubkey()).await.unwrap();
+        let mut mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &owner.pubkey(), &admin.pubkey(), 10);
+        mint_tokens.accounts[5] = AccountMeta::new(sponsor.pubkey(), true);
+        send(&mut banks, &[mint_tokens], &sponsor, &[&sponsor, &admin])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, owner_account).await, 10);
+        assert_eq!(
+            banks.get_balance(admin.pubkey()).await.unwrap(),
+            admin_lamports
+        );
+        let rent = banks
+            .get_account(owner_account)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        assert!(banks.get_balance(sponsor.pubkey()).await.unwrap() <= sponsor_lamports - rent);
+
+        // and for the recipient's, which the transfer creates
+        let mut transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint,
+            &owner_account,
+            &recipient,
+            4,
+        );
+        transfer.accounts[2] = AccountMeta::new(sponsor.pubkey(), true);
+        send(&mut banks, &[transfer], &sponsor, &[&sponsor, &owner])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, owner_account).await, 6);
+        assert_eq!(token_balance(&mut banks, recipient_account).await, 4);
+        assert_eq!(
+            token_account(&mut banks, recipient_account).await.owner,
+            recipient
+        );
+
+        // Once it exists, later transfers reuse it
+        let transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint,
+            &owner_account,
+            &recipient,
+            1,
+        );
+        send(&mut banks, &[transfer], &owner, &[&owner])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, recipient_account).await, 5);
+    }
+}
@@ -0,0 +1,315 @@
+// Token-2022 mints created with extensions, in an in-process bank
+use crate::bank::*;
+use project_4_cpis::{EnabledExtensions, MetadataArgs, MintExtensions, TransferFeeArgs};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_2022::{
+    extension::{
+        interest_bearing_mint::InterestBearingConfig, metadata_pointer::MetadataPointer,
+        permanent_delegate::PermanentDelegate, transfer_fee::TransferFeeAmount,
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account, Mint},
+};
+use spl_token_metadata_interface::state::TokenMetadata;
+
+fn transfer_fee(basis_points: u16, maximum_fee: u64) -> MintExtensions {
+    MintExtensions {
+        transfer_fee: Some(TransferFeeArgs {
+            basis_points,
+            maximum_fee,
+        }),
+        ..Default::default()
+    }
+}
+
+async fn withheld(banks: &mut solana_program_test::BanksClient, address: Pubkey) -> u64 {
+    let account = banks.get_account(address).await.unwrap().unwrap();
+    let state = StateWithExtensions::<Account>::unpack(&account.data).unwrap();
+    state
+        .get_extension::<TransferFeeAmount>()
+        .unwrap()
+        .withheld_amount
+        .into()
+}
+
+#[tokio::test]
+async fn test_records_enabled_extensions() {
+    let (mut banks, payer) = start().await;
+    let delegate = Keypair::new();
+    let extensions = MintExtensions {
+        interest_rate: Some(500),
+        permanent_delegate: Some(delegate.pubkey()),
+        metadata: Some(MetadataArgs {
+            name: "Project Token".to_string(),
+            symbol: "PRJ".to_string(),
+            uri: "https://example.com/token.json".to_string(),
+        }),
+        ..Default::default()
+    };
+    let (admin, mint) = create_project_with_extensions(&mut banks, &payer, 6, extensions).await;
+    let project = token_project_address(&admin.pubkey(), &mint);
+
+    let token_project = fetch_token_project(&mut banks, project).await;
+    assert_eq!(token_project.decimals, 6);
+    assert_eq!(
+        token_project.extensions,
+        EnabledExtensions {
+            interest_bearing: true,
+            permanent_delegate: true,
+            metadata: true,
+            ..Default::default()
+        }
+    );
+
+    let account = banks.get_account(mint).await.unwrap().unwrap();
+    assert_eq!(account.owner, spl_token_2022::ID);
+    let state = StateWithExtensions::<Mint>::unpack(&account.data).unwrap();
+    assert_eq!(state.base.decimals, 6);
+    assert_eq!(state.base.mint_authority, Some(project).into());
+
+    let interest = state.get_extension::<InterestBearingConfig>().unwrap();
+    assert_eq!(i16::from(interest.current_rate), 500);
+    assert_eq!(interest.rate_authority, Some(project).try_into().unwrap());
+    let permanent_delegate = state.get_extension::<PermanentDelegate>().unwrap();
+    assert_eq!(
+        permanent_delegate.delegate,
+        Some(delegate.pubkey()).try_into().unwrap()
+    );
+
+    // The metadata pointer points at the mint, which holds the metadata
+    let pointer = state.get_extension::<MetadataPointer>().unwrap();
+    assert_eq!(pointer.metadata_address, Some(mint).try_into().unwrap());
+    let metadata = state.get_variable_len_extension::<TokenMetadata>().unwrap();
+    assert_eq!(metadata.mint, mint);
+    assert_eq!(metadata.update_authority, Some(project).try_into().unwrap());
+    assert_eq!(metadata.name, "Project Token");
+    assert_eq!(metadata.symbol, "PRJ");
+    assert_eq!(metadata.uri, "https://example.com/token.json");
+}
+
+#[tokio::test]
+async fn test_transfer_fees_are_withheld_and_withdrawn() {
+    let (mut banks, payer) = start().await;
+    // 1% per transfer, at most 5,000 base units
+    let (admin, mint) =
+        create_project_with_extensions(&mut banks, &payer, 6, transfer_fee(100, 5_000)).await;
+    let token_project =
+        fetch_token_project(&mut banks, token_project_address(&admin.pubkey(), &mint)).await;
+    assert!(token_project.extensions.transfer_fee);
+
+    let owner = funded(&mut banks, &payer).await;
+    let owner_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &owner.pubkey(),
+        &mint,
+    )
+    .await;
+    let recipient_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &payer.pubkey(),
+        &mint,
+    )
+    .await;
+    let treasury = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &admin.pubkey(),
+        &mint,
+    )
+    .await;
+    let mint_tokens = mint_tokens_instruction(
+        &spl_token_2022::ID,
+        &mint,
+        &owner.pubkey(),
+        &admin.pubkey(),
+        1_000_000,
+    );
+    send(&mut banks, &[mint_tokens], &admin, &[&admin])
+        .await
+        .unwrap();
+
+    // The recipient receives the amount less the fee, which stays withheld
+    // in their account
+    let transfer = transfer_tokens_instruction(
+        &spl_token_2022::ID,
+        &admin.pubkey(),
+        &owner.pubkey(),
+        &mint,
+        &owner_account,
+        &payer.pubkey(),
+        10_000,
+    );
+    send(&mut banks, &[transfer], &owner, &[&owner])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut banks, owner_account).await, 990_000);
+    assert_eq!(token_balance(&mut banks, recipient_account).await, 9_900);
+    assert_eq!(withheld(&mut banks, recipient_account).await, 100);
+
+    // Large transfers pay the maximum fee
+    let transfer = transfer_tokens_instruction(
+        &spl_token_2022::ID,
+        &admin.pubkey(),
+        &owner.pubkey(),
+        &mint,
+        &owner_account,
+        &payer.pubkey(),
+        900_000,
+    );
+    send(&mut banks, &[transfer], &owner, &[&owner])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut banks, recipient_account).await, 904_900);
+    assert_eq!(withheld(&mut banks, recipient_account).await, 5_100);
+
+    // Only the admin can withdraw the withheld fees
+    let impostor = funded(&mut banks, &payer).await;
+    let mut withdraw =
+        withdraw_withheld_instruction(&mint, &treasury, &impostor.pubkey(), &[recipient_account]);
+    withdraw.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint);
+    assert!(send(&mut banks, &[withdraw], &impostor, &[&impostor])
+        .await
+        .is_err());
+
+    let withdraw =
+        withdraw_withheld_instruction(&mint, &treasury, &admin.pubkey(), &[recipient_account]);
+    send(&mut banks, &[withdraw], &admin, &[&admin])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut banks, treasury).await, 5_100);
+    assert_eq!(withheld(&mut banks, recipient_account).await, 0);
+    assert_eq!(token_balance(&mut banks, recipient_account).await, 904_900);
+}
+
+#[tokio::test]
+async fn test_non_transferable_tokens_stay_put() {
+    let (mut banks, payer) = start().await;
+    let extensions = MintExtensions {
+        non_transferable: true,
+        ..Default::default()
+    };
+    let (admin, mint) = create_project_with_extensions(&mut banks, &payer, 0, extensions).await;
+    let owner = funded(&mut banks, &payer).await;
+    let owner_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &owner.pubkey(),
+        &mint,
+    )
+    .await;
+    let recipient_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &payer.pubkey(),
+        &mint,
+    )
+    .await;
+
+    // Minting works, moving the tokens afterwards doesn't
+    let mint_tokens = mint_tokens_instruction(
+        &spl_token_2022::ID,
+        &mint,
+        &owner.pubkey(),
+        &admin.pubkey(),
+        1,
+    );
+    send(&mut banks, &[mint_tokens], &admin, &[&admin])
+        .await
+        .unwrap();
+    let transfer = transfer_tokens_instruction(
+        &spl_token_2022::ID,
+        &admin.pubkey(),
+        &owner.pubkey(),
+        &mint,
+        &owner_account,
+        &payer.pubkey(),
+        1,
+    );
+    assert!(send(&mut banks, &[transfer], &owner, &[&owner])
+        .await
+        .is_err());
+    assert_eq!(token_balance(&mut banks, owner_account).await, 1);
+    assert_eq!(token_balance(&mut banks, recipient_account).await, 0);
+}
+
+#[tokio::test]
+async fn test_permanent_delegate_moves_any_tokens() {
+    let (mut banks, payer) = start().await;
+    let delegate = funded(&mut banks, &payer).await;
+    let extensions = MintExtensions {
+        permanent_delegate: Some(delegate.pubkey()),
+        ..Default::default()
+    };
+    let (admin, mint) = create_project_with_extensions(&mut banks, &payer, 0, extensions).await;
+    let owner = Keypair::new();
+    let owner_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &owner.pubkey(),
+        &mint,
+    )
+    .await;
+    let recipient_account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token_2022::ID,
+        &payer.pubkey(),
+        &mint,
+    )
+    .await;
+    let mint_tokens = mint_tokens_instruction(
+        &spl_token_2022::ID,
+        &mint,
+        &owner.pubkey(),
+        &admin.pubkey(),
+        10,
+    );
+    send(&mut banks, &[mint_tokens], &admin, &[&admin])
+        .await
+        .unwrap();
+
+    // The delegate signs in place of the owner, who never approved it
+    let transfer = transfer_tokens_instruction(
+        &spl_token_2022::ID,
+        &admin.pubkey(),
+        &delegate.pubkey(),
+        &mint,
+        &owner_account,
+        &payer.pubkey(),
+        4,
+    );
+    send(&mut banks, &[transfer], &delegate, &[&delegate])
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut banks, owner_account).await, 6);
+    assert_eq!(token_balance(&mut banks, recipient_account).await, 4);
+}
+
+#[tokio::test]
+async fn test_extensions_require_token_2022() {
+    let (mut banks, payer) = start().await;
+    let mint = Keypair::new();
+    let initialize = initialize_instruction(&payer.pubkey(), &payer.pubkey(), &mint.pubkey());
+    let mut create_mint = create_mint_with_extensions_instruction(
+        &mint.pubkey(),
+        &payer.pubkey(),
+        6,
+        transfer_fee(100, 5_000),
+    );
+    create_mint.accounts[3].pubkey = spl_token::ID;
+    send(&mut banks, &[initialize], &payer, &[&payer])
+        .await
+        .unwrap();
+    assert!(send(&mut banks, &[create_mint], &payer, &[&payer, &mint])
+        .await
+        .is_err());
+    assert!(banks.get_account(mint.pubkey()).await.unwrap().is_none());
+}
@@ -0,0 +1,109 @@
+// Metaplex metadata created through the token metadata program, loaded into
+// an in-process bank from its dumped binary, or its stand-in without one
+use crate::bank::*;
+use mpl_token_metadata::accounts::Metadata;
+use solana_program_test::BanksClient;
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+// The token metadata program pads names, symbols and URIs with NULs
+async fn fetch_metadata(banks: &mut BanksClient, mint: &Pubkey) -> Metadata {
+    let account = banks
+        .get_account(metadata_address(mint))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.owner, mpl_token_metadata::ID);
+    let mut metadata = Metadata::safe_deserialize(&account.data).unwrap();
+    for field in [&mut metadata.name, &mut metadata.symbol, &mut metadata.uri] {
+        *field = field.trim_end_matches('\0').to_string();
+    }
+    metadata
+}
+
+#[tokio::test]
+async fn test_create_and_update_metadata() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start_with_token_metadata().await;
+        let (admin, mint) = create_project(&mut banks, &payer, &token_program, 9).await;
+        let project = token_project_address(&admin.pubkey(), &mint);
+
+        let create = create_metadata_instruction(
+            &mint,
+            &admin.pubkey(),
+            "Project Token",
+            "PRJ",
+            "https://example.com/token.json",
+        );
+        send(&mut banks, &[create], &admin, &[&admin])
+            .await
+            .unwrap();
+        let metadata = fetch_metadata(&mut banks, &mint).await;
+        assert_eq!(metadata.mint, mint);
+        assert_eq!(metadata.update_authority, project);
+        assert!(metadata.is_mutable);
+        assert_eq!(metadata.name, "Project Token");
+        assert_eq!(metadata.symbol, "PRJ");
+        assert_eq!(metadata.uri, "https://example.com/token.json");
+
+        let update = update_metadata_instruction(
+            &mint,
+            &admin.pubkey(),
+            "Renamed Token",
+            "RNM",
+            "https://example.com/renamed.json",
+        );
+        send(&mut banks, &[update], &admin, &[&admin])
+            .await
+            .unwrap();
+        let metadata = fetch_metadata(&mut banks, &mint).await;
+        assert_eq!(metadata.update_authority, project);
+        assert_eq!(metadata.name, "Renamed Token");
+        assert_eq!(metadata.symbol, "RNM");
+        assert_eq!(metadata.uri, "https://example.com/renamed.json");
+    }
+}
+
+#[tokio::test]
+async fn test_only_the_admin_manages_metadata() {
+    let (mut banks, payer) = start_with_token_metadata().await;
+    let (admin, mint) = create_project(&mut banks, &payer, &spl_token::ID, 9).await;
+    let impostor = funded(&mut banks, &payer).await;
+
+    // Signing as the admin of someone else's project
+    let mut create =
+        create_metadata_instruction(&mint, &impostor.pubkey(), "Fake", "FAKE", "https://fake");
+    create.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint);
+    assert!(send(&mut banks, &[create], &impostor, &[&impostor])
+        .await
+        .is_err());
+
+    // Through a project of their own, which isn't the mint's authority
+    let initialize = initialize_instruction(&impostor.pubkey(), &impostor.pubkey(), &mint);
+    send(&mut banks, &[initialize], &impostor, &[&impostor])
+        .await
+        .unwrap();
+    let create =
+        create_metadata_instruction(&mint, &impostor.pubkey(), "Fake", "FAKE", "https://fake");
+    assert!(send(&mut banks, &[create], &impostor, &[&impostor])
+        .await
+        .is_err());
+    assert!(banks
+        .get_account(metadata_address(&mint))
+        .await
+        .unwrap()
+        .is_none());
+
+    let create =
+        create_metadata_instruction(&mint, &admin.pubkey(), "Real", "REAL", "https://real");
+    send(&mut banks, &[create], &admin, &[&admin])
+        .await
+        .unwrap();
+
+    // Nor can they update it: their project isn't the update authority
+    let update =
+        update_metadata_instruction(&mint, &impostor.pubkey(), "Fake", "FAKE", "https://fake");
+    assert!(send(&mut banks, &[update], &impostor, &[&impostor])
+        .await
+        .is_err());
+    assert_eq!(fetch_metadata(&mut banks, &mint).await.name, "Real");
+}
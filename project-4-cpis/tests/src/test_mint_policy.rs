@@ -0,0 +1,244 @@
+// The supply cap and mint rate limits enforced by mint_tokens
+use crate::bank::*;
+use project_4_cpis::{ErrorCode, SECONDS_PER_DAY};
+use solana_program_test::{BanksClient, BanksClientError};
+use solana_sdk::{clock::Clock, pubkey::Pubkey, signature::Keypair, signer::Signer};
+
+async fn mint(
+    banks: &mut BanksClient,
+    admin: &Keypair,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<(), BanksClientError> {
+    let mint_tokens = mint_tokens_instruction(&spl_token::ID, mint, owner, &admin.pubkey(), amount);
+    send(banks, &[mint_tokens], admin, &[admin]).await
+}
+
+async fn set_policy(
+    banks: &mut BanksClient,
+    admin: &Keypair,
+    mint: &Pubkey,
+    max_supply: Option<u64>,
+    epoch_limit: Option<u64>,
+    daily_limit: Option<u64>,
+) -> Result<(), BanksClientError> {
+    let set_policy =
+        set_mint_policy_instruction(mint, &admin.pubkey(), max_supply, epoch_limit, daily_limit);
+    send(banks, &[set_policy], admin, &[admin]).await
+}
+
+#[tokio::test]
+async fn test_supply_cap() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint_address) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token::ID,
+        &payer.pubkey(),
+        &mint_address,
+    )
+    .await;
+    set_policy(&mut banks, &admin, &mint_address, Some(1_000), None, None)
+        .await
+        .unwrap();
+
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 600)
+        .await
+        .unwrap();
+    // One over the cap fails, exactly up to it succeeds
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 401)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::SupplyCapExceeded));
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 400)
+        .await
+        .unwrap();
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 1)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::SupplyCapExceeded));
+
+    let token_project = fetch_token_project(
+        &mut banks,
+        token_project_address(&admin.pubkey(), &mint_address),
+    )
+    .await;
+    assert_eq!(token_project.max_supply, Some(1_000));
+    assert_eq!(token_project.minted, 1_000);
+    assert_eq!(token_balance(&mut banks, account).await, 1_000);
+
+    // The cap can't be lowered below what was already minted
+    let err = set_policy(&mut banks, &admin, &mint_address, Some(999), None, None)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::SupplyCapExceeded));
+    // but can be raised or lifted
+    set_policy(&mut banks, &admin, &mint_address, Some(1_001), None, None)
+        .await
+        .unwrap();
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 1)
+        .await
+        .unwrap();
+    set_policy(&mut banks, &admin, &mint_address, None, None, None)
+        .await
+        .unwrap();
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 5_000)
+        .await
+        .unwrap();
+    assert_eq!(token_balance(&mut banks, account).await, 6_001);
+}
+
+#[tokio::test]
+async fn test_epoch_limit() {
+    let mut context = start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let (admin, mint_address) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    set_policy(&mut banks, &admin, &mint_address, None, Some(100), None)
+        .await
+        .unwrap();
+
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 60)
+        .await
+        .unwrap();
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 41)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::RateLimited));
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 40)
+        .await
+        .unwrap();
+
+    // A new epoch starts a new allowance
+    let clock: Clock = banks.get_sysvar().await.unwrap();
+    context.warp_to_epoch(clock.epoch + 1).unwrap();
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 100)
+        .await
+        .unwrap();
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 1)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::RateLimited));
+
+    let token_project = fetch_token_project(
+        &mut banks,
+        token_project_address(&admin.pubkey(), &mint_address),
+    )
+    .await;
+    assert_eq!(token_project.current_epoch, clock.epoch + 1);
+    assert_eq!(token_project.epoch_minted, 100);
+    assert_eq!(token_project.minted, 200);
+}
+
+#[tokio::test]
+async fn test_daily_limit() {
+    let context = start_with_context().await;
+    let mut banks = context.banks_client.clone();
+    let payer = context.payer.insecure_clone();
+    let (admin, mint_address) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let account = create_ata(
+        &mut banks,
+        &payer,
+        &spl_token::ID,
+        &payer.pubkey(),
+        &mint_address,
+    )
+    .await;
+    set_policy(&mut banks, &admin, &mint_address, None, None, Some(100))
+        .await
+        .unwrap();
+
+    // Start a second before a day ends
+    let mut clock: Clock = banks.get_sysvar().await.unwrap();
+    let day = clock.unix_timestamp.div_euclid(SECONDS_PER_DAY);
+    clock.unix_timestamp = (day + 1) * SECONDS_PER_DAY - 1;
+    context.set_sysvar(&clock);
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 100)
+        .await
+        .unwrap();
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 1)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::RateLimited));
+
+    // The next second is a new day
+    clock.unix_timestamp += 1;
+    context.set_sysvar(&clock);
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 99)
+        .await
+        .unwrap();
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 2)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::RateLimited));
+
+    let token_project = fetch_token_project(
+        &mut banks,
+        token_project_address(&admin.pubkey(), &mint_address),
+    )
+    .await;
+    assert_eq!(token_project.current_day, day + 1);
+    assert_eq!(token_project.day_minted, 99);
+    assert_eq!(token_balance(&mut banks, account).await, 199);
+}
+
+#[tokio::test]
+async fn test_failed_mints_are_not_counted() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint_address) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    set_policy(&mut banks, &admin, &mint_address, Some(100), Some(50), None)
+        .await
+        .unwrap();
+
+    // Within the cap but over the epoch limit
+    let err = mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 51)
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(err, program_error(ErrorCode::RateLimited));
+    let token_project = fetch_token_project(
+        &mut banks,
+        token_project_address(&admin.pubkey(), &mint_address),
+    )
+    .await;
+    assert_eq!(token_project.minted, 0);
+    assert_eq!(token_project.epoch_minted, 0);
+    mint(&mut banks, &admin, &mint_address, &payer.pubkey(), 50)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_only_the_admin_sets_the_mint_policy() {
+    let (mut banks, payer) = start().await;
+    let (admin, mint_address) = create_project(&mut banks, &payer, &spl_token::ID, 0).await;
+    let impostor = funded(&mut banks, &payer).await;
+
+    let mut set_policy =
+        set_mint_policy_instruction(&mint_address, &impostor.pubkey(), None, None, None);
+    set_policy.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint_address);
+    assert!(send(&mut banks, &[set_policy], &impostor, &[&impostor])
+        .await
+        .is_err());
+
+    let set_policy =
+        set_mint_policy_instruction(&mint_address, &admin.pubkey(), Some(1), None, None);
+    send(&mut banks, &[set_policy], &admin, &[&admin])
+        .await
+        .unwrap();
+    let token_project = fetch_token_project(
+        &mut banks,
+        token_project_address(&admin.pubkey(), &mint_address),
+    )
+    .await;
+    assert_eq!(token_project.max_supply, Some(1));
+}
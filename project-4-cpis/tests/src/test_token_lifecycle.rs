@@ -0,0 +1,234 @@
+// Burning, freezing, closing and authority changes, once per token program
+use crate::bank::*;
+use solana_program_test::BanksClient;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_token_2022::state::AccountState;
+
+// A project with `amount` tokens minted to a new owner's associated token
+// account, returning the admin, the mint, the owner and their account
+async fn funded_owner(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    token_program: &Pubkey,
+    amount: u64,
+) -> (Keypair, Pubkey, Keypair, Pubkey) {
+    let (admin, mint) = create_project(banks, payer, token_program, 0).await;
+    let owner = funded(banks, payer).await;
+    let owner_account = create_ata(banks, payer, token_program, &owner.pubkey(), &mint).await;
+    let mint_tokens = mint_tokens_instruction(
+        token_program,
+        &mint,
+        &owner.pubkey(),
+        &admin.pubkey(),
+        amount,
+    );
+    send(banks, &[mint_tokens], &admin, &[&admin])
+        .await
+        .unwrap();
+    (admin, mint, owner, owner_account)
+}
+
+#[tokio::test]
+async fn test_burn_tokens() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (_, mint, owner, owner_account) =
+            funded_owner(&mut banks, &payer, &token_program, 100).await;
+
+        let burn =
+            burn_tokens_instruction(&token_program, &owner.pubkey(), &mint, &owner_account, 30);
+        send(&mut banks, &[burn], &owner, &[&owner]).await.unwrap();
+        assert_eq!(token_balance(&mut banks, owner_account).await, 70);
+        assert_eq!(mint_state(&mut banks, mint).await.supply, 70);
+
+        // Nobody else can burn the owner's tokens, nor can the owner burn
+        // more than they hold
+        let other = funded(&mut banks, &payer).await;
+        let burn =
+            burn_tokens_instruction(&token_program, &other.pubkey(), &mint, &owner_account, 1);
+        assert!(send(&mut banks, &[burn], &other, &[&other]).await.is_err());
+        let burn =
+            burn_tokens_instruction(&token_program, &owner.pubkey(), &mint, &owner_account, 71);
+        assert!(send(&mut banks, &[burn], &owner, &[&owner]).await.is_err());
+        assert_eq!(token_balance(&mut banks, owner_account).await, 70);
+    }
+}
+
+#[tokio::test]
+async fn test_freeze_and_thaw() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint, owner, owner_account) =
+            funded_owner(&mut banks, &payer, &token_program, 10).await;
+        let recipient_account =
+            create_ata(&mut banks, &payer, &token_program, &payer.pubkey(), &mint).await;
+        assert_eq!(
+            mint_state(&mut banks, mint).await.freeze_authority,
+            Some(token_project_address(&admin.pubkey(), &mint)).into()
+        );
+
+        let freeze =
+            freeze_instruction(&token_program, &mint, &owner_account, &admin.pubkey(), true);
+        send(&mut banks, &[freeze], &admin, &[&admin])
+            .await
+            .unwrap();
+        assert_eq!(
+            token_account(&mut banks, owner_account).await.state,
+            AccountState::Frozen
+        );
+        let transfer = transfer_tokens_instruction(
+            &token_program,
+            &admin.pubkey(),
+            &owner.pubkey(),
+            &mint,
+            &owner_account,
+            &payer.pubkey(),
+            1,
+        );
+        assert!(send(
+            &mut banks,
+            std::slice::from_ref(&transfer),
+            &owner,
+            &[&owner]
+        )
+        .await
+        .is_err());
+
+        // Only the admin can thaw it
+        let impostor = funded(&mut banks, &payer).await;
+        let mut thaw = freeze_instruction(
+            &token_program,
+            &mint,
+            &owner_account,
+            &impostor.pubkey(),
+            false,
+        );
+        thaw.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint);
+        assert!(send(&mut banks, &[thaw], &impostor, &[&impostor])
+            .await
+            .is_err());
+
+        let thaw = freeze_instruction(
+            &token_program,
+            &mint,
+            &owner_account,
+            &admin.pubkey(),
+            false,
+        );
+        send(&mut banks, &[thaw], &admin, &[&admin]).await.unwrap();
+        assert_eq!(
+            token_account(&mut banks, owner_account).await.state,
+            AccountState::Initialized
+        );
+        resend(&mut banks, &[transfer], &owner, &[&owner])
+            .await
+            .unwrap();
+        assert_eq!(token_balance(&mut banks, recipient_account).await, 1);
+    }
+}
+
+#[tokio::test]
+async fn test_close_account_refunds_rent() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (_, mint, owner, owner_account) =
+            funded_owner(&mut banks, &payer, &token_program, 10).await;
+        let destination = Keypair::new().pubkey();
+
+        // An account still holding tokens can't be closed
+        let close = close_token_account_instruction(
+            &token_program,
+            &owner_account,
+            &destination,
+            &owner.pubkey(),
+        );
+        assert!(
+            send(&mut banks, std::slice::from_ref(&close), &owner, &[&owner])
+                .await
+                .is_err()
+        );
+
+        let burn =
+            burn_tokens_instruction(&token_program, &owner.pubkey(), &mint, &owner_account, 10);
+        send(&mut banks, &[burn], &owner, &[&owner]).await.unwrap();
+        let rent = banks
+            .get_account(owner_account)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        resend(&mut banks, &[close], &owner, &[&owner])
+            .await
+            .unwrap();
+        assert!(banks.get_account(owner_account).await.unwrap().is_none());
+        assert_eq!(banks.get_balance(destination).await.unwrap(), rent);
+    }
+}
+
+#[tokio::test]
+async fn test_set_freeze_authority() {
+    for token_program in TOKEN_PROGRAMS {
+        let (mut banks, payer) = start().await;
+        let (admin, mint, _, owner_account) =
+            funded_owner(&mut banks, &payer, &token_program, 10).await;
+        let impostor = funded(&mut banks, &payer).await;
+
+        // Only the admin can hand over the freeze authority
+        let mut rotate = set_freeze_authority_instruction(
+            &token_program,
+            &mint,
+            &impostor.pubkey(),
+            Some(impostor.pubkey()),
+        );
+        rotate.accounts[0].pubkey = token_project_address(&admin.pubkey(), &mint);
+        assert!(send(&mut banks, &[rotate], &impostor, &[&impostor])
+            .await
+            .is_err());
+
+        // The new freeze authority freezes directly, without the program
+        let guardian = funded(&mut banks, &payer).await;
+        let rotate = set_freeze_authority_instruction(
+            &token_program,
+            &mint,
+            &admin.pubkey(),
+            Some(guardian.pubkey()),
+        );
+        send(&mut banks, &[rotate], &admin, &[&admin])
+            .await
+            .unwrap();
+        assert_eq!(
+            mint_state(&mut banks, mint).await.freeze_authority,
+            Some(guardian.pubkey()).into()
+        );
+        let freeze = spl_token_2022::instruction::freeze_account(
+            &token_program,
+            &owner_account,
+            &mint,
+            &guardian.pubkey(),
+            &[],
+        )
+        .unwrap();
+        send(&mut banks, &[freeze], &guardian, &[&guardian])
+            .await
+            .unwrap();
+
+        // The mint authority stays with the project, which keeps minting
+        // under its policy
+        let project = token_project_address(&admin.pubkey(), &mint);
+        assert_eq!(
+            mint_state(&mut banks, mint).await.mint_authority,
+            Some(project).into()
+        );
+        let mint_tokens =
+            mint_tokens_instruction(&token_program, &mint, &payer.pubkey(), &admin.pubkey(), 1);
+        send(&mut banks, &[mint_tokens], &admin, &[&admin])
+            .await
+            .unwrap();
+
+        // Once handed over, the project can no longer change it
+        let remove = set_freeze_authority_instruction(&token_program, &mint, &admin.pubkey(), None);
+        assert!(send(&mut banks, &[remove], &admin, &[&admin])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,210 @@
+// The store flows against the program in an in-process bank, so `cargo test`
+// needs no validator
+use anchor_lang::{error::ERROR_CODE_OFFSET, AccountDeserialize, InstructionData, ToAccountMetas};
+use project_5_capstone::{Config, ErrorCode, Store};
+use solana_program_test::{processor, BanksClient, BanksClientError, ProgramTest};
+use solana_sdk::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account,
+};
+
+// Anchor's entrypoint ties the account slice and account infos to a single
+// lifetime, which the native processor signature doesn't provide
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    project_5_capstone::entry(program_id, accounts, data)
+}
+
+async fn start() -> (BanksClient, Keypair) {
+    let program_test = ProgramTest::new(
+        "project_5_capstone",
+        project_5_capstone::ID,
+        processor!(process_instruction),
+    );
+    let (banks, payer, _) = program_test.start().await;
+    (banks, payer)
+}
+
+async fn send(
+    banks: &mut BanksClient,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = banks.get_latest_blockhash().await?;
+    let tx =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), signers, blockhash);
+    banks.process_transaction(tx).await
+}
+
+async fn fetch<T: AccountDeserialize>(banks: &mut BanksClient, address: Pubkey) -> T {
+    let account = banks.get_account(address).await.unwrap().unwrap();
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+fn store_address(authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"store", authority.as_ref()], &project_5_capstone::ID).0
+}
+
+fn register_store_instruction(authority: &Pubkey, name: &str) -> Instruction {
+    Instruction {
+        program_id: project_5_capstone::ID,
+        accounts: project_5_capstone::accounts::RegisterStore {
+            store: store_address(authority),
+            authority: *authority,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_5_capstone::instruction::RegisterStore {
+            name: name.to_string(),
+        }
+        .data(),
+    }
+}
+
+// Create a mint and give `customer` `amount` tokens in their associated
+// token account, returning the mint
+async fn fund_customer(
+    banks: &mut BanksClient,
+    payer: &Keypair,
+    customer: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = Rent::default().minimum_balance(spl_token::state::Mint::LEN);
+    let customer_account = get_associated_token_address(customer, &mint.pubkey());
+    let instructions = [
+        system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            rent,
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::ID,
+        ),
+        spl_token::instruction::initialize_mint2(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &payer.pubkey(),
+            None,
+            6,
+        )
+        .unwrap(),
+        create_associated_token_account(&payer.pubkey(), customer, &mint.pubkey(), &spl_token::ID),
+        spl_token::instruction::mint_to(
+            &spl_token::ID,
+            &mint.pubkey(),
+            &customer_account,
+            &payer.pubkey(),
+            &[],
+            amount,
+        )
+        .unwrap(),
+    ];
+    send(banks, &instructions, payer, &[payer, &mint])
+        .await
+        .unwrap();
+    mint.pubkey()
+}
+
+fn verify_balance_instruction(
+    authority: &Pubkey,
+    customer: &Pubkey,
+    mint: &Pubkey,
+    min_balance: u64,
+) -> Instruction {
+    Instruction {
+        program_id: project_5_capstone::ID,
+        accounts: project_5_capstone::accounts::VerifyBalance {
+            store: store_address(authority),
+            customer_token_account: get_associated_token_address(customer, mint),
+            customer: *customer,
+            authority: *authority,
+            mint: *mint,
+            token_program: spl_token::ID,
+        }
+        .to_account_metas(None),
+        data: project_5_capstone::instruction::VerifyBalance { min_balance }.data(),
+    }
+}
+
+#[tokio::test]
+async fn test_register_store_and_initialize_config() {
+    let (mut banks, authority) = start().await;
+
+    let register = register_store_instruction(&authority.pubkey(), "Initial Store");
+    send(&mut banks, &[register], &authority, &[&authority])
+        .await
+        .unwrap();
+    let store: Store = fetch(&mut banks, store_address(&authority.pubkey())).await;
+    assert_eq!(store.name, "Initial Store");
+    assert_eq!(store.authority, authority.pubkey());
+
+    let config = Pubkey::find_program_address(&[b"config"], &project_5_capstone::ID).0;
+    let initialize = Instruction {
+        program_id: project_5_capstone::ID,
+        accounts: project_5_capstone::accounts::InitializeConfigAndUpdateStore {
+            store: store_address(&authority.pubkey()),
+            config,
+            authority: authority.pubkey(),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: project_5_capstone::instruction::InitializeConfigAndUpdateStore {
+            new_store_name: "Updated Store".to_string(),
+        }
+        .data(),
+    };
+    send(&mut banks, &[initialize], &authority, &[&authority])
+        .await
+        .unwrap();
+
+    let store: Store = fetch(&mut banks, store_address(&authority.pubkey())).await;
+    assert_eq!(store.name, "Updated Store");
+    let config: Config = fetch(&mut banks, config).await;
+    assert_eq!(config.owner, authority.pubkey());
+}
+
+#[tokio::test]
+async fn test_verify_balance() {
+    let (mut banks, authority) = start().await;
+    let customer = Keypair::new();
+    let register = register_store_instruction(&authority.pubkey(), "Store");
+    send(&mut banks, &[register], &authority, &[&authority])
+        .await
+        .unwrap();
+    let mint = fund_customer(&mut banks, &authority, &customer.pubkey(), 100).await;
+
+    // Exactly the minimum balance passes
+    let verify = verify_balance_instruction(&authority.pubkey(), &customer.pubkey(), &mint, 100);
+    send(&mut banks, &[verify], &authority, &[&authority, &customer])
+        .await
+        .unwrap();
+
+    let verify = verify_balance_instruction(&authority.pubkey(), &customer.pubkey(), &mint, 101);
+    let err = send(&mut banks, &[verify], &authority, &[&authority, &customer])
+        .await
+        .unwrap_err()
+        .unwrap();
+    assert_eq!(
+        err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(ERROR_CODE_OFFSET + ErrorCode::InsufficientBalance as u32)
+        )
+    );
+}
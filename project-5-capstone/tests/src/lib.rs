@@ -1,2 +1,4 @@
 #[cfg(test)]
+mod bank_test;
+#[cfg(test)]
 mod e2e_test;